@@ -0,0 +1,13 @@
+fn main() {
+    // The code of the gRPC server is generated from the proto file with a vendored protoc,
+    // so building with the grpc feature doesn't need protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+
+        tonic_build::configure()
+            .build_client(false)
+            .compile(&["proto/market.proto"], &["proto"])
+            .unwrap();
+    }
+}
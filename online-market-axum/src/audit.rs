@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::Request, middleware::Next, response::IntoResponse};
+use online_market_data::audit::{self, AuditContext};
+
+use crate::{request_id, AppState};
+
+/// Middleware that runs the request with the audit context of the authenticated user, so every
+/// change it makes is recorded in the audit log with its actor and request id.
+///
+/// The request id is the one given by the [`request_id`](crate::request_id::request_id) middleware
+pub async fn audit_context<B>(
+    State(state): State<Arc<AppState>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> impl IntoResponse {
+    let actor = state
+        .auth_keys
+        .authenticate(req.headers())
+        .ok()
+        .map(|user| user.dni);
+
+    let context = AuditContext {
+        actor,
+        request_id: request_id::current(),
+    };
+
+    audit::scope(context, next.run(req)).await
+}
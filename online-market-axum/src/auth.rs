@@ -0,0 +1,144 @@
+use std::{error::Error, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Json},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use online_market_model::Roles;
+use serde::{Deserialize, Serialize};
+
+use crate::{handler::build_error_response, AppState};
+
+/// Keys used to sign and verify the access tokens
+pub struct AuthKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Lifetime of the issued tokens in seconds
+    pub token_ttl: i64,
+}
+
+impl AuthKeys {
+    pub fn new(secret: &[u8], token_ttl: i64) -> Self {
+        AuthKeys {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            token_ttl,
+        }
+    }
+
+    /// Returns a signed token for the given user
+    pub fn issue(&self, dni: String, rol: Roles) -> Result<String, jsonwebtoken::errors::Error> {
+        let claims = Claims {
+            sub: dni,
+            rol,
+            exp: (chrono::Utc::now().timestamp() + self.token_ttl) as usize,
+        };
+
+        encode(&Header::default(), &claims, &self.encoding)
+    }
+
+    /// Returns the user of the bearer token of the request
+    pub fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<AuthUser, (StatusCode, Json<serde_json::Value>)> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| unauthorized("MISSING BEARER TOKEN"))?;
+
+        let claims = self
+            .verify(token)
+            .map_err(|_| unauthorized("INVALID OR EXPIRED TOKEN"))?;
+
+        Ok(AuthUser {
+            dni: claims.sub,
+            rol: claims.rol,
+        })
+    }
+
+    fn verify(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        decode::<Claims>(token, &self.decoding, &Validation::default()).map(|data| data.claims)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    /// Dni of the user
+    sub: String,
+    rol: Roles,
+    exp: usize,
+}
+
+/// User authenticated with a bearer token
+pub struct AuthUser {
+    pub dni: String,
+    pub rol: Roles,
+}
+
+impl AuthUser {
+    /// Admins can act on behalf of any user, everyone else only on behalf of themselves
+    pub fn can_act_as(&self, dni: &str) -> bool {
+        self.dni == dni || self.is_admin()
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.rol == Roles::Admin
+    }
+}
+
+/// Whether the request can act on behalf of the dni. The endpoints that existed before the tokens
+/// still accept requests without one, so the apps already released keep working; a token limits
+/// the request to its own user
+pub fn may_act_as(user: Option<&AuthUser>, dni: &str) -> bool {
+    user.is_none_or(|user| user.can_act_as(dni))
+}
+
+/// User authenticated with a bearer token that has the Admin role
+pub struct AdminUser(pub AuthUser);
+
+/// Returns the rejection used when the user is authenticated but not allowed to do the operation
+pub fn forbidden(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error: Box<dyn Error> = message.into();
+
+    (StatusCode::FORBIDDEN, Json(build_error_response(error)))
+}
+
+fn unauthorized(message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let error: Box<dyn Error> = message.into();
+
+    (StatusCode::UNAUTHORIZED, Json(build_error_response(error)))
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        state.auth_keys.authenticate(&parts.headers)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminUser {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+
+        if !user.is_admin() {
+            return Err(forbidden("ADMIN ROLE REQUIRED"));
+        }
+
+        Ok(AdminUser(user))
+    }
+}
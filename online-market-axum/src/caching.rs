@@ -0,0 +1,117 @@
+use axum::{
+    body::{self, Full},
+    extract::MatchedPath,
+    http::{header, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::versioning::unversioned_route;
+
+/// Returns the Cache-Control of a successful read
+///
+/// # Argument
+///
+/// * route - Route template of the request, for example "/v1/category/all"
+/// * authenticated - The request has a bearer token
+///
+fn cache_control(route: &str, authenticated: bool) -> &'static str {
+    let route = unversioned_route(route);
+
+    if route == "/metrics" {
+        "no-store"
+    } else if authenticated {
+        // The response can depend on the user, shared caches must not keep it
+        "private, no-cache"
+    } else if route.starts_with("/category") {
+        // The catalogue rarely changes and every screen reads it
+        "public, max-age=60"
+    } else {
+        "public, no-cache"
+    }
+}
+
+/// Returns if the If-None-Match header lists the ETag, using the weak comparison
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(if_none_match), Ok(etag)) = (if_none_match.to_str(), etag.to_str()) else {
+        return false;
+    };
+
+    let etag = etag.trim_start_matches("W/");
+
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Middleware that makes the reads cacheable by the clients.
+///
+/// Successful GET responses get a Cache-Control and an ETag with the hash of the body. The
+/// version set by the handler is kept at the start of the ETag for If-Match, but it doesn't
+/// cover everything in the body: the location, favorites_count, the viewer and the language
+/// change it without a new version. When the If-None-Match header of the request lists the
+/// ETag the body is left out with 304
+pub async fn http_cache<B>(req: Request<B>, next: Next<B>) -> Response {
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_owned(),
+        None => "unmatched".to_owned(),
+    };
+    let authenticated = req.headers().contains_key(header::AUTHORIZATION);
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(req).await;
+
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let hash = hex::encode(&Sha256::digest(&bytes)[..16]);
+
+    let etag = match parts
+        .headers
+        .get(header::ETAG)
+        .and_then(|version| version.to_str().ok())
+    {
+        Some(version) => format!("\"{}.{}\"", version.trim_matches('"'), hash),
+        None => format!("W/\"{}\"", hash),
+    };
+    let etag = HeaderValue::from_str(&etag).unwrap();
+
+    parts.headers.insert(header::ETAG, etag.clone());
+    // The body depends on the user of the token
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("authorization"));
+
+    if !parts.headers.contains_key(header::CACHE_CONTROL) {
+        parts.headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control(&route, authenticated)),
+        );
+    }
+
+    if let Some(if_none_match) = if_none_match {
+        if matches_etag(&if_none_match, &etag) {
+            parts.status = StatusCode::NOT_MODIFIED;
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.remove(header::CONTENT_TYPE);
+
+            return Response::from_parts(parts, body::boxed(body::Empty::new()));
+        }
+    }
+
+    Response::from_parts(parts, body::boxed(Full::from(bytes)))
+}
@@ -0,0 +1,116 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::dataloader::Loader;
+use online_market_model::{
+    CategoryResponse, CommentResponse, RatingSummary, ServiceResponse, UserResponse,
+};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Users by dni
+pub struct UserLoader(pub Arc<AppState>);
+
+impl Loader<String> for UserLoader {
+    type Value = UserResponse;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let users = self.0.user_repository.get_by_dnis(keys, &self.0.db).await?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| (user.dni.clone(), user))
+            .collect())
+    }
+}
+
+/// Categories by id, read from the cached catalogue
+pub struct CategoryLoader(pub Arc<AppState>);
+
+impl Loader<i64> for CategoryLoader {
+    type Value = CategoryResponse;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[i64]) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+        let categories = self
+            .0
+            .category_repository
+            .get_by_ids(keys, &self.0.db)
+            .await?;
+
+        Ok(categories
+            .into_iter()
+            .map(|category| (category.id, category))
+            .collect())
+    }
+}
+
+/// Services by dni of the seller
+pub struct ServicesBySellerLoader(pub Arc<AppState>);
+
+impl Loader<String> for ServicesBySellerLoader {
+    type Value = Vec<ServiceResponse>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let services = self
+            .0
+            .service_repository
+            .get_by_sellers(keys, &self.0.db)
+            .await?;
+
+        let mut by_seller: HashMap<String, Self::Value> = HashMap::new();
+
+        for service in services {
+            by_seller
+                .entry(service.user_id.clone())
+                .or_default()
+                .push(service);
+        }
+
+        Ok(by_seller)
+    }
+}
+
+/// Rating summaries by dni of the rated user
+pub struct RatingSummaryLoader(pub Arc<AppState>);
+
+impl Loader<String> for RatingSummaryLoader {
+    type Value = RatingSummary;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        Ok(self
+            .0
+            .rate_repository
+            .get_summaries(keys, &self.0.db)
+            .await?)
+    }
+}
+
+/// Visible replies by id of the replied comment
+pub struct RepliesLoader(pub Arc<AppState>);
+
+impl Loader<Uuid> for RepliesLoader {
+    type Value = Vec<CommentResponse>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        let replies = self
+            .0
+            .comment_repository
+            .get_replies(keys, &self.0.db)
+            .await?;
+
+        let mut by_parent: HashMap<Uuid, Self::Value> = HashMap::new();
+
+        for reply in replies {
+            if let Some(parent_id) = reply.parent_id {
+                by_parent.entry(parent_id).or_default().push(reply);
+            }
+        }
+
+        Ok(by_parent)
+    }
+}
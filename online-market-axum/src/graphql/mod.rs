@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    dataloader::DataLoader, http::GraphiQLSource, Context, EmptySubscription, ErrorExtensions,
+    Schema,
+};
+use axum::{
+    extract::{Json, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse},
+};
+
+use crate::{
+    auth::AuthUser,
+    handler::{build_error_response, build_repository_error},
+    AppState,
+};
+
+use self::{
+    loaders::{
+        CategoryLoader, RatingSummaryLoader, RepliesLoader, ServicesBySellerLoader, UserLoader,
+    },
+    mutation::Mutation,
+    query::Query,
+};
+
+pub mod loaders;
+pub mod mutation;
+pub mod query;
+pub mod types;
+
+pub type MarketSchema = Schema<Query, Mutation, EmptySubscription>;
+
+/// Size of the pages of the connections when the first argument is missing
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+pub fn build_schema() -> MarketSchema {
+    Schema::build(Query, Mutation, EmptySubscription).finish()
+}
+
+/// Executes a GraphQL request. The bearer token is optional, but an invalid one is rejected
+/// like in the rest of the API
+pub async fn graphql_handler(
+    State(app): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let user = if headers.contains_key(header::AUTHORIZATION) {
+        Some(app.auth_keys.authenticate(&headers)?)
+    } else {
+        None
+    };
+
+    // The loaders live for a single request, so they batch the loads of the request
+    // without sharing values between users
+    let request = request
+        .data(app.clone())
+        .data(user)
+        .data(DataLoader::new(UserLoader(app.clone()), tokio::spawn))
+        .data(DataLoader::new(CategoryLoader(app.clone()), tokio::spawn))
+        .data(DataLoader::new(
+            ServicesBySellerLoader(app.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            RatingSummaryLoader(app.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(RepliesLoader(app.clone()), tokio::spawn));
+
+    let response = app.graphql_schema.execute(request).await;
+
+    Ok(Json(response))
+}
+
+/// Serves the GraphiQL explorer of the endpoint
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+fn app<'a>(ctx: &Context<'a>) -> &'a Arc<AppState> {
+    ctx.data_unchecked::<Arc<AppState>>()
+}
+
+/// Returns the user of the bearer token, None for anonymous requests
+fn current_user<'a>(ctx: &Context<'a>) -> Option<&'a AuthUser> {
+    ctx.data_unchecked::<Option<AuthUser>>().as_ref()
+}
+
+fn require_user<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a AuthUser> {
+    current_user(ctx).ok_or_else(|| {
+        let error: Box<dyn std::error::Error> = "MISSING BEARER TOKEN".into();
+
+        rejection((StatusCode::UNAUTHORIZED, Json(build_error_response(error))))
+    })
+}
+
+/// Turns the rejection of a handler into a GraphQL error with the same message. The status
+/// code the REST API would return goes in the status extension and its error code in the code one
+fn rejection((status, Json(body)): (StatusCode, Json<serde_json::Value>)) -> async_graphql::Error {
+    let message = body["result"].as_str().unwrap_or_default().to_string();
+    let code = body["code"].as_str().unwrap_or_default().to_string();
+
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        extensions.set("status", status.as_u16());
+        extensions.set("code", code);
+    })
+}
+
+fn repository_error(error: Box<dyn std::error::Error + Send + Sync>) -> async_graphql::Error {
+    rejection(build_repository_error(error))
+}
+
+fn sql_error(error: sqlx::Error) -> async_graphql::Error {
+    repository_error(Box::new(error))
+}
+
+fn load_error(error: Arc<sqlx::Error>) -> async_graphql::Error {
+    let error: Box<dyn std::error::Error> = error.to_string().into();
+
+    rejection((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(build_error_response(error)),
+    ))
+}
+
+/// Returns the number of items of a page for the first argument of a connection
+fn page_size(first: Option<usize>) -> usize {
+    first.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+}
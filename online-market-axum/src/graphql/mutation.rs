@@ -0,0 +1,299 @@
+use async_graphql::{Context, InputObject, Object, Result};
+use axum::{http::StatusCode, Json};
+use online_market_model::{
+    Category as CategoryRecord, Comment as CommentRecord, CommentReply, CommentUpdate, Money,
+    Rate as RateRecord, Roles, Service as ServiceRecord, ServiceResponse,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{auth::forbidden, handler::build_error_response};
+
+use super::{
+    app, rejection, repository_error, require_user, sql_error,
+    types::{Category, Comment, Modality, Rate, Service},
+};
+
+#[derive(InputObject)]
+pub struct MoneyInput {
+    /// Decimal amount with up to two decimals
+    amount: Decimal,
+    /// ISO 4217 code of the currency
+    currency: String,
+}
+
+#[derive(InputObject)]
+pub struct ServiceInput {
+    /// Dni of the seller that publishes the service
+    seller: String,
+    category_id: i64,
+    price: MoneyInput,
+    description: String,
+    modality: Modality,
+}
+
+impl ServiceInput {
+    fn into_record(self, id: Option<Uuid>) -> Result<ServiceRecord> {
+        let price = Money::new(self.price.amount, self.price.currency).map_err(|message| {
+            let error: Box<dyn std::error::Error> = message.into();
+
+            rejection((StatusCode::BAD_REQUEST, Json(build_error_response(error))))
+        })?;
+
+        Ok(ServiceRecord {
+            id,
+            user_id: self.seller,
+            category_id: self.category_id,
+            price,
+            description: self.description,
+            modality: self.modality.into(),
+        })
+    }
+}
+
+/// Returns the service if the authenticated user can manage it
+async fn get_owned_service(ctx: &Context<'_>, id: Uuid) -> Result<ServiceResponse> {
+    let app = app(ctx);
+    let user = require_user(ctx)?;
+
+    let service = app
+        .service_repository
+        .get_by_id(id, &app.db)
+        .await
+        .map_err(sql_error)?;
+
+    if !user.can_act_as(&service.user_id) {
+        return Err(rejection(forbidden(
+            "YOU CAN ONLY MANAGE YOUR OWN SERVICES",
+        )));
+    }
+
+    Ok(service)
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    /// Only available to admins
+    async fn create_category(&self, ctx: &Context<'_>, name: String) -> Result<Category> {
+        let app = app(ctx);
+
+        if require_user(ctx)?.rol != Roles::Admin {
+            return Err(rejection(forbidden("ADMIN ROLE REQUIRED")));
+        }
+
+        let category = app
+            .category_repository
+            .save(CategoryRecord { name }, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Category(category))
+    }
+
+    async fn create_service(&self, ctx: &Context<'_>, input: ServiceInput) -> Result<Service> {
+        let app = app(ctx);
+
+        if !require_user(ctx)?.can_act_as(&input.seller) {
+            return Err(rejection(forbidden(
+                "YOU CAN ONLY PUBLISH SERVICES ON YOUR OWN BEHALF",
+            )));
+        }
+
+        let service = app
+            .service_repository
+            .save(input.into_record(None)?, &app.db)
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Service(service))
+    }
+
+    /// The version is the one returned when the service was read
+    async fn update_service(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        version: i32,
+        input: ServiceInput,
+    ) -> Result<Service> {
+        let app = app(ctx);
+
+        get_owned_service(ctx, id).await?;
+
+        let service = app
+            .service_repository
+            .update_service(input.into_record(Some(id))?, version, &app.db)
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Service(service))
+    }
+
+    /// Returns the deleted service
+    async fn delete_service(&self, ctx: &Context<'_>, id: Uuid) -> Result<Service> {
+        let app = app(ctx);
+
+        let service = get_owned_service(ctx, id).await?;
+
+        app.service_repository
+            .delete_service(id, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Service(service))
+    }
+
+    async fn rate_user(
+        &self,
+        ctx: &Context<'_>,
+        rater: String,
+        rated: String,
+        rate: f32,
+    ) -> Result<Rate> {
+        let app = app(ctx);
+
+        if !require_user(ctx)?.can_act_as(&rater) {
+            return Err(rejection(forbidden("YOU CAN ONLY RATE ON YOUR OWN BEHALF")));
+        }
+
+        let rate = app
+            .rate_repository
+            .save(RateRecord { rater, rated, rate }, &app.db)
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Rate(rate))
+    }
+
+    /// The version is the one returned when the rate was read
+    async fn update_rate(
+        &self,
+        ctx: &Context<'_>,
+        rater: String,
+        rated: String,
+        rate: f32,
+        version: i32,
+    ) -> Result<Rate> {
+        let app = app(ctx);
+
+        if !require_user(ctx)?.can_act_as(&rater) {
+            return Err(rejection(forbidden("YOU CAN ONLY UPDATE YOUR OWN RATES")));
+        }
+
+        let rate = app
+            .rate_repository
+            .update_rate(RateRecord { rater, rated, rate }, version, &app.db)
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Rate(rate))
+    }
+
+    async fn comment_user(
+        &self,
+        ctx: &Context<'_>,
+        commentator: String,
+        commented: String,
+        comment: String,
+    ) -> Result<Comment> {
+        let app = app(ctx);
+
+        if !require_user(ctx)?.can_act_as(&commentator) {
+            return Err(rejection(forbidden(
+                "YOU CAN ONLY COMMENT ON YOUR OWN BEHALF",
+            )));
+        }
+
+        let comment = app
+            .comment_repository
+            .save(
+                CommentRecord {
+                    commentator,
+                    commented,
+                    comment,
+                },
+                &app.db,
+            )
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Comment(comment))
+    }
+
+    /// Only the commented user and the author of the comment can reply
+    async fn reply_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        commentator: String,
+        comment: String,
+    ) -> Result<Comment> {
+        let app = app(ctx);
+
+        if !require_user(ctx)?.can_act_as(&commentator) {
+            return Err(rejection(forbidden(
+                "YOU CAN ONLY COMMENT ON YOUR OWN BEHALF",
+            )));
+        }
+
+        let parent = app
+            .comment_repository
+            .get_comment(id, false, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        if commentator != parent.commented && commentator != parent.commentator {
+            return Err(rejection(forbidden(
+                "ONLY THE COMMENTED USER AND THE AUTHOR CAN REPLY TO A COMMENT",
+            )));
+        }
+
+        let reply = app
+            .comment_repository
+            .reply(
+                id,
+                CommentReply {
+                    commentator,
+                    comment,
+                },
+                &app.db,
+            )
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Comment(reply))
+    }
+
+    /// The version is the one returned when the comment was read
+    async fn update_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        comment: String,
+        version: i32,
+    ) -> Result<Comment> {
+        let app = app(ctx);
+
+        let current = app
+            .comment_repository
+            .get_comment(id, true, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        if !require_user(ctx)?.can_act_as(&current.commentator) {
+            return Err(rejection(forbidden(
+                "YOU CAN ONLY UPDATE YOUR OWN COMMENTS",
+            )));
+        }
+
+        let comment = app
+            .comment_repository
+            .update_comment(id, CommentUpdate { comment }, version, &app.db)
+            .await
+            .map_err(repository_error)?;
+
+        Ok(Comment(comment))
+    }
+}
@@ -0,0 +1,177 @@
+use async_graphql::{
+    connection::{self, Connection, Edge, OpaqueCursor},
+    Context, Object, Result,
+};
+use online_market_data::{Pagination, PaginationRequest};
+use uuid::Uuid;
+
+use super::{
+    app, current_user, page_size, sql_error,
+    types::{Category, Comment, Rate, Service, User},
+};
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn user(&self, ctx: &Context<'_>, dni: String) -> Result<User> {
+        let app = app(ctx);
+
+        let user = app
+            .user_repository
+            .get_by_dni(dni, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(User(user))
+    }
+
+    /// Users ordered by dni
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<OpaqueCursor<String>, User>> {
+        connection::query(
+            after,
+            None,
+            first,
+            None,
+            |after: Option<OpaqueCursor<String>>, _, first, _| async move {
+                let limit = page_size(first);
+                let app = app(ctx);
+
+                let mut users = app
+                    .user_repository
+                    .get_page(after.map(|cursor| cursor.0), limit as i64 + 1, &app.db)
+                    .await
+                    .map_err(sql_error)?;
+
+                let has_next_page = users.len() > limit;
+                users.truncate(limit);
+
+                let mut connection = Connection::new(false, has_next_page);
+                connection.edges.extend(
+                    users
+                        .into_iter()
+                        .map(|user| Edge::new(OpaqueCursor(user.dni.clone()), User(user))),
+                );
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    async fn service(&self, ctx: &Context<'_>, id: Uuid) -> Result<Service> {
+        let app = app(ctx);
+
+        let service = app
+            .service_repository
+            .get_by_id(id, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Service(service))
+    }
+
+    /// Services ordered by id, optionally of a single category
+    async fn services(
+        &self,
+        ctx: &Context<'_>,
+        category_id: Option<i64>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<OpaqueCursor<Uuid>, Service>> {
+        connection::query(
+            after,
+            None,
+            first,
+            None,
+            |after: Option<OpaqueCursor<Uuid>>, _, first, _| async move {
+                let limit = page_size(first);
+                let app = app(ctx);
+
+                let mut services = app
+                    .service_repository
+                    .get_page(
+                        after.map(|cursor| cursor.0),
+                        category_id,
+                        limit as i64 + 1,
+                        &app.db,
+                    )
+                    .await
+                    .map_err(sql_error)?;
+
+                let has_next_page = services.len() > limit;
+                services.truncate(limit);
+
+                let mut connection = Connection::new(false, has_next_page);
+                connection.edges.extend(
+                    services
+                        .into_iter()
+                        .map(|service| Edge::new(OpaqueCursor(service.id), Service(service))),
+                );
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    async fn category(&self, ctx: &Context<'_>, id: i64) -> Result<Category> {
+        let app = app(ctx);
+
+        let category = app
+            .category_repository
+            .get_by_id(id, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Category(category))
+    }
+
+    /// Every category. The catalogue is small, so it isn't paginated
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let app = app(ctx);
+
+        let pagination = Pagination::new(PaginationRequest {
+            page: Some(1),
+            per_page: Some(i64::MAX),
+        });
+
+        match app.category_repository.get_all(pagination, &app.db).await {
+            Ok(categories) => Ok(categories.into_iter().map(Category).collect()),
+            Err(sqlx::Error::RowNotFound) => Ok(Vec::new()),
+            Err(error) => Err(sql_error(error)),
+        }
+    }
+
+    async fn rate(&self, ctx: &Context<'_>, rater: String, rated: String) -> Result<Rate> {
+        let app = app(ctx);
+
+        let rate = app
+            .rate_repository
+            .get_rate(rater, rated, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Rate(rate))
+    }
+
+    async fn comment(&self, ctx: &Context<'_>, id: Uuid) -> Result<Comment> {
+        let app = app(ctx);
+
+        // Comments hidden by the moderation are only shown to the admins
+        let include_hidden = current_user(ctx).is_some_and(|user| user.is_admin());
+
+        let comment = app
+            .comment_repository
+            .get_comment(id, include_hidden, &app.db)
+            .await
+            .map_err(sql_error)?;
+
+        Ok(Comment(comment))
+    }
+}
@@ -0,0 +1,409 @@
+use async_graphql::{
+    connection::{self, Connection, Edge, OpaqueCursor},
+    dataloader::DataLoader,
+    Context, Enum, Object, Result,
+};
+use chrono::{DateTime, Utc};
+use online_market_model::{
+    CategoryResponse, CommentResponse, RateResponse, RatingSummary, ServiceResponse, UserResponse,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use super::{
+    app, current_user, load_error,
+    loaders::{
+        CategoryLoader, RatingSummaryLoader, RepliesLoader, ServicesBySellerLoader, UserLoader,
+    },
+    page_size, sql_error,
+};
+
+/// Loads a user that must exist, like the author of a rate
+async fn load_user(ctx: &Context<'_>, dni: &str) -> Result<User> {
+    let user = ctx
+        .data_unchecked::<DataLoader<UserLoader>>()
+        .load_one(dni.to_owned())
+        .await
+        .map_err(load_error)?;
+
+    match user {
+        Some(user) => Ok(User(user)),
+        None => Err(sql_error(sqlx::Error::RowNotFound)),
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "online_market_model::Modality")]
+pub enum Modality {
+    Domicilio,
+    Presencial,
+    Hibrido,
+}
+
+pub struct User(pub UserResponse);
+
+#[Object]
+impl User {
+    async fn dni(&self) -> &str {
+        &self.0.dni
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Only visible to the user and the admins
+    async fn email(&self, ctx: &Context<'_>) -> Option<&str> {
+        match current_user(ctx) {
+            Some(user) if user.can_act_as(&self.0.dni) => Some(&self.0.email),
+            _ => None,
+        }
+    }
+
+    async fn contact_number(&self) -> &str {
+        &self.0.contact_number
+    }
+
+    async fn registered_at(&self) -> DateTime<Utc> {
+        self.0.registered_at
+    }
+
+    async fn is_seller(&self) -> bool {
+        self.0.is_seller
+    }
+
+    /// When the seller paused their listings. Null when the seller is active
+    async fn seller_paused_at(&self) -> Option<DateTime<Utc>> {
+        self.0.seller_paused_at
+    }
+
+    /// Version of the user, required to update it
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    /// Category of the seller
+    async fn category(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        let category_id = match self.0.category_id {
+            Some(category_id) => category_id,
+            None => return Ok(None),
+        };
+
+        let category = ctx
+            .data_unchecked::<DataLoader<CategoryLoader>>()
+            .load_one(category_id)
+            .await
+            .map_err(load_error)?;
+
+        Ok(category.map(Category))
+    }
+
+    /// Services published by the seller
+    async fn services(&self, ctx: &Context<'_>) -> Result<Vec<Service>> {
+        let services = ctx
+            .data_unchecked::<DataLoader<ServicesBySellerLoader>>()
+            .load_one(self.0.dni.clone())
+            .await
+            .map_err(load_error)?;
+
+        Ok(services
+            .unwrap_or_default()
+            .into_iter()
+            .map(Service)
+            .collect())
+    }
+
+    async fn rating_summary(&self, ctx: &Context<'_>) -> Result<Option<RatingSummaryObject>> {
+        let summary = ctx
+            .data_unchecked::<DataLoader<RatingSummaryLoader>>()
+            .load_one(self.0.dni.clone())
+            .await
+            .map_err(load_error)?;
+
+        Ok(summary.map(RatingSummaryObject))
+    }
+
+    /// Rates received by the user, ordered by rater
+    async fn rates(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<OpaqueCursor<String>, Rate>> {
+        connection::query(
+            after,
+            None,
+            first,
+            None,
+            |after: Option<OpaqueCursor<String>>, _, first, _| async move {
+                let limit = page_size(first);
+                let app = app(ctx);
+
+                let mut rates = app
+                    .rate_repository
+                    .get_page_by_rated(
+                        self.0.dni.clone(),
+                        after.map(|cursor| cursor.0),
+                        limit as i64 + 1,
+                        &app.db,
+                    )
+                    .await
+                    .map_err(sql_error)?;
+
+                let has_next_page = rates.len() > limit;
+                rates.truncate(limit);
+
+                let mut connection = Connection::new(false, has_next_page);
+                connection.edges.extend(
+                    rates
+                        .into_iter()
+                        .map(|rate| Edge::new(OpaqueCursor(rate.rater.clone()), Rate(rate))),
+                );
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// Visible top level comments about the user, the newest first
+    async fn comments(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<OpaqueCursor<(DateTime<Utc>, Uuid)>, Comment>> {
+        connection::query(
+            after,
+            None,
+            first,
+            None,
+            |after: Option<OpaqueCursor<(DateTime<Utc>, Uuid)>>, _, first, _| async move {
+                let limit = page_size(first);
+                let app = app(ctx);
+
+                let mut comments = app
+                    .comment_repository
+                    .get_latest_by_commented(
+                        self.0.dni.clone(),
+                        after.map(|cursor| cursor.0),
+                        limit as i64 + 1,
+                        &app.db,
+                    )
+                    .await
+                    .map_err(sql_error)?;
+
+                let has_next_page = comments.len() > limit;
+                comments.truncate(limit);
+
+                let mut connection = Connection::new(false, has_next_page);
+                connection.edges.extend(comments.into_iter().map(|comment| {
+                    Edge::new(
+                        OpaqueCursor((comment.created_at, comment.id)),
+                        Comment(comment),
+                    )
+                }));
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+}
+
+pub struct Category(pub CategoryResponse);
+
+#[Object]
+impl Category {
+    async fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+}
+
+pub struct Money {
+    amount: Decimal,
+    currency: String,
+}
+
+#[Object]
+impl Money {
+    /// Decimal amount with up to two decimals
+    async fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    /// ISO 4217 code of the currency
+    async fn currency(&self) -> &str {
+        &self.currency
+    }
+}
+
+pub struct Service(pub ServiceResponse);
+
+#[Object]
+impl Service {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn price(&self) -> Money {
+        Money {
+            amount: self.0.price.amount,
+            currency: self.0.price.currency.clone(),
+        }
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn modality(&self) -> Modality {
+        self.0.modality.clone().into()
+    }
+
+    /// Number of users that have the service in their favorites
+    async fn favorites_count(&self) -> i64 {
+        self.0.favorites_count
+    }
+
+    /// Version of the service, required to update it
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn seller(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, &self.0.user_id).await
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        let category = ctx
+            .data_unchecked::<DataLoader<CategoryLoader>>()
+            .load_one(self.0.category_id)
+            .await
+            .map_err(load_error)?;
+
+        Ok(category.map(Category))
+    }
+}
+
+pub struct Rate(pub RateResponse);
+
+#[Object]
+impl Rate {
+    async fn rate(&self) -> f32 {
+        self.0.rate
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.0.updated_at
+    }
+
+    /// The rater has a completed booking with the rated user
+    async fn verified(&self) -> bool {
+        self.0.verified
+    }
+
+    /// Version of the rate, required to update it
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn rater(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, &self.0.rater).await
+    }
+
+    async fn rated(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, &self.0.rated).await
+    }
+}
+
+pub struct Comment(pub CommentResponse);
+
+#[Object]
+impl Comment {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    /// Id of the replied comment, null for top level comments
+    async fn parent_id(&self) -> Option<Uuid> {
+        self.0.parent_id
+    }
+
+    async fn comment(&self) -> &str {
+        &self.0.comment
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.0.updated_at
+    }
+
+    /// The commentator has a completed booking with the commented user
+    async fn verified(&self) -> bool {
+        self.0.verified
+    }
+
+    /// Version of the comment, required to update it
+    async fn version(&self) -> i32 {
+        self.0.version
+    }
+
+    async fn commentator(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, &self.0.commentator).await
+    }
+
+    async fn commented(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, &self.0.commented).await
+    }
+
+    /// Visible replies, the oldest first. Replies have no replies
+    async fn replies(&self, ctx: &Context<'_>) -> Result<Vec<Comment>> {
+        if self.0.parent_id.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let replies = ctx
+            .data_unchecked::<DataLoader<RepliesLoader>>()
+            .load_one(self.0.id)
+            .await
+            .map_err(load_error)?;
+
+        Ok(replies
+            .unwrap_or_default()
+            .into_iter()
+            .map(Comment)
+            .collect())
+    }
+}
+
+/// Rates received by a user
+pub struct RatingSummaryObject(pub RatingSummary);
+
+#[Object(name = "RatingSummary")]
+impl RatingSummaryObject {
+    async fn rates_count(&self) -> i64 {
+        self.0.rates_count
+    }
+
+    /// Null when the user has no rates
+    async fn average_rate(&self) -> Option<f64> {
+        self.0.average_rate
+    }
+
+    /// Rates whose rater has a completed booking with the user
+    async fn verified_rates_count(&self) -> i64 {
+        self.0.verified_rates_count
+    }
+}
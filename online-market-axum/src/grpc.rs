@@ -0,0 +1,405 @@
+// Status is the error type of every tonic handler, so its size can't be reduced
+#![allow(clippy::result_large_err)]
+
+use std::{net::SocketAddr, pin::Pin, sync::Arc};
+
+use online_market_data::{location::LocationPolicy, Pagination, PaginationRequest};
+use online_market_model::{
+    CategoryResponse, CommentResponse, Modality, RateResponse, RatingSummary, ServiceResponse,
+    UserLocation, UserResponse,
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+use uuid::Uuid;
+
+use crate::{AppState, LocationUpdate};
+
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("market.v1");
+}
+
+use proto::market_server::{Market, MarketServer};
+
+/// Serves the gRPC API on the given address until the process stops
+pub async fn serve(app: Arc<AppState>, address: SocketAddr) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(MarketServer::new(MarketService { app }))
+        .serve(address)
+        .await
+}
+
+pub struct MarketService {
+    app: Arc<AppState>,
+}
+
+impl MarketService {
+    /// Rules to show the locations of the users. The clients aren't authenticated, so they see
+    /// them like anonymous requests of the REST API
+    async fn location_policy(&self) -> Result<LocationPolicy, Status> {
+        self.app
+            .location_privacy_repository
+            .policy(None, &self.app.db)
+            .await
+            .map_err(status)
+    }
+}
+
+/// Returns the status of an error returned by a repository
+fn status(error: sqlx::Error) -> Status {
+    match error {
+        sqlx::Error::RowNotFound => Status::not_found(error.to_string()),
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+/// Returns the items of a list, where the repositories use RowNotFound for empty pages
+fn list<T>(result: Result<Vec<T>, sqlx::Error>) -> Result<Vec<T>, Status> {
+    match result {
+        Ok(items) => Ok(items),
+        Err(sqlx::Error::RowNotFound) => Ok(Vec::new()),
+        Err(error) => Err(status(error)),
+    }
+}
+
+fn pagination(page: Option<proto::Page>) -> Pagination {
+    let page = page.unwrap_or_default();
+
+    // Zero is the default value of proto3, so it means the field is missing
+    Pagination::new(PaginationRequest {
+        page: Some(page.page).filter(|page| *page > 0),
+        per_page: Some(page.per_page).filter(|per_page| *per_page > 0),
+    })
+}
+
+fn parse_id(id: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(id).map_err(|_| Status::invalid_argument("INVALID ID"))
+}
+
+impl From<UserResponse> for proto::User {
+    fn from(user: UserResponse) -> Self {
+        proto::User {
+            dni: user.dni,
+            name: user.name,
+            contact_number: user.contact_number,
+            registered_at: user.registered_at.to_rfc3339(),
+            is_seller: user.is_seller,
+            category_id: user.category_id,
+            latitude: user.latitude,
+            longitude: user.longitude,
+            seller_paused_at: user
+                .seller_paused_at
+                .map(|paused_at| paused_at.to_rfc3339()),
+            version: user.version,
+        }
+    }
+}
+
+impl From<ServiceResponse> for proto::Service {
+    fn from(service: ServiceResponse) -> Self {
+        let modality = match service.modality {
+            Modality::Domicilio => proto::Modality::Domicilio,
+            Modality::Presencial => proto::Modality::Presencial,
+            Modality::Hibrido => proto::Modality::Hibrido,
+        };
+
+        proto::Service {
+            id: service.id.to_string(),
+            seller: service.user_id,
+            category_id: service.category_id,
+            price: Some(proto::Money {
+                amount: service.price.amount.to_string(),
+                currency: service.price.currency,
+            }),
+            description: service.description,
+            modality: modality.into(),
+            favorites_count: service.favorites_count,
+            version: service.version,
+        }
+    }
+}
+
+impl From<CategoryResponse> for proto::Category {
+    fn from(category: CategoryResponse) -> Self {
+        proto::Category {
+            id: category.id,
+            name: category.name,
+        }
+    }
+}
+
+impl From<RatingSummary> for proto::RatingSummary {
+    fn from(summary: RatingSummary) -> Self {
+        proto::RatingSummary {
+            dni: summary.dni,
+            rates_count: summary.rates_count,
+            average_rate: summary.average_rate,
+            verified_rates_count: summary.verified_rates_count,
+        }
+    }
+}
+
+impl From<RateResponse> for proto::Rate {
+    fn from(rate: RateResponse) -> Self {
+        proto::Rate {
+            rater: rate.rater,
+            rated: rate.rated,
+            rate: rate.rate,
+            created_at: rate.created_at.to_rfc3339(),
+            updated_at: rate.updated_at.map(|updated_at| updated_at.to_rfc3339()),
+            verified: rate.verified,
+            version: rate.version,
+        }
+    }
+}
+
+impl From<CommentResponse> for proto::Comment {
+    fn from(comment: CommentResponse) -> Self {
+        proto::Comment {
+            id: comment.id.to_string(),
+            parent_id: comment.parent_id.map(|parent_id| parent_id.to_string()),
+            commentator: comment.commentator,
+            commented: comment.commented,
+            comment: comment.comment,
+            created_at: comment.created_at.to_rfc3339(),
+            updated_at: comment.updated_at.map(|updated_at| updated_at.to_rfc3339()),
+            hidden: comment.hidden,
+            verified: comment.verified,
+            version: comment.version,
+        }
+    }
+}
+
+impl From<UserLocation> for proto::LocationUpdate {
+    fn from(location: UserLocation) -> Self {
+        proto::LocationUpdate {
+            dni: location.dni,
+            latitude: location.latitude,
+            longitude: location.longitude,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Market for MarketService {
+    async fn get_user(
+        &self,
+        request: Request<proto::GetUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        let policy = self.location_policy().await?;
+
+        let mut user = self
+            .app
+            .user_repository
+            .get_by_dni(request.into_inner().dni, &self.app.db)
+            .await
+            .map_err(status)?;
+
+        policy.apply(&mut user);
+
+        Ok(Response::new(user.into()))
+    }
+
+    async fn list_users(
+        &self,
+        request: Request<proto::ListUsersRequest>,
+    ) -> Result<Response<proto::ListUsersResponse>, Status> {
+        let pagination = pagination(request.into_inner().page);
+        let policy = self.location_policy().await?;
+
+        let users = list(
+            self.app
+                .user_repository
+                .get_all(pagination, &self.app.db)
+                .await,
+        )?;
+
+        Ok(Response::new(proto::ListUsersResponse {
+            users: users
+                .into_iter()
+                .map(|mut user| {
+                    policy.apply(&mut user);
+                    user.into()
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_service(
+        &self,
+        request: Request<proto::GetServiceRequest>,
+    ) -> Result<Response<proto::Service>, Status> {
+        let id = parse_id(&request.into_inner().id)?;
+
+        let service = self
+            .app
+            .service_repository
+            .get_by_id(id, &self.app.db)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(service.into()))
+    }
+
+    async fn list_services_by_seller(
+        &self,
+        request: Request<proto::ListServicesBySellerRequest>,
+    ) -> Result<Response<proto::ListServicesResponse>, Status> {
+        let request = request.into_inner();
+
+        let services = list(
+            self.app
+                .service_repository
+                .get_by_seller(request.dni, pagination(request.page), &self.app.db)
+                .await,
+        )?;
+
+        Ok(Response::new(proto::ListServicesResponse {
+            services: services.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_category(
+        &self,
+        request: Request<proto::GetCategoryRequest>,
+    ) -> Result<Response<proto::Category>, Status> {
+        let category = self
+            .app
+            .category_repository
+            .get_by_id(request.into_inner().id, &self.app.db)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(category.into()))
+    }
+
+    async fn list_categories(
+        &self,
+        request: Request<proto::ListCategoriesRequest>,
+    ) -> Result<Response<proto::ListCategoriesResponse>, Status> {
+        let pagination = pagination(request.into_inner().page);
+
+        let categories = list(
+            self.app
+                .category_repository
+                .get_all(pagination, &self.app.db)
+                .await,
+        )?;
+
+        Ok(Response::new(proto::ListCategoriesResponse {
+            categories: categories.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_rating_summary(
+        &self,
+        request: Request<proto::GetRatingSummaryRequest>,
+    ) -> Result<Response<proto::RatingSummary>, Status> {
+        let summary = self
+            .app
+            .rate_repository
+            .get_summary(request.into_inner().dni, &self.app.db)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn list_rates_by_rated(
+        &self,
+        request: Request<proto::ListRatesByRatedRequest>,
+    ) -> Result<Response<proto::ListRatesResponse>, Status> {
+        let request = request.into_inner();
+
+        let rates = list(
+            self.app
+                .rate_repository
+                .get_rates_by_rated(request.dni, pagination(request.page), &self.app.db)
+                .await,
+        )?;
+
+        Ok(Response::new(proto::ListRatesResponse {
+            rates: rates.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_comment(
+        &self,
+        request: Request<proto::GetCommentRequest>,
+    ) -> Result<Response<proto::Comment>, Status> {
+        let id = parse_id(&request.into_inner().id)?;
+
+        let comment = self
+            .app
+            .comment_repository
+            .get_comment(id, false, &self.app.db)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(comment.into()))
+    }
+
+    async fn list_comments_by_commented(
+        &self,
+        request: Request<proto::ListCommentsByCommentedRequest>,
+    ) -> Result<Response<proto::ListCommentThreadsResponse>, Status> {
+        let request = request.into_inner();
+
+        let threads = list(
+            self.app
+                .comment_repository
+                .get_comments_by_commented(request.dni, pagination(request.page), &self.app.db)
+                .await,
+        )?;
+
+        Ok(Response::new(proto::ListCommentThreadsResponse {
+            threads: threads
+                .into_iter()
+                .map(|thread| proto::CommentThread {
+                    comment: Some(thread.comment.into()),
+                    replies: thread.replies.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }))
+    }
+
+    type WatchLocationsStream =
+        Pin<Box<dyn Stream<Item = Result<proto::LocationUpdate, Status>> + Send>>;
+
+    async fn watch_locations(
+        &self,
+        request: Request<proto::WatchLocationsRequest>,
+    ) -> Result<Response<Self::WatchLocationsStream>, Status> {
+        let dnis = request.into_inner().dnis;
+        let policy = self.location_policy().await?;
+
+        // Subscribers that fall behind the channel miss the oldest locations instead of
+        // slowing down the WebSocket that receives them. Hidden locations are skipped and the
+        // rest are sent as the subscriber can see them
+        let stream = BroadcastStream::new(self.app.location_updates.subscribe()).filter_map(
+            move |update| match update {
+                Ok(LocationUpdate {
+                    location,
+                    visibility,
+                }) if dnis.is_empty() || dnis.contains(&location.dni) => {
+                    let (latitude, longitude) = policy.coordinates(
+                        &location.dni,
+                        visibility,
+                        location.latitude,
+                        location.longitude,
+                    )?;
+
+                    Some(Ok(UserLocation {
+                        latitude,
+                        longitude,
+                        ..location
+                    }
+                    .into()))
+                }
+                _ => None,
+            },
+        );
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_data::audit::AuditLogRequest;
+use std::sync::Arc;
+
+use serde_json;
+
+use crate::{auth::AdminUser, AppState};
+
+use super::{build_error_response, build_success_response};
+
+#[utoipa::path(
+    get,
+    path="/audit",
+    tag="audit",
+    params(
+        online_market_data::audit::AuditLogRequest
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Recorded changes, the newest first", body = AuditEntryListResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_audit_log(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(request): Query<AuditLogRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.audit_repository.get(request, &app.db).await;
+
+    match result {
+        Ok(entries) => {
+            let response = build_success_response(entries);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
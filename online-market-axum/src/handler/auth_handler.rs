@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, sync::Arc};
+use online_market_data::password;
+use online_market_model::Secret;
+use utoipa::ToSchema;
+
+use serde_json;
+
+use crate::AppState;
+
+use super::{build_error_response, build_success_response};
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct Credentials {
+    pub dni: String,
+    #[schema(value_type = String, format = Password)]
+    pub password: Secret,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[schema(example = "Bearer")]
+    pub token_type: String,
+    /// Seconds until the token expires
+    pub expires_in: i64,
+}
+
+#[utoipa::path(
+    post,
+    path="/auth/login",
+    tag="auth",
+    request_body = Credentials,
+    responses(
+        (status=200, description = "Access token to send in the Authorization header", body = TokenResult),
+        (status=401, description = "Invalid credentials", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn login(
+    State(app): State<Arc<AppState>>,
+    Json(credentials): Json<Credentials>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .user_repository
+        .get_by_dni(credentials.dni, &app.db)
+        .await;
+
+    let user = match result {
+        Ok(user) if password::verify(credentials.password.expose(), user.password.expose()) => user,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            let error: Box<dyn Error> = "INVALID CREDENTIALS".into();
+            return Err((StatusCode::UNAUTHORIZED, Json(build_error_response(error))));
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)));
+        }
+    };
+
+    // Users saved before the passwords were hashed get their password hashed on their next login
+    if password::needs_rehash(user.password.expose()) {
+        let result = app
+            .user_repository
+            .update_password(user.dni.clone(), credentials.password, &app.db)
+            .await;
+
+        if let Err(error) = result {
+            let response = build_error_response(Box::new(error));
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)));
+        }
+    }
+
+    match app.auth_keys.issue(user.dni, user.rol) {
+        Ok(token) => {
+            let response = build_success_response(TokenResponse {
+                access_token: token,
+                token_type: "Bearer".to_string(),
+                expires_in: app.auth_keys.token_ttl,
+            });
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
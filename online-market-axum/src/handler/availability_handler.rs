@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use online_market_data::availability::SlotsRequest;
+use std::sync::Arc;
+
+use online_market_model::{AvailabilityException, WeeklySchedule};
+use serde_json;
+
+use crate::{auth::AuthUser, AppState};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+#[utoipa::path(
+    put,
+    path="/seller/availability",
+    tag="availability",
+    request_body = WeeklySchedule,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Weekly schedule replaced", body = AvailabilityResult),
+        (status=400, description = "Invalid timezone or overlapping windows", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=409, description = "The user is not a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn set_availability(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(schedule): Json<WeeklySchedule>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .availability_repository
+        .set_schedule(user.dni, schedule, &app.db)
+        .await;
+
+    match result {
+        Ok(availability) => {
+            let response = build_success_response(availability);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/seller/availability/exception",
+    tag="availability",
+    request_body = AvailabilityException,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The date is marked as not available", body = AvailabilityResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=409, description = "The user is not a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn add_availability_exception(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(exception): Json<AvailabilityException>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .availability_repository
+        .add_exception(user.dni, exception, &app.db)
+        .await;
+
+    match result {
+        Ok(availability) => {
+            let response = build_success_response(availability);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path="/seller/availability/exception/{date}",
+    tag="availability",
+    params(
+        ("date" = String, Path, description = "Date of the exception, like 2023-12-25")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The date is available again", body = AvailabilityResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn delete_availability_exception(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(date): Path<NaiveDate>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .availability_repository
+        .remove_exception(user.dni, date, &app.db)
+        .await;
+
+    match result {
+        Ok(availability) => {
+            let response = build_success_response(availability);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/seller/{dni}/availability",
+    tag="availability",
+    params(
+        ("dni" = String, Path, description = "Dni of the seller")
+    ),
+    responses(
+        (status=200, description = "Weekly schedule and upcoming exceptions of the seller", body = AvailabilityResult),
+        (status=404, description = "The seller has no schedule", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_availability(
+    State(app): State<Arc<AppState>>,
+    Path(dni): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.availability_repository.get(dni, &app.db).await;
+
+    match result {
+        Ok(availability) => {
+            let response = build_success_response(availability);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/seller/{dni}/slots",
+    tag="availability",
+    params(
+        ("dni" = String, Path, description = "Dni of the seller"),
+        online_market_data::availability::SlotsRequest
+    ),
+    responses(
+        (status=200, description = "Free slots of the seller in the range of dates", body = TimeSlotListResult),
+        (status=400, description = "Invalid range of dates or duration", body = ErrorResponse),
+        (status=404, description = "The seller has no schedule", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_free_slots(
+    State(app): State<Arc<AppState>>,
+    Path(dni): Path<String>,
+    Query(request): Query<SlotsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .availability_repository
+        .free_slots(dni, request, &app.db)
+        .await;
+
+    match result {
+        Ok(slots) => {
+            let response = build_success_response(slots);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
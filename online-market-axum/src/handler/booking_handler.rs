@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use online_market_model::{Booking, BookingResponse, BookingStatus, BookingStatusUpdate, Roles};
+use serde_json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{forbidden, AuthUser},
+    AppState,
+};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+/// Returns the booking if the authenticated user is its customer, its seller or an admin
+async fn get_own_booking(
+    app: &AppState,
+    user: &AuthUser,
+    id: Uuid,
+) -> Result<BookingResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app.booking_repository.get_by_id(id, &app.db).await {
+        Ok(booking)
+            if user.can_act_as(&booking.customer_id) || user.can_act_as(&booking.seller_id) =>
+        {
+            Ok(booking)
+        }
+        Ok(_) => Err(forbidden("YOU CAN ONLY SEE YOUR OWN BOOKINGS")),
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/booking",
+    tag="booking",
+    request_body = Booking,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "Booking created as pending", body = BookingResult),
+        (status=400, description = "Invalid time range", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "No service found", body = ErrorResponse),
+        (status=409, description = "The time isn't available", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn save_booking(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(booking): Json<Booking>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .booking_repository
+        .save(user.dni, booking, &app.db)
+        .await;
+
+    match result {
+        Ok(booking) => {
+            let response = build_success_response(booking);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/booking/{id}",
+    tag="booking",
+    params(
+        ("id" = Uuid, Path, description = "Id of the booking")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Get booking by id", body = BookingResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The booking belongs to other users", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_booking(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let booking = get_own_booking(&app, &user, id).await?;
+
+    let response = build_success_response(booking);
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    patch,
+    path="/booking/{id}/status",
+    tag="booking",
+    params(
+        ("id" = Uuid, Path, description = "Id of the booking")
+    ),
+    request_body = BookingStatusUpdate,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Status updated", body = BookingResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Only the seller can confirm or complete the booking", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=409, description = "The status can't change from the current one", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn update_booking_status(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(update): Json<BookingStatusUpdate>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let booking = get_own_booking(&app, &user, id).await?;
+
+    let as_seller = user.dni == booking.seller_id || user.rol == Roles::Admin;
+
+    if !as_seller && update.status != BookingStatus::Cancelled {
+        return Err(forbidden(
+            "ONLY THE SELLER CAN CONFIRM OR COMPLETE A BOOKING",
+        ));
+    }
+
+    let result = app
+        .booking_repository
+        .update_status(id, update.status, as_seller, &app.db)
+        .await;
+
+    match result {
+        Ok(booking) => {
+            let response = build_success_response(booking);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
@@ -0,0 +1,274 @@
+use axum::{
+    body::Bytes,
+    extract::{Json, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use online_market_data::bulk::{BulkFormat, ImportReport};
+use serde::Deserialize;
+use std::{error::Error, sync::Arc};
+use utoipa::IntoParams;
+
+use serde_json;
+
+use crate::{auth::AdminUser, AppState};
+
+use super::{build_error_response, build_fail_response, build_success_response};
+
+#[derive(Deserialize, IntoParams)]
+pub struct ExportRequest {
+    /// csv or ndjson. Defaults to csv
+    pub format: Option<String>,
+}
+
+/// Reads the format of the payload from the Content-Type header
+fn import_format(headers: &HeaderMap) -> Result<BulkFormat, (StatusCode, Json<serde_json::Value>)> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    match BulkFormat::from_content_type(content_type) {
+        Some(format) => Ok(format),
+        None => {
+            let error: Box<dyn Error> =
+                "CONTENT TYPE MUST BE text/csv OR application/x-ndjson".into();
+            Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json(build_error_response(error)),
+            ))
+        }
+    }
+}
+
+fn export_format(
+    request: ExportRequest,
+) -> Result<BulkFormat, (StatusCode, Json<serde_json::Value>)> {
+    match request.format {
+        None => Ok(BulkFormat::Csv),
+        Some(format) => format.parse::<BulkFormat>().map_err(|error| {
+            let error: Box<dyn Error> = error.into();
+            (StatusCode::BAD_REQUEST, Json(build_error_response(error)))
+        }),
+    }
+}
+
+/// Builds the response of an import. Rejected imports return every row error
+fn import_response(
+    result: Result<ImportReport, sqlx::Error>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
+    match result {
+        Ok(report) if report.errors.is_empty() => {
+            Ok((StatusCode::CREATED, Json(build_success_response(report))))
+        }
+        Ok(report) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(build_fail_response(report)),
+        )),
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+/// Builds the response of an export with the content type of the format
+fn export_response(
+    format: BulkFormat,
+    result: Result<Vec<u8>, Box<dyn Error>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match result {
+        Ok(body) => Ok((
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, format.content_type())],
+            body,
+        )),
+        Err(error) => {
+            let response = build_error_response(error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/import/categories",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    request_body(content = String, description = "Rows with the column name", content_type = "text/csv"),
+    responses(
+        (status=201, description = "Every category was imported", body = ImportResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=415, description = "Unsupported content type", body = ErrorResponse),
+        (status=422, description = "Nothing was imported, the result lists the errors of each row", body = ImportFailure),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn import_categories(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = import_format(&headers)?;
+
+    let result = app
+        .bulk_repository
+        .import_categories(format, &body, &app.db)
+        .await;
+
+    if result.is_ok() {
+        app.category_repository.invalidate();
+    }
+
+    import_response(result)
+}
+
+#[utoipa::path(
+    post,
+    path="/import/users",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    request_body(
+        content = String,
+        description = "Rows with the columns dni, email, password, name, date_of_birth and contact_number",
+        content_type = "text/csv"
+    ),
+    responses(
+        (status=201, description = "Every user was imported", body = ImportResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=415, description = "Unsupported content type", body = ErrorResponse),
+        (status=422, description = "Nothing was imported, the result lists the errors of each row", body = ImportFailure),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn import_users(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = import_format(&headers)?;
+
+    let result = app
+        .bulk_repository
+        .import_users(format, &body, &app.db)
+        .await;
+
+    import_response(result)
+}
+
+#[utoipa::path(
+    post,
+    path="/import/services",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    request_body(
+        content = String,
+        description = "Rows with the columns user_id, category_id, price, currency (optional), description and modality",
+        content_type = "text/csv"
+    ),
+    responses(
+        (status=201, description = "Every service was imported", body = ImportResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=415, description = "Unsupported content type", body = ErrorResponse),
+        (status=422, description = "Nothing was imported, the result lists the errors of each row", body = ImportFailure),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn import_services(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = import_format(&headers)?;
+
+    let result = app
+        .bulk_repository
+        .import_services(format, &body, &app.db)
+        .await;
+
+    import_response(result)
+}
+
+#[utoipa::path(
+    get,
+    path="/export/categories",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    params(ExportRequest),
+    responses(
+        (status=200, description = "Every category in the requested format", body = String, content_type = "text/csv"),
+        (status=400, description = "Unsupported format", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn export_categories(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(request): Query<ExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = export_format(request)?;
+
+    let result = app.bulk_repository.export_categories(format, &app.db).await;
+
+    export_response(format, result)
+}
+
+#[utoipa::path(
+    get,
+    path="/export/users",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    params(ExportRequest),
+    responses(
+        (status=200, description = "Every user, without passwords, in the requested format", body = String, content_type = "text/csv"),
+        (status=400, description = "Unsupported format", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn export_users(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(request): Query<ExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = export_format(request)?;
+
+    let result = app.bulk_repository.export_users(format, &app.db).await;
+
+    export_response(format, result)
+}
+
+#[utoipa::path(
+    get,
+    path="/export/services",
+    tag="bulk",
+    security(("bearer_auth" = [])),
+    params(ExportRequest),
+    responses(
+        (status=200, description = "Every service in the requested format", body = String, content_type = "text/csv"),
+        (status=400, description = "Unsupported format", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn export_services(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(request): Query<ExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let format = export_format(request)?;
+
+    let result = app.bulk_repository.export_services(format, &app.db).await;
+
+    export_response(format, result)
+}
@@ -16,9 +16,11 @@ use super::{build_error_response, build_success_multi_response, build_success_re
 #[utoipa::path(
     post,
     path="/category",
+    tag="category",
+    request_body = Category,
     responses(
-        (status=201, description = "Category created"),
-        (status=500, description = "Internal error")
+        (status=201, description = "Category created", body = CategoryResult),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn save_category(
@@ -43,10 +45,14 @@ pub async fn save_category(
 #[utoipa::path(
     get,
     path="/category/{id}",
+    tag="category",
+    params(
+        ("id" = i64, Path, description = "Id of the category")
+    ),
     responses(
-        (status=200, description = "Get category by id"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get category by id", body = CategoryResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_category_by_id(
@@ -79,12 +85,14 @@ pub async fn get_category_by_id(
 #[utoipa::path(
     get,
     path="/category/all",
+    tag="category",
     params(
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get all categories"),
-        (status=404, description = "Not found")
+        (status=200, description = "Get all categories", body = CategoryListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_all_categories(
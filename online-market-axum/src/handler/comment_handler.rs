@@ -9,25 +9,40 @@ use std::sync::Arc;
 
 use serde_json;
 
-use online_market_model::Comment;
+use online_market_model::{Comment, CommentReply, CommentUpdate};
+use uuid::Uuid;
 
-use crate::AppState;
+use crate::{
+    auth::{forbidden, may_act_as, AuthUser},
+    AppState,
+};
 
-use super::{build_error_response, build_success_response};
+use super::{
+    build_error_response, build_repository_error, build_success_response, etag, IfMatch,
+};
 
 #[utoipa::path(
     post,
     path="/comment",
+    tag="comment",
+    request_body = Comment,
+    security((), ("bearer_auth" = [])),
     responses(
-        (status=201, description = "Comment created"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=201, description = "Comment created", body = CommentResult),
+        (status=403, description = "The token belongs to another user than the commentator", body = ErrorResponse),
+        (status=409, description = "The commentator has no completed booking with the commented user and verification is required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn save_comment(
     State(app): State<Arc<AppState>>,
+    user: Option<AuthUser>,
     Json(comment): Json<Comment>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !may_act_as(user.as_ref(), &comment.commentator) {
+        return Err(forbidden("YOU CAN ONLY COMMENT ON YOUR OWN BEHALF"));
+    }
+
     let result = app.comment_repository.save(comment, &app.db).await;
 
     match result {
@@ -36,69 +51,140 @@ pub async fn save_comment(
 
             Ok((StatusCode::CREATED, Json(response)))
         }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
-        }
+        Err(error) => Err(build_repository_error(error)),
     }
 }
 
 #[utoipa::path(
     get,
-    path="/comment/{id_commented}/{id_commentator}",
+    path="/comment/{id}",
+    tag="comment",
+    params(
+        ("id" = Uuid, Path, description = "Id of the comment")
+    ),
     responses(
-        (status=200, description = "Get comment"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get comment", body = CommentResult, headers(
+            ("ETag" = String, description = "Version of the comment, sent in the If-Match header to update it")
+        )),
+        (status=404, description = "Not found, or hidden by the moderation and the user isn't an admin", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_comment(
     State(app): State<Arc<AppState>>,
-    Path(id_commentator): Path<String>,
-    Path(id_commented): Path<String>,
+    Path(id): Path<Uuid>,
+    viewer: Option<AuthUser>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // Comments hidden by the moderation are only shown to the admins
+    let include_hidden = viewer.is_some_and(|viewer| viewer.is_admin());
+
     let result = app
         .comment_repository
-        .get_comment(id_commentator, id_commented, &app.db)
+        .get_comment(id, include_hidden, &app.db)
         .await;
 
     match result {
         Ok(comment) => {
+            let headers = etag(comment.version);
             let response = build_success_response(comment);
 
-            Ok((StatusCode::OK, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/comment/{id}/reply",
+    tag="comment",
+    params(
+        ("id" = Uuid, Path, description = "Id of the replied comment")
+    ),
+    request_body = CommentReply,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "Reply created", body = CommentResult),
+        (status=400, description = "The comment is a reply", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The commentator is not the authenticated user or isn't part of the thread", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn reply_comment(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(reply): Json<CommentReply>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !user.can_act_as(&reply.commentator) {
+        return Err(forbidden("YOU CAN ONLY COMMENT ON YOUR OWN BEHALF"));
+    }
+
+    let comment = match app.comment_repository.get_comment(id, false, &app.db).await {
+        Ok(comment) => comment,
+        Err(error) => return Err(build_repository_error(Box::new(error))),
+    };
+
+    // Only the commented user and the author of the comment take part in the thread
+    if reply.commentator != comment.commented && reply.commentator != comment.commentator {
+        return Err(forbidden(
+            "ONLY THE COMMENTED USER AND THE AUTHOR CAN REPLY TO A COMMENT",
+        ));
+    }
+
+    let result = app.comment_repository.reply(id, reply, &app.db).await;
+
+    match result {
+        Ok(comment) => {
+            let response = build_success_response(comment);
+
+            Ok((StatusCode::CREATED, Json(response)))
         }
+        Err(error) => Err(build_repository_error(error)),
     }
 }
 
 #[utoipa::path(
     get,
     path="/comment/commentator/{id_commentator}",
+    tag="comment",
     params(
+        ("id_commentator" = String, Path, description = "Dni of the user who commented"),
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get comment"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get comments by commentator", body = CommentListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_comments_by_commentator(
     State(app): State<Arc<AppState>>,
     Path(id_commentator): Path<String>,
     Query(pagination): Query<PaginationRequest>,
+    viewer: Option<AuthUser>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     // Creation of pagination
     // If no per_page or page is provided the default values will be used
     let pagination = Pagination::new(pagination);
 
+    // Comments hidden by the moderation are only shown to the admins
+    let include_hidden = viewer.is_some_and(|viewer| viewer.is_admin());
+
     let result = app
         .comment_repository
-        .get_comments_by_commentator(id_commentator, pagination, &app.db)
+        .get_comments_by_commentator(id_commentator, include_hidden, pagination, &app.db)
         .await;
 
     match result {
@@ -123,13 +209,15 @@ pub async fn get_comments_by_commentator(
 #[utoipa::path(
     get,
     path="/comment/commented/{id_commented}",
+    tag="comment",
     params(
+        ("id_commented" = String, Path, description = "Dni of the commented user"),
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get comment"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get the visible comments about the user with their replies", body = CommentThreadListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_comments_by_commented(
@@ -167,32 +255,48 @@ pub async fn get_comments_by_commented(
 
 #[utoipa::path(
     patch,
-    path="/comment/update",
+    path="/comment/{id}",
+    tag="comment",
+    params(
+        ("id" = Uuid, Path, description = "Id of the comment"),
+        ("If-Match" = String, Header, description = "ETag of the comment returned by GET /comment/{id}")
+    ),
+    request_body = CommentUpdate,
+    security((), ("bearer_auth" = [])),
     responses(
-        (status=200, description = "Comment updated"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Comment updated", body = CommentResult),
+        (status=403, description = "The token belongs to another user than the commentator", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=409, description = "The commentator has no completed booking with the commented user and verification is required", body = ErrorResponse),
+        (status=412, description = "The comment changed since the given ETag", body = ErrorResponse),
+        (status=428, description = "Missing If-Match header", body = ErrorResponse)
     )
 )]
 pub async fn update_comment(
     State(app): State<Arc<AppState>>,
-    Json(comment): Json<Comment>,
+    user: Option<AuthUser>,
+    Path(id): Path<Uuid>,
+    IfMatch(version): IfMatch,
+    Json(comment): Json<CommentUpdate>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app.comment_repository.get_comment(id, true, &app.db).await {
+        Ok(current) if may_act_as(user.as_ref(), &current.commentator) => {}
+        Ok(_) => return Err(forbidden("YOU CAN ONLY UPDATE YOUR OWN COMMENTS")),
+        Err(error) => return Err(build_repository_error(Box::new(error))),
+    }
+
     let result = app
         .comment_repository
-        .update_comment(comment, &app.db)
+        .update_comment(id, comment, version, &app.db)
         .await;
 
     match result {
         Ok(comment) => {
+            let headers = etag(comment.version);
             let response = build_success_response(comment);
 
-            Ok((StatusCode::OK, Json(response)))
-        }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::NOT_FOUND, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
+        Err(error) => Err(build_repository_error(error)),
     }
 }
-
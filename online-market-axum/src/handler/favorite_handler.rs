@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_data::{Pagination, PaginationRequest};
+use std::sync::Arc;
+
+use serde_json;
+use uuid::Uuid;
+
+use crate::{auth::AuthUser, AppState};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+/// Returns the status code and Json of a favorite operation that failed
+fn favorite_error(error: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    match error {
+        sqlx::Error::RowNotFound => {
+            let response = build_error_response(Box::new(error));
+            (StatusCode::NOT_FOUND, Json(response))
+        }
+        _ => {
+            let response = build_error_response(Box::new(error));
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/favorite/service/{id}",
+    tag="favorite",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "The service is in the favorites of the user", body = FavoriteResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "No service found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn add_favorite_service(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .favorite_repository
+        .add_service(user.dni, id, &app.db)
+        .await;
+
+    match result {
+        Ok(favorite) => {
+            let response = build_success_response(favorite);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(favorite_error(error)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path="/favorite/service/{id}",
+    tag="favorite",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The service is no longer in the favorites of the user", body = FavoriteResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "The service is not in the favorites", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn remove_favorite_service(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .favorite_repository
+        .remove_service(user.dni, id, &app.db)
+        .await;
+
+    match result {
+        Ok(favorite) => {
+            let response = build_success_response(favorite);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(favorite_error(error)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/favorite/seller/{dni}",
+    tag="favorite",
+    params(
+        ("dni" = String, Path, description = "Dni of the seller")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "The seller is in the favorites of the user", body = FavoriteResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=409, description = "The user is not a seller or is the authenticated user", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn add_favorite_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(dni): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .favorite_repository
+        .add_seller(user.dni, dni, &app.db)
+        .await;
+
+    match result {
+        Ok(favorite) => {
+            let response = build_success_response(favorite);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path="/favorite/seller/{dni}",
+    tag="favorite",
+    params(
+        ("dni" = String, Path, description = "Dni of the seller")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The seller is no longer in the favorites of the user", body = FavoriteResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "The seller is not in the favorites", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn remove_favorite_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(dni): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .favorite_repository
+        .remove_seller(user.dni, dni, &app.db)
+        .await;
+
+    match result {
+        Ok(favorite) => {
+            let response = build_success_response(favorite);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(favorite_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/me/favorites",
+    tag="favorite",
+    params(
+        online_market_data::PaginationRequest
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Favorites of the authenticated user, the newest first", body = FavoriteListResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_my_favorites(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(pagination): Query<PaginationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // Creation of pagination
+    // If no per_page or page is provided the default values will be used
+    let pagination = Pagination::new(pagination);
+
+    let result = app
+        .favorite_repository
+        .get_by_user(user.dni, pagination, &app.db)
+        .await;
+
+    match result {
+        Ok(favorites) => {
+            let response = build_success_response(favorites);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(favorite_error(error)),
+    }
+}
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_model::{
+    CategoryLabelUpdate, Language, LabelsResponse, Modality, ModalityLabel, RoleLabel, Roles,
+};
+use std::sync::Arc;
+
+use serde_json;
+
+use crate::{auth::AdminUser, i18n, AppState};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+#[utoipa::path(
+    get,
+    path="/labels",
+    tag="label",
+    params(
+        ("Accept-Language" = Option<String>, Header, description = "Language of the labels, English by default")
+    ),
+    responses(
+        (status=200, description = "Labels of the modalities, roles and categories", body = LabelsResult),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_labels(
+    State(app): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let language = i18n::current();
+
+    let result = app.category_repository.get_labels(language, &app.db).await;
+
+    match result {
+        Ok(categories) => {
+            let response = build_success_response(LabelsResponse {
+                language,
+                modalities: Modality::ALL
+                    .into_iter()
+                    .map(|modality| ModalityLabel {
+                        label: modality.label(language).to_owned(),
+                        modality,
+                    })
+                    .collect(),
+                roles: Roles::ALL
+                    .into_iter()
+                    .map(|rol| RoleLabel {
+                        label: rol.label(language).to_owned(),
+                        rol,
+                    })
+                    .collect(),
+                categories,
+            });
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path="/category/{id}/label/{language}",
+    tag="label",
+    request_body = CategoryLabelUpdate,
+    params(
+        ("id" = i64, Path, description = "Id of the category"),
+        ("language" = Language, Path, description = "Language of the label")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Label saved", body = CategoryLabelResult),
+        (status=400, description = "Invalid label or the category doesn't exist", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn set_category_label(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Path((id, language)): Path<(i64, Language)>,
+    Json(update): Json<CategoryLabelUpdate>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .category_repository
+        .set_label(id, language, update.label, &app.db)
+        .await;
+
+    match result {
+        Ok(label) => {
+            let response = build_success_response(label);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
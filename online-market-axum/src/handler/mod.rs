@@ -1,11 +1,103 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderName, StatusCode},
+    Json,
+};
+use online_market_data::{
+    bulk::ImportReport,
+    errors::{Conflict, InvalidInput, PreconditionFailed},
+};
+use online_market_model::{
+    AuditEntry, AvailabilityResponse, BookingResponse, CategoryLabel, CategoryResponse, CommentReportResponse, CommentResponse, CommentThread, FavoriteResponse, LabelsResponse, LocationPoint, RateResponse, RatingSummary, SellerOnboardingResponse, SellerSummary, ServiceArea, ServiceResponse,
+    SettingsResponse, TimeSlot, UserResponse,
+};
 use serde::Serialize;
+use utoipa::ToSchema;
 
+use self::auth_handler::TokenResponse;
+use crate::{i18n, request_id};
 
+pub mod audit_handler;
+pub mod auth_handler;
+pub mod availability_handler;
+pub mod booking_handler;
+pub mod bulk_handler;
 pub mod category_handler;
 pub mod user_handler;
 pub mod rate_handler;
 pub mod comment_handler;
+pub mod favorite_handler;
+pub mod label_handler;
+pub mod moderation_handler;
+pub mod seller_handler;
+pub mod service_handler;
+pub mod settings_handler;
 
+/// Envelope of every successful response
+#[derive(Serialize, ToSchema)]
+#[aliases(
+    CategoryResult = SuccessResponse<CategoryResponse>,
+    CategoryListResult = SuccessResponse<Vec<CategoryResponse>>,
+    CategoryLabelResult = SuccessResponse<CategoryLabel>,
+    LabelsResult = SuccessResponse<LabelsResponse>,
+    UserResult = SuccessResponse<UserResponse>,
+    UserListResult = SuccessResponse<Vec<UserResponse>>,
+    LocationPointListResult = SuccessResponse<Vec<LocationPoint>>,
+    RateResult = SuccessResponse<RateResponse>,
+    RateListResult = SuccessResponse<Vec<RateResponse>>,
+    RatingSummaryResult = SuccessResponse<RatingSummary>,
+    CommentResult = SuccessResponse<CommentResponse>,
+    CommentListResult = SuccessResponse<Vec<CommentResponse>>,
+    CommentThreadListResult = SuccessResponse<Vec<CommentThread>>,
+    CommentReportResult = SuccessResponse<CommentReportResponse>,
+    CommentReportListResult = SuccessResponse<Vec<CommentReportResponse>>,
+    ServiceResult = SuccessResponse<ServiceResponse>,
+    ServiceListResult = SuccessResponse<Vec<ServiceResponse>>,
+    ServiceAreaListResult = SuccessResponse<Vec<ServiceArea>>,
+    SettingsResult = SuccessResponse<SettingsResponse>,
+    AvailabilityResult = SuccessResponse<AvailabilityResponse>,
+    TimeSlotListResult = SuccessResponse<Vec<TimeSlot>>,
+    BookingResult = SuccessResponse<BookingResponse>,
+    FavoriteResult = SuccessResponse<FavoriteResponse>,
+    FavoriteListResult = SuccessResponse<Vec<FavoriteResponse>>,
+    SellerOnboardingResult = SuccessResponse<SellerOnboardingResponse>,
+    SellerSummaryListResult = SuccessResponse<Vec<SellerSummary>>,
+    AuditEntryListResult = SuccessResponse<Vec<AuditEntry>>,
+    ImportResult = SuccessResponse<ImportReport>,
+    TokenResult = SuccessResponse<TokenResponse>
+)]
+pub struct SuccessResponse<T> {
+    #[schema(example = "success")]
+    pub status: String,
+    pub result: T,
+}
+
+/// Envelope of failed responses that carry details about the failure
+#[derive(Serialize, ToSchema)]
+#[aliases(ImportFailure = FailResponse<ImportReport>)]
+pub struct FailResponse<T> {
+    #[schema(example = "fail")]
+    pub status: String,
+    pub result: T,
+}
+
+/// Envelope of every failed response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    #[schema(example = "fail")]
+    pub status: String,
+    /// Identifier of the error, the same in every language
+    #[schema(example = "NOT_FOUND")]
+    pub code: String,
+    /// Description of the error in the language of the Accept-Language header
+    #[schema(example = "THE RESOURCE DOESN'T EXIST")]
+    pub result: String,
+    /// Id of the request, also returned in the x-request-id header, to find it in the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "0f8d1c8e-5a43-4c1e-9f5e-2d0b7a3c6e11")]
+    pub request_id: Option<String>,
+}
 
 /// Returns a Json with status keys and payload for successful operations
 ///
@@ -17,9 +109,9 @@ pub fn build_success_response<T>(payload: T) -> serde_json::Value
 where
     T: Serialize,
 {
-    serde_json::json!({
-        "status": "success",
-        "result": payload
+    serde_json::json!(SuccessResponse {
+        status: "success".to_string(),
+        result: payload
     })
 }
 
@@ -33,9 +125,9 @@ pub fn build_success_multi_response<T>(payload: Vec<T>) -> serde_json::Value
 where
     T: Serialize,
 {
-    serde_json::json!({
-        "status": "success",
-        "result": payload
+    serde_json::json!(SuccessResponse {
+        status: "success".to_string(),
+        result: payload
     })
 }
 
@@ -46,8 +138,119 @@ where
 /// * payload - Object will be the value of the payload key. It must implements trait Serialize
 ///
 pub fn build_error_response(error: Box<dyn std::error::Error>) -> serde_json::Value {
-    serde_json::json!({
-        "status": "fail",
-        "result": format!("{}", error)
+    let (code, message) = i18n::error_message(error.as_ref());
+
+    // The message returned may hide the details of the error, which are only in the logs
+    if code == i18n::INTERNAL_ERROR_CODE {
+        tracing::error!(code, error = %error, "request failed");
+    } else {
+        tracing::debug!(code, error = %error, "request failed");
+    }
+
+    serde_json::json!(ErrorResponse {
+        status: "fail".to_string(),
+        code: code.to_string(),
+        result: message,
+        request_id: request_id::current()
     })
-}
\ No newline at end of file
+}
+
+/// Returns the status code and Json of an error returned by a repository that
+/// validates the state of the resources
+///
+/// # Argument
+///
+/// * error - Error returned by the repository
+///
+pub fn build_repository_error(
+    error: Box<dyn std::error::Error + Send + Sync>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let status = if error.is::<Conflict>() {
+        StatusCode::CONFLICT
+    } else if error.is::<InvalidInput>() {
+        StatusCode::BAD_REQUEST
+    } else if error.is::<PreconditionFailed>() {
+        StatusCode::PRECONDITION_FAILED
+    } else {
+        match error.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            // A referenced resource, like the category, doesn't exist
+            Some(sqlx::Error::Database(database_error))
+                if database_error.is_foreign_key_violation() =>
+            {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    };
+
+    (status, Json(build_error_response(error)))
+}
+
+/// Returns a Json with status keys and payload for failed operations that carry details
+///
+/// # Argument
+///
+/// * payload - Object will be the value of the payload key. It must implements trait Serialize
+///
+pub fn build_fail_response<T>(payload: T) -> serde_json::Value
+where
+    T: Serialize,
+{
+    serde_json::json!(FailResponse {
+        status: "fail".to_string(),
+        result: payload
+    })
+}
+
+/// Returns the ETag header of the given version of a resource
+pub fn etag(version: i32) -> [(HeaderName, String); 1] {
+    [(header::ETAG, format!("\"{}\"", version))]
+}
+
+/// Version of the resource required by the If-Match header of an update.
+///
+/// Updates without the header are rejected with 428, so clients can't overwrite changes they
+/// haven't seen. It takes the ETag of an update, "3", or of a read, "3.5f1c...", whose hash
+/// of the body is ignored. Values that aren't the ETag of a version are rejected with 412
+pub struct IfMatch(pub i32);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = match parts.headers.get(header::IF_MATCH) {
+            Some(value) => value,
+            None => {
+                let error: Box<dyn std::error::Error> = "THE IF-MATCH HEADER IS REQUIRED".into();
+
+                return Err((
+                    StatusCode::PRECONDITION_REQUIRED,
+                    Json(build_error_response(error)),
+                ));
+            }
+        };
+
+        let version = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().strip_prefix('"'))
+            .and_then(|value| value.strip_suffix('"'))
+            .and_then(|value| value.split('.').next())
+            .and_then(|value| value.parse::<i32>().ok());
+
+        match version {
+            Some(version) => Ok(IfMatch(version)),
+            None => {
+                let error: Box<dyn std::error::Error> =
+                    "THE IF-MATCH HEADER DOESN'T MATCH ANY VERSION".into();
+
+                Err((
+                    StatusCode::PRECONDITION_FAILED,
+                    Json(build_error_response(error)),
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_data::{Pagination, PaginationRequest};
+use std::sync::Arc;
+
+use online_market_model::{CommentReport, ModerationDecision};
+use serde_json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{AdminUser, AuthUser},
+    AppState,
+};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+#[utoipa::path(
+    post,
+    path="/comment/{id}/report",
+    tag="comment",
+    params(
+        ("id" = Uuid, Path, description = "Id of the comment")
+    ),
+    request_body = CommentReport,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "Comment reported to the moderators", body = CommentReportResult),
+        (status=400, description = "Invalid reason", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "No comment found", body = ErrorResponse),
+        (status=409, description = "The comment is already reported by the user or is their own", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn report_comment(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(report): Json<CommentReport>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .moderation_repository
+        .report(user.dni, id, report.reason, &app.db)
+        .await;
+
+    match result {
+        Ok(report) => {
+            let response = build_success_response(report);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/moderation/queue",
+    tag="moderation",
+    params(
+        online_market_data::PaginationRequest
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Open reports, the oldest first", body = CommentReportListResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_moderation_queue(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(pagination): Query<PaginationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // Creation of pagination
+    // If no per_page or page is provided the default values will be used
+    let pagination = Pagination::new(pagination);
+
+    let result = app
+        .moderation_repository
+        .get_queue(pagination, &app.db)
+        .await;
+
+    match result {
+        Ok(reports) => {
+            let response = build_success_response(reports);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/moderation/report/{id}",
+    tag="moderation",
+    params(
+        ("id" = Uuid, Path, description = "Id of the report")
+    ),
+    request_body = ModerationDecision,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Action applied", body = CommentReportResult),
+        (status=400, description = "Invalid reason", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=409, description = "The report is already resolved or the comment isn't hidden", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn resolve_report(
+    State(app): State<Arc<AppState>>,
+    AdminUser(admin): AdminUser,
+    Path(id): Path<Uuid>,
+    Json(decision): Json<ModerationDecision>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .moderation_repository
+        .resolve(id, admin.dni, decision, &app.db)
+        .await;
+
+    match result {
+        Ok(report) => {
+            let response = build_success_response(report);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
@@ -11,23 +11,37 @@ use serde_json;
 
 use online_market_model::Rate;
 
-use crate::AppState;
+use crate::{
+    auth::{forbidden, may_act_as, AuthUser},
+    AppState,
+};
 
-use super::{build_error_response, build_success_response};
+use super::{
+    build_error_response, build_repository_error, build_success_response, etag, IfMatch,
+};
 
 #[utoipa::path(
     post,
     path="/rate",
+    tag="rate",
+    request_body = Rate,
+    security((), ("bearer_auth" = [])),
     responses(
-        (status=201, description = "Rate saved"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=201, description = "Rate saved", body = RateResult),
+        (status=403, description = "The token belongs to another user than the rater", body = ErrorResponse),
+        (status=409, description = "The rater has no completed booking with the rated user and verification is required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn save_rate(
     State(app): State<Arc<AppState>>,
+    user: Option<AuthUser>,
     Json(rate): Json<Rate>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !may_act_as(user.as_ref(), &rate.rater) {
+        return Err(forbidden("YOU CAN ONLY RATE ON YOUR OWN BEHALF"));
+    }
+
     let result = app.rate_repository.save(rate, &app.db).await;
 
     match result {
@@ -36,26 +50,29 @@ pub async fn save_rate(
 
             Ok((StatusCode::CREATED, Json(response)))
         }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
-        }
+        Err(error) => Err(build_repository_error(error)),
     }
 }
 
 #[utoipa::path(
     get,
     path="/rate/{id_rater}/{id_rated}",
+    tag="rate",
+    params(
+        ("id_rater" = String, Path, description = "Dni of the user who rated"),
+        ("id_rated" = String, Path, description = "Dni of the rated user")
+    ),
     responses(
-        (status=200, description = "Get rates by rated"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get rates by rated", body = RateResult, headers(
+            ("ETag" = String, description = "Version of the rate, sent in the If-Match header to update it")
+        )),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_rate(
     State(app): State<Arc<AppState>>,
-    Path(id_rater): Path<String>,
-    Path(id_rated): Path<String>,
+    Path((id_rater, id_rated)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let result = app
         .rate_repository
@@ -64,27 +81,74 @@ pub async fn get_rate(
 
     match result {
         Ok(rate) => {
+            let headers = etag(rate.version);
             let response = build_success_response(rate);
 
-            Ok((StatusCode::OK, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/rate/summary/{dni}",
+    tag="rate",
+    params(
+        ("dni" = String, Path, description = "Dni of the rated user")
+    ),
+    responses(
+        (status=200, description = "Number and average of the rates received by the user", body = RatingSummaryResult),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_rating_summary(
+    State(app): State<Arc<AppState>>,
+    Path(dni): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.rate_repository.get_summary(dni, &app.db).await;
+
+    match result {
+        Ok(summary) => {
+            let response = build_success_response(summary);
+
+            Ok((StatusCode::OK, Json(response)))
         }
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
     }
 }
 
 #[utoipa::path(
     get,
     path="/rate/rater/{id_rater}",
+    tag="rate",
     params(
+        ("id_rater" = String, Path, description = "Dni of the user who rated"),
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get rates by rater"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get rates by rater", body = RateListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_rates_by_rater(
@@ -123,13 +187,15 @@ pub async fn get_rates_by_rater(
 #[utoipa::path(
     get,
     path="/rate/rated/{id_rated}",
+    tag="rate",
     params(
+        ("id_rated" = String, Path, description = "Dni of the rated user"),
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get rates by rated"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Get rates by rated", body = RateListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_rates_by_rated(
@@ -168,30 +234,43 @@ pub async fn get_rates_by_rated(
 #[utoipa::path(
     patch,
     path="/rate/update",
+    tag="rate",
+    params(
+        ("If-Match" = String, Header, description = "ETag of the rate returned by GET /rate/{id_rater}/{id_rated}")
+    ),
+    request_body = Rate,
+    security((), ("bearer_auth" = [])),
     responses(
-        (status=200, description = "Update rate"),
-        (status=404, description = "Not found"),
-        (status=500, description = "Internal error")
+        (status=200, description = "Update rate", body = RateResult),
+        (status=403, description = "The token belongs to another user than the rater", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=409, description = "The rater has no completed booking with the rated user and verification is required", body = ErrorResponse),
+        (status=412, description = "The rate changed since the given ETag", body = ErrorResponse),
+        (status=428, description = "Missing If-Match header", body = ErrorResponse)
     )
 )]
 pub async fn update_rate(
     State(app): State<Arc<AppState>>,
+    user: Option<AuthUser>,
+    IfMatch(version): IfMatch,
     Json(rate): Json<Rate>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !may_act_as(user.as_ref(), &rate.rater) {
+        return Err(forbidden("YOU CAN ONLY UPDATE YOUR OWN RATES"));
+    }
+
     let result = app
         .rate_repository
-        .update_rate(rate, &app.db)
+        .update_rate(rate, version, &app.db)
         .await;
 
     match result {
         Ok(rate) => {
+            let headers = etag(rate.version);
             let response = build_success_response(rate);
 
-            Ok((StatusCode::OK, Json(response)))
-        }
-        Err(error) => {
-            let response = build_error_response(Box::new(error));
-            Err((StatusCode::NOT_FOUND, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
+        Err(error) => Err(build_repository_error(error)),
     }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use axum::{
+    extract::{Json, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_data::SellerListRequest;
+use std::sync::Arc;
+
+use online_market_model::SellerOnboarding;
+use serde_json;
+
+use crate::{auth::AuthUser, AppState};
+
+use super::{build_error_response, build_repository_error, build_success_response};
+
+#[utoipa::path(
+    post,
+    path="/seller/onboard",
+    tag="seller",
+    request_body = SellerOnboarding,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "The user is now a seller and its first service was published", body = SellerOnboardingResult),
+        (status=400, description = "The category doesn't exist", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=409, description = "The user is already a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn onboard_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(onboarding): Json<SellerOnboarding>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .seller_repository
+        .onboard(user.dni, onboarding, &app.db)
+        .await;
+
+    match result {
+        Ok(onboarding) => {
+            let response = build_success_response(onboarding);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/seller/pause",
+    tag="seller",
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The seller is paused and its services aren't listed", body = UserResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=409, description = "The user is not a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn pause_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .seller_repository
+        .set_paused(user.dni, true, &app.db)
+        .await;
+
+    match result {
+        Ok(user) => {
+            let response = build_success_response(user);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/seller/resume",
+    tag="seller",
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The seller is active again", body = UserResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=409, description = "The user is not a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn resume_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .seller_repository
+        .set_paused(user.dni, false, &app.db)
+        .await;
+
+    match result {
+        Ok(user) => {
+            let response = build_success_response(user);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/seller/leave",
+    tag="seller",
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "The user is not a seller anymore and its services were removed", body = UserResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=409, description = "The user is not a seller", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn leave_seller(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.seller_repository.leave(user.dni, &app.db).await;
+
+    match result {
+        Ok(user) => {
+            let response = build_success_response(user);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/seller/all",
+    tag="seller",
+    params(
+        online_market_data::SellerListRequest
+    ),
+    responses(
+        (status=200, description = "Active sellers, the highest reputation first unless another order is given", body = SellerSummaryListResult),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_all_sellers(
+    State(app): State<Arc<AppState>>,
+    Query(request): Query<SellerListRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // If no per_page or page is provided the default values will be used
+    let result = app.seller_repository.get_all(request, &app.db).await;
+
+    match result {
+        Ok(sellers) => {
+            let response = build_success_response(sellers);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use online_market_data::{
+    area::PointRequest, Pagination, PaginationRequest, ServiceSearchRequest,
+};
+use std::sync::Arc;
+
+use online_market_model::{Service, ServiceArea, ServiceResponse};
+use serde_json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{forbidden, AuthUser},
+    AppState,
+};
+
+use super::{
+    build_error_response, build_repository_error, build_success_response, etag, IfMatch,
+};
+
+/// Returns the service if the authenticated user can manage it
+async fn get_owned_service(
+    app: &AppState,
+    user: &AuthUser,
+    id: Uuid,
+) -> Result<ServiceResponse, (StatusCode, Json<serde_json::Value>)> {
+    match app.service_repository.get_by_id(id, &app.db).await {
+        Ok(service) if user.can_act_as(&service.user_id) => Ok(service),
+        Ok(_) => Err(forbidden("YOU CAN ONLY MANAGE YOUR OWN SERVICES")),
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    post,
+    path="/service",
+    tag="service",
+    request_body = Service,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=201, description = "Service published", body = ServiceResult),
+        (status=400, description = "The category doesn't exist", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The seller is not the authenticated user", body = ErrorResponse),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=409, description = "The user is not a seller or reached the limit of services", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn save_service(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(service): Json<Service>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !user.can_act_as(&service.user_id) {
+        return Err(forbidden(
+            "YOU CAN ONLY PUBLISH SERVICES ON YOUR OWN BEHALF",
+        ));
+    }
+
+    let result = app.service_repository.save(service, &app.db).await;
+
+    match result {
+        Ok(service) => {
+            let response = build_success_response(service);
+
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/service/{id}",
+    tag="service",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    responses(
+        (status=200, description = "Get service by id", body = ServiceResult, headers(
+            ("ETag" = String, description = "Version of the service, sent in the If-Match header to update it")
+        )),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_service_by_id(
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.service_repository.get_by_id(id, &app.db).await;
+
+    match result {
+        Ok(service) => {
+            let headers = etag(service.version);
+            let response = build_success_response(service);
+
+            Ok((StatusCode::OK, headers, Json(response)))
+        }
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/service/seller/{dni}",
+    tag="service",
+    params(
+        ("dni" = String, Path, description = "Dni of the seller"),
+        online_market_data::PaginationRequest
+    ),
+    responses(
+        (status=200, description = "Get services of a seller", body = ServiceListResult),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_services_by_seller(
+    State(app): State<Arc<AppState>>,
+    Path(dni): Path<String>,
+    Query(pagination): Query<PaginationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    // Creation of pagination
+    // If no per_page or page is provided the default values will be used
+    let pagination = Pagination::new(pagination);
+
+    let result = app
+        .service_repository
+        .get_by_seller(dni, pagination, &app.db)
+        .await;
+
+    match result {
+        Ok(services) => {
+            let response = build_success_response(services);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path="/service/{id}",
+    tag="service",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service"),
+        ("If-Match" = String, Header, description = "ETag of the service returned by GET /service/{id}")
+    ),
+    request_body = Service,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Update service", body = ServiceResult),
+        (status=400, description = "The category doesn't exist", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The service belongs to another seller", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=412, description = "The service changed since the given ETag", body = ErrorResponse),
+        (status=428, description = "Missing If-Match header", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn update_service(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    IfMatch(version): IfMatch,
+    Json(mut service): Json<Service>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    get_owned_service(&app, &user, id).await?;
+
+    // The id of the path identifies the service, not the one of the body
+    service.id = Some(id);
+
+    let result = app
+        .service_repository
+        .update_service(service, version, &app.db)
+        .await;
+
+    match result {
+        Ok(service) => {
+            let headers = etag(service.version);
+            let response = build_success_response(service);
+
+            Ok((StatusCode::OK, headers, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path="/service/{id}",
+    tag="service",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Service deleted", body = ServiceResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The service belongs to another seller", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn delete_service(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let service = get_owned_service(&app, &user, id).await?;
+
+    let result = app.service_repository.delete_service(id, &app.db).await;
+
+    match result {
+        Ok(()) => {
+            let response = build_success_response(service);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => match error {
+            sqlx::Error::RowNotFound => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::NOT_FOUND, Json(response)))
+            }
+            _ => {
+                let response = build_error_response(Box::new(error));
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+            }
+        },
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/service/{id}/areas",
+    tag="service",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    responses(
+        (status=200, description = "Areas the seller travels to for the service", body = ServiceAreaListResult),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_service_areas(
+    State(app): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.service_area_repository.get_areas(id, &app.db).await;
+
+    match result {
+        Ok(areas) => {
+            let response = build_success_response(areas);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path="/service/{id}/areas",
+    tag="service",
+    params(
+        ("id" = Uuid, Path, description = "Id of the service")
+    ),
+    request_body = Vec<ServiceArea>,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Areas of the service replaced", body = ServiceAreaListResult),
+        (status=400, description = "Invalid geometry or the service isn't a home-visit one", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The service belongs to another seller", body = ErrorResponse),
+        (status=404, description = "Not found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn set_service_areas(
+    State(app): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(areas): Json<Vec<ServiceArea>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    get_owned_service(&app, &user, id).await?;
+
+    let result = app
+        .service_area_repository
+        .set_areas(id, areas, &app.db)
+        .await;
+
+    match result {
+        Ok(areas) => {
+            let response = build_success_response(areas);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/service/covering",
+    tag="service",
+    params(
+        PointRequest
+    ),
+    responses(
+        (status=200, description = "Home-visit services with an area that covers the point", body = ServiceListResult),
+        (status=400, description = "Invalid coordinates", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_covering_services(
+    State(app): State<Arc<AppState>>,
+    Query(request): Query<PointRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .service_area_repository
+        .covering_services(request, &app.db)
+        .await;
+
+    match result {
+        Ok(services) => {
+            let response = build_success_response(services);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/service/search",
+    tag="service",
+    params(
+        ServiceSearchRequest
+    ),
+    responses(
+        (status=200, description = "Services a buyer at the point can get. Home-visit services only when they cover it", body = ServiceListResult),
+        (status=400, description = "Invalid coordinates", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn search_services(
+    State(app): State<Arc<AppState>>,
+    Query(request): Query<ServiceSearchRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.service_repository.search(request, &app.db).await;
+
+    match result {
+        Ok(services) => {
+            let response = build_success_response(services);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
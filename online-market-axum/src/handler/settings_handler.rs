@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use online_market_model::Settings;
+use serde_json;
+
+use crate::{auth::AdminUser, AppState};
+
+use super::{build_error_response, build_success_response};
+
+#[utoipa::path(
+    get,
+    path="/settings",
+    tag="settings",
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Current settings of the market", body = SettingsResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_settings(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app.settings_repository.get(&app.db).await;
+
+    match result {
+        Ok(settings) => {
+            let response = build_success_response(settings);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path="/settings",
+    tag="settings",
+    request_body = Settings,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Settings updated", body = SettingsResult),
+        (status=400, description = "Invalid settings", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "Admin role required", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn update_settings(
+    State(app): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Json(settings): Json<Settings>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if matches!(settings.max_services_per_seller, Some(limit) if limit < 1) {
+        let error: Box<dyn std::error::Error> =
+            "THE LIMIT OF SERVICES PER SELLER MUST BE AT LEAST 1".into();
+        return Err((StatusCode::BAD_REQUEST, Json(build_error_response(error))));
+    }
+
+    if matches!(settings.reputation_prior_mean, Some(mean) if !(mean >= 0.0 && mean.is_finite()))
+        || matches!(settings.reputation_prior_weight, Some(weight) if !(weight > 0.0 && weight.is_finite()))
+        || matches!(settings.reputation_half_life_days, Some(days) if days < 1)
+    {
+        let error: Box<dyn std::error::Error> =
+            "THE PRIOR MEAN CAN'T BE NEGATIVE AND THE PRIOR WEIGHT AND HALF LIFE MUST BE POSITIVE"
+                .into();
+        return Err((StatusCode::BAD_REQUEST, Json(build_error_response(error))));
+    }
+
+    if matches!(settings.location_retention_days, Some(days) if days < 1) {
+        let error: Box<dyn std::error::Error> =
+            "THE LOCATION RETENTION MUST BE AT LEAST 1 DAY".into();
+        return Err((StatusCode::BAD_REQUEST, Json(build_error_response(error))));
+    }
+
+    if matches!(settings.location_grid_meters, Some(meters) if meters < 1) {
+        let error: Box<dyn std::error::Error> =
+            "THE LOCATION GRID MUST BE AT LEAST 1 METER".into();
+        return Err((StatusCode::BAD_REQUEST, Json(build_error_response(error))));
+    }
+
+    let result = app.settings_repository.update(settings, &app.db).await;
+
+    match result {
+        Ok(settings) => {
+            let response = build_success_response(settings);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => {
+            let response = build_error_response(Box::new(error));
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
+        }
+    }
+}
@@ -7,77 +7,151 @@ use axum::{
 use axum_typed_websockets::{Message, WebSocket, WebSocketUpgrade};
 
 use futures::{SinkExt, StreamExt};
-use online_market_data::{Pagination, PaginationRequest};
+use online_market_data::{
+    audit,
+    location::{LocationPolicy, NearbyRequest, TrajectoryRequest},
+    Pagination, PaginationRequest,
+};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tracing::Instrument;
 
-use online_market_model::{User, UserLocation};
+use online_market_model::{LocationVisibilityUpdate, User, UserLocation};
 use serde_json;
 
-use crate::AppState;
+use crate::{
+    auth::{forbidden, may_act_as, AuthUser},
+    metrics::{LOCATION_UPDATES_TOTAL, WS_ACTIVE_CONNECTIONS},
+    AppState, LocationUpdate,
+};
+
+use super::{
+    build_error_response, build_repository_error, build_success_multi_response,
+    build_success_response, etag, IfMatch,
+};
 
-use super::{build_error_response, build_success_multi_response, build_success_response};
+/// Loads the rules to show the locations of the users to the viewer of the request
+async fn location_policy(
+    app: &AppState,
+    viewer: Option<&AuthUser>,
+) -> Result<LocationPolicy, (StatusCode, Json<serde_json::Value>)> {
+    app.location_privacy_repository
+        .policy(viewer.map(|viewer| (viewer.dni.as_str(), &viewer.rol)), &app.db)
+        .await
+        .map_err(|error| {
+            let response = build_error_response(Box::new(error));
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        })
+}
 
+/// Upgrades the connection to a WebSocket that receives the locations of the users.
+///
+/// Every text frame must be a UserLocation serialized as Json. The server answers each frame with
+/// a number: 200 when the location was saved, 403 when the connection was opened with the token
+/// of another user and 500 when the location couldn't be saved.
+#[utoipa::path(
+    get,
+    path="/ws/user/update/location",
+    tag="user",
+    security((), ("bearer_auth" = [])),
+    responses(
+        (status=101, description = "Switching protocols. Frames sent by the client are UserLocation, frames sent by the server are status numbers")
+    )
+)]
 pub async fn handler_user_location(
     ws: WebSocketUpgrade<i16, UserLocation>,
     State(app): State<Arc<AppState>>,
+    user: Option<AuthUser>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| update_user_location_socket(socket, app))
+    // The upgraded connection runs in another task, so the audit context and the span of the
+    // request go with it
+    let context = audit::current();
+    let span = tracing::Span::current();
+
+    ws.on_upgrade(|socket| {
+        audit::scope(context, update_user_location_socket(socket, app, user)).instrument(span)
+    })
 }
 
-pub async fn update_user_location_socket(socket: WebSocket<i16, UserLocation>, app: Arc<AppState>) {
+pub async fn update_user_location_socket(
+    socket: WebSocket<i16, UserLocation>,
+    app: Arc<AppState>,
+    user: Option<AuthUser>,
+) {
+    WS_ACTIVE_CONNECTIONS.inc();
+
     // create channel if time this function is called
     let (tx, mut rx) = mpsc::unbounded_channel::<UserLocation>();
 
     // split the new web socket connection in sender and receiver
     let (mut sender, mut receiver) = socket.split();
 
-    tokio::spawn(async move {
+    let context = audit::current();
+
+    tokio::spawn(audit::scope(context, async move {
         while let Some(user_location) = rx.recv().await {
+            // A token limits the connection to the locations of its user
+            if !may_act_as(user.as_ref(), &user_location.dni) {
+                LOCATION_UPDATES_TOTAL.with_label_values(&["forbidden"]).inc();
+                let _ = sender.send(Message::Item(403)).await;
+                continue;
+            }
+
             let result = app
                 .user_repository
-                .update_location(user_location, &app.db)
+                .update_location(user_location.clone(), &app.db)
                 .await;
 
             match result {
-                Ok(_) => {
-                    let _ = sender.send(Message::Item(200));
+                Ok(visibility) => {
+                    LOCATION_UPDATES_TOTAL.with_label_values(&["ok"]).inc();
+                    // Nobody may be following the locations, which isn't an error
+                    if let Some(visibility) = visibility {
+                        let _ = app.location_updates.send(LocationUpdate {
+                            location: user_location,
+                            visibility,
+                        });
+                    }
+                    let _ = sender.send(Message::Item(200)).await;
                 }
                 Err(_) => {
-                    let _ = sender.send(Message::Item(500));
+                    LOCATION_UPDATES_TOTAL.with_label_values(&["error"]).inc();
+                    let _ = sender.send(Message::Item(500)).await;
                 }
             }
         }
-    });
+    }).instrument(tracing::Span::current()));
 
     while let Some(message) = receiver.next().await {
         match message {
-            Ok(message) => match message {
-                Message::Item(user_location) => {
+            Ok(message) => {
+                if let Message::Item(user_location) = message {
                     tx.send(user_location).unwrap();
                 }
-                _ => {}
-            },
-            Err(e) => {
-                println!("{}", e);
+            }
+            Err(error) => {
+                tracing::warn!(%error, "Invalid frame on the location WebSocket");
             }
         }
     }
+
+    WS_ACTIVE_CONNECTIONS.dec();
 }
 
 #[utoipa::path(
     post,
     path="/user",
+    tag="user",
+    request_body = User,
     responses(
-        (status=201, description = "User saved"),
-        (status=500, description = "Internal error")
+        (status=201, description = "User saved", body = UserResult),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn save_user(
     State(app): State<Arc<AppState>>,
     Json(user): Json<User>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    println!("{:?}", user);
     let result = app.user_repository.save(user, &app.db).await;
 
     match result {
@@ -95,22 +169,35 @@ pub async fn save_user(
 #[utoipa::path(
     get,
     path="/user/{dni}",
+    tag="user",
+    params(
+        ("dni" = String, Path, description = "Dni of the user")
+    ),
     responses(
-        (status=200, description = "Get user by id"),
-        (status=404, description = "No user found")
+        (status=200, description = "Get user by id", body = UserResult, headers(
+            ("ETag" = String, description = "Version of the user, sent in the If-Match header to update it")
+        )),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_user_by_dni(
     Path(dni): Path<String>,
     State(app): State<Arc<AppState>>,
+    viewer: Option<AuthUser>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let policy = location_policy(&app, viewer.as_ref()).await?;
+
     let result = app.user_repository.get_by_dni(dni, &app.db).await;
 
     match result {
-        Ok(user) => {
+        Ok(mut user) => {
+            policy.apply(&mut user);
+
+            let headers = etag(user.version);
             let response = build_success_response(user);
 
-            Ok((StatusCode::OK, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
         Err(error) => match error {
             sqlx::Error::RowNotFound => {
@@ -128,18 +215,23 @@ pub async fn get_user_by_dni(
 #[utoipa::path(
     get,
     path="/user/all",
+    tag="user",
     params(
         online_market_data::PaginationRequest
     ),
     responses(
-        (status=200, description = "Get all users"),
-        (status=404, description = "No user found")
+        (status=200, description = "Get all users", body = UserListResult),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn get_all_user(
     State(app): State<Arc<AppState>>,
+    viewer: Option<AuthUser>,
     Query(pagination): Query<PaginationRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let policy = location_policy(&app, viewer.as_ref()).await?;
+
     // Creation of pagination
     // If no per_page or page is provided the default values will be used
     let pagination = Pagination::new(pagination);
@@ -147,9 +239,12 @@ pub async fn get_all_user(
     let result = app.user_repository.get_all(pagination, &app.db).await;
 
     match result {
-        Ok(user) => {
-            let response = build_success_multi_response(user);
-            println!("{}", response);
+        Ok(mut users) => {
+            for user in users.iter_mut() {
+                policy.apply(user);
+            }
+
+            let response = build_success_multi_response(users);
             Ok((StatusCode::OK, Json(response)))
         }
         Err(error) => match error {
@@ -168,25 +263,155 @@ pub async fn get_all_user(
 #[utoipa::path(
     patch,
     path="/user/update",
+    tag="user",
+    params(
+        ("If-Match" = String, Header, description = "ETag of the user returned by GET /user/{dni}")
+    ),
+    request_body = User,
+    security((), ("bearer_auth" = [])),
     responses(
-        (status=200, description = "User updated"),
-        (status=404, description = "No user found")
+        (status=200, description = "User updated", body = UserResult),
+        (status=403, description = "The token belongs to another user", body = ErrorResponse),
+        (status=404, description = "No user found", body = ErrorResponse),
+        (status=412, description = "The user changed since the given ETag", body = ErrorResponse),
+        (status=428, description = "Missing If-Match header", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
     )
 )]
 pub async fn update_user(
     State(app): State<Arc<AppState>>,
+    auth_user: Option<AuthUser>,
+    IfMatch(version): IfMatch,
     Json(user): Json<User>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let result = app.user_repository.update_user(user, &app.db).await;
+    if !may_act_as(auth_user.as_ref(), &user.dni) {
+        return Err(forbidden("YOU CAN ONLY UPDATE YOUR OWN USER"));
+    }
+
+    let result = app
+        .user_repository
+        .update_user(user, version, &app.db)
+        .await;
 
     match result {
         Ok(user) => {
+            let headers = etag(user.version);
             let response = build_success_response(user);
+            Ok((StatusCode::OK, headers, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/user/{dni}/locations",
+    tag="user",
+    params(
+        ("dni" = String, Path, description = "Dni of the user"),
+        TrajectoryRequest
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Locations of the user in the window of time, oldest first", body = LocationPointListResult),
+        (status=400, description = "Invalid window of time or max_points", body = ErrorResponse),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=403, description = "The locations belong to another user", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_user_locations(
+    State(app): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(dni): Path<String>,
+    Query(request): Query<TrajectoryRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if !auth_user.can_act_as(&dni) {
+        return Err(forbidden("YOU CAN ONLY SEE YOUR OWN LOCATIONS"));
+    }
+
+    let result = app
+        .location_history_repository
+        .trajectory(dni, request, &app.db)
+        .await;
+
+    match result {
+        Ok(points) => {
+            let response = build_success_response(points);
+
+            Ok((StatusCode::OK, Json(response)))
+        }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/user/nearby",
+    tag="user",
+    params(
+        NearbyRequest
+    ),
+    responses(
+        (status=200, description = "Users whose visible location is within the radius, nearest first", body = UserListResult),
+        (status=400, description = "Invalid coordinates or radius", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn get_nearby_users(
+    State(app): State<Arc<AppState>>,
+    viewer: Option<AuthUser>,
+    Query(request): Query<NearbyRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let policy = location_policy(&app, viewer.as_ref()).await?;
+
+    let result = app
+        .location_privacy_repository
+        .nearby(request, &policy, &app.db)
+        .await;
+
+    match result {
+        Ok(users) => {
+            let response = build_success_response(users);
+
             Ok((StatusCode::OK, Json(response)))
         }
+        Err(error) => Err(build_repository_error(error)),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path="/user/location/visibility",
+    tag="user",
+    request_body = LocationVisibilityUpdate,
+    security(("bearer_auth" = [])),
+    responses(
+        (status=200, description = "Visibility of the location of the authenticated user changed", body = UserResult),
+        (status=401, description = "Missing or invalid token", body = ErrorResponse),
+        (status=500, description = "Internal error", body = ErrorResponse)
+    )
+)]
+pub async fn update_location_visibility(
+    State(app): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(update): Json<LocationVisibilityUpdate>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let result = app
+        .user_repository
+        .update_location_visibility(auth_user.dni, update.location_visibility, &app.db)
+        .await;
+
+    match result {
+        Ok(user) => {
+            let headers = etag(user.version);
+            let response = build_success_response(user);
+
+            Ok((StatusCode::OK, headers, Json(response)))
+        }
         Err(error) => {
             let response = build_error_response(Box::new(error));
-            Err((StatusCode::NOT_FOUND, Json(response)))
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(response)))
         }
     }
 }
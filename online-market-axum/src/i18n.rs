@@ -0,0 +1,472 @@
+use std::{error::Error, future::Future};
+
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use online_market_model::Language;
+
+tokio::task_local! {
+    static LANGUAGE: Language;
+}
+
+/// Message of an error in every language, identified by a code that doesn't change
+struct Message {
+    code: &'static str,
+    en: &'static str,
+    es: &'static str,
+}
+
+impl Message {
+    fn text(&self, language: Language) -> &'static str {
+        match language {
+            Language::En => self.en,
+            Language::Es => self.es,
+        }
+    }
+}
+
+const NOT_FOUND: Message = Message {
+    code: "NOT_FOUND",
+    en: "THE RESOURCE DOESN'T EXIST",
+    es: "EL RECURSO NO EXISTE",
+};
+
+const REFERENCE_NOT_FOUND: Message = Message {
+    code: "REFERENCE_NOT_FOUND",
+    en: "A RESOURCE REFERENCED BY THE REQUEST DOESN'T EXIST",
+    es: "UN RECURSO REFERENCIADO POR LA SOLICITUD NO EXISTE",
+};
+
+/// Code of the unexpected errors, whose details aren't returned to the clients
+pub const INTERNAL_ERROR_CODE: &str = "INTERNAL_ERROR";
+
+const INTERNAL_ERROR: Message = Message {
+    code: INTERNAL_ERROR_CODE,
+    en: "SOMETHING WENT WRONG",
+    es: "ALGO SALIÓ MAL",
+};
+
+/// Code of the errors that aren't in the catalogue, whose message isn't translated
+const UNKNOWN_ERROR: &str = "ERROR";
+
+/// Messages of the errors returned by the handlers and repositories. The English one is the
+/// message of the error, which is used to find it
+const CATALOGUE: &[Message] = &[
+    Message {
+        code: "ADMIN_ROLE_REQUIRED",
+        en: "ADMIN ROLE REQUIRED",
+        es: "SE REQUIERE EL ROL DE ADMINISTRADOR",
+    },
+    Message {
+        code: "MISSING_TOKEN",
+        en: "MISSING BEARER TOKEN",
+        es: "FALTA EL TOKEN BEARER",
+    },
+    Message {
+        code: "INVALID_TOKEN",
+        en: "INVALID OR EXPIRED TOKEN",
+        es: "EL TOKEN ES INVÁLIDO O EXPIRÓ",
+    },
+    Message {
+        code: "INVALID_CREDENTIALS",
+        en: "INVALID CREDENTIALS",
+        es: "CREDENCIALES INVÁLIDAS",
+    },
+    Message {
+        code: "INVALID_ID",
+        en: "INVALID ID",
+        es: "ID INVÁLIDO",
+    },
+    Message {
+        code: "IF_MATCH_REQUIRED",
+        en: "THE IF-MATCH HEADER IS REQUIRED",
+        es: "EL ENCABEZADO IF-MATCH ES OBLIGATORIO",
+    },
+    Message {
+        code: "IF_MATCH_INVALID",
+        en: "THE IF-MATCH HEADER DOESN'T MATCH ANY VERSION",
+        es: "EL ENCABEZADO IF-MATCH NO CORRESPONDE A NINGUNA VERSIÓN",
+    },
+    Message {
+        code: "VERSION_CHANGED",
+        en: "THE RESOURCE WAS CHANGED SINCE THE GIVEN VERSION",
+        es: "EL RECURSO CAMBIÓ DESDE LA VERSIÓN INDICADA",
+    },
+    Message {
+        code: "NOT_OWN_USER",
+        en: "YOU CAN ONLY UPDATE YOUR OWN USER",
+        es: "SOLO PUEDES ACTUALIZAR TU PROPIO USUARIO",
+    },
+    Message {
+        code: "AMOUNT_NEGATIVE",
+        en: "AMOUNT CAN'T BE NEGATIVE",
+        es: "EL MONTO NO PUEDE SER NEGATIVO",
+    },
+    Message {
+        code: "AMOUNT_TOO_PRECISE",
+        en: "AMOUNT CAN'T HAVE MORE THAN 2 DECIMALS",
+        es: "EL MONTO NO PUEDE TENER MÁS DE 2 DECIMALES",
+    },
+    Message {
+        code: "AMOUNT_TOO_LARGE",
+        en: "AMOUNT IS TOO LARGE",
+        es: "EL MONTO ES DEMASIADO GRANDE",
+    },
+    Message {
+        code: "INVALID_CURRENCY",
+        en: "CURRENCY MUST BE AN ISO 4217 CODE",
+        es: "LA MONEDA DEBE SER UN CÓDIGO ISO 4217",
+    },
+    Message {
+        code: "NO_SERVICE_ID",
+        en: "NO ID PROVIDED TO UPDATE THE SERVICE",
+        es: "NO SE INDICÓ EL ID DEL SERVICIO A ACTUALIZAR",
+    },
+    Message {
+        code: "NOT_SERVICE_OWNER",
+        en: "YOU CAN ONLY MANAGE YOUR OWN SERVICES",
+        es: "SOLO PUEDES ADMINISTRAR TUS PROPIOS SERVICIOS",
+    },
+    Message {
+        code: "PUBLISH_ON_OTHERS_BEHALF",
+        en: "YOU CAN ONLY PUBLISH SERVICES ON YOUR OWN BEHALF",
+        es: "SOLO PUEDES PUBLICAR SERVICIOS EN TU PROPIO NOMBRE",
+    },
+    Message {
+        code: "SERVICE_LIMIT_REACHED",
+        en: "THE SELLER REACHED THE LIMIT OF SERVICES",
+        es: "EL VENDEDOR ALCANZÓ EL LÍMITE DE SERVICIOS",
+    },
+    Message {
+        code: "NOT_A_SELLER",
+        en: "USER IS NOT A SELLER",
+        es: "EL USUARIO NO ES VENDEDOR",
+    },
+    Message {
+        code: "ALREADY_A_SELLER",
+        en: "USER IS ALREADY A SELLER",
+        es: "EL USUARIO YA ES VENDEDOR",
+    },
+    Message {
+        code: "SELLER_PAUSED",
+        en: "THE SELLER IS PAUSED",
+        es: "EL VENDEDOR ESTÁ EN PAUSA",
+    },
+    Message {
+        code: "SELLER_WITHOUT_AVAILABILITY",
+        en: "THE SELLER HAS NO AVAILABILITY",
+        es: "EL VENDEDOR NO TIENE DISPONIBILIDAD",
+    },
+    Message {
+        code: "INVALID_TIMEZONE",
+        en: "TIMEZONE MUST BE AN IANA TIMEZONE",
+        es: "LA ZONA HORARIA DEBE SER UNA ZONA HORARIA IANA",
+    },
+    Message {
+        code: "INVALID_AVAILABILITY_WINDOW",
+        en: "WINDOWS MUST START BEFORE THEY END",
+        es: "LAS FRANJAS DEBEN EMPEZAR ANTES DE TERMINAR",
+    },
+    Message {
+        code: "AVAILABILITY_WINDOWS_OVERLAP",
+        en: "AVAILABILITY WINDOWS CAN'T OVERLAP",
+        es: "LAS FRANJAS DE DISPONIBILIDAD NO PUEDEN SUPERPONERSE",
+    },
+    Message {
+        code: "RANGE_START_AFTER_END",
+        en: "from MUST BE BEFORE to",
+        es: "from DEBE SER ANTERIOR A to",
+    },
+    Message {
+        code: "DATE_RANGE_TOO_LONG",
+        en: "THE RANGE OF DATES CAN'T BE LONGER THAN 31 DAYS",
+        es: "EL RANGO DE FECHAS NO PUEDE SUPERAR LOS 31 DÍAS",
+    },
+    Message {
+        code: "BOOKING_INVALID_RANGE",
+        en: "THE BOOKING MUST START BEFORE IT ENDS",
+        es: "LA RESERVA DEBE EMPEZAR ANTES DE TERMINAR",
+    },
+    Message {
+        code: "BOOKING_IN_THE_PAST",
+        en: "THE BOOKING MUST START IN THE FUTURE",
+        es: "LA RESERVA DEBE EMPEZAR EN EL FUTURO",
+    },
+    Message {
+        code: "BOOKING_OWN_SERVICE",
+        en: "YOU CAN'T BOOK YOUR OWN SERVICE",
+        es: "NO PUEDES RESERVAR TU PROPIO SERVICIO",
+    },
+    Message {
+        code: "BOOKING_OUTSIDE_AVAILABILITY",
+        en: "THE BOOKING IS OUTSIDE THE AVAILABILITY OF THE SELLER",
+        es: "LA RESERVA ESTÁ FUERA DE LA DISPONIBILIDAD DEL VENDEDOR",
+    },
+    Message {
+        code: "SLOT_ALREADY_BOOKED",
+        en: "THE SLOT IS ALREADY BOOKED",
+        es: "LA FRANJA YA ESTÁ RESERVADA",
+    },
+    Message {
+        code: "NOT_BOOKING_PARTY",
+        en: "YOU CAN ONLY SEE YOUR OWN BOOKINGS",
+        es: "SOLO PUEDES VER TUS PROPIAS RESERVAS",
+    },
+    Message {
+        code: "NOT_BOOKING_SELLER",
+        en: "ONLY THE SELLER CAN CONFIRM OR COMPLETE A BOOKING",
+        es: "SOLO EL VENDEDOR PUEDE CONFIRMAR O COMPLETAR UNA RESERVA",
+    },
+    Message {
+        code: "FAVORITE_YOURSELF",
+        en: "YOU CAN'T ADD YOURSELF TO YOUR FAVORITES",
+        es: "NO PUEDES AGREGARTE A TUS FAVORITOS",
+    },
+    Message {
+        code: "RATE_ON_OTHERS_BEHALF",
+        en: "YOU CAN ONLY RATE ON YOUR OWN BEHALF",
+        es: "SOLO PUEDES CALIFICAR EN TU PROPIO NOMBRE",
+    },
+    Message {
+        code: "NOT_RATE_AUTHOR",
+        en: "YOU CAN ONLY UPDATE YOUR OWN RATES",
+        es: "SOLO PUEDES ACTUALIZAR TUS PROPIAS CALIFICACIONES",
+    },
+    Message {
+        code: "REVIEW_WITHOUT_BOOKING",
+        en: "ONLY CUSTOMERS WITH A COMPLETED BOOKING CAN REVIEW THE SELLER",
+        es: "SOLO LOS CLIENTES CON UNA RESERVA COMPLETADA PUEDEN RESEÑAR AL VENDEDOR",
+    },
+    Message {
+        code: "COMMENT_ON_OTHERS_BEHALF",
+        en: "YOU CAN ONLY COMMENT ON YOUR OWN BEHALF",
+        es: "SOLO PUEDES COMENTAR EN TU PROPIO NOMBRE",
+    },
+    Message {
+        code: "NOT_COMMENT_AUTHOR",
+        en: "YOU CAN ONLY UPDATE YOUR OWN COMMENTS",
+        es: "SOLO PUEDES ACTUALIZAR TUS PROPIOS COMENTARIOS",
+    },
+    Message {
+        code: "REPLY_NOT_ALLOWED",
+        en: "ONLY THE COMMENTED USER AND THE AUTHOR CAN REPLY TO A COMMENT",
+        es: "SOLO EL USUARIO COMENTADO Y EL AUTOR PUEDEN RESPONDER UN COMENTARIO",
+    },
+    Message {
+        code: "REPLY_TO_REPLY",
+        en: "YOU CAN ONLY REPLY TO A TOP LEVEL COMMENT",
+        es: "SOLO PUEDES RESPONDER A UN COMENTARIO PRINCIPAL",
+    },
+    Message {
+        code: "REPORT_OWN_COMMENT",
+        en: "YOU CAN'T REPORT YOUR OWN COMMENT",
+        es: "NO PUEDES REPORTAR TU PROPIO COMENTARIO",
+    },
+    Message {
+        code: "ALREADY_REPORTED",
+        en: "YOU ALREADY REPORTED THIS COMMENT",
+        es: "YA REPORTASTE ESTE COMENTARIO",
+    },
+    Message {
+        code: "INVALID_REPORT_REASON",
+        en: "THE REASON MUST HAVE BETWEEN 1 AND 200 CHARACTERS",
+        es: "EL MOTIVO DEBE TENER ENTRE 1 Y 200 CARACTERES",
+    },
+    Message {
+        code: "COMMENT_NOT_HIDDEN",
+        en: "THE COMMENT IS NOT HIDDEN",
+        es: "EL COMENTARIO NO ESTÁ OCULTO",
+    },
+    Message {
+        code: "REPORT_ALREADY_RESOLVED",
+        en: "THE REPORT IS ALREADY RESOLVED",
+        es: "EL REPORTE YA FUE RESUELTO",
+    },
+    Message {
+        code: "INVALID_SERVICE_LIMIT",
+        en: "THE LIMIT OF SERVICES PER SELLER MUST BE AT LEAST 1",
+        es: "EL LÍMITE DE SERVICIOS POR VENDEDOR DEBE SER AL MENOS 1",
+    },
+    Message {
+        code: "INVALID_REPUTATION_SETTINGS",
+        en: "THE PRIOR MEAN CAN'T BE NEGATIVE AND THE PRIOR WEIGHT AND HALF LIFE MUST BE POSITIVE",
+        es: "LA MEDIA A PRIORI NO PUEDE SER NEGATIVA Y EL PESO A PRIORI Y LA VIDA MEDIA DEBEN SER POSITIVOS",
+    },
+    Message {
+        code: "INVALID_LOCATION_RETENTION",
+        en: "THE LOCATION RETENTION MUST BE AT LEAST 1 DAY",
+        es: "LA RETENCIÓN DE UBICACIONES DEBE SER DE AL MENOS 1 DÍA",
+    },
+    Message {
+        code: "INVALID_LOCATION_GRID",
+        en: "THE LOCATION GRID MUST BE AT LEAST 1 METER",
+        es: "LA CUADRÍCULA DE UBICACIONES DEBE SER DE AL MENOS 1 METRO",
+    },
+    Message {
+        code: "LOCATIONS_OF_OTHERS",
+        en: "YOU CAN ONLY SEE YOUR OWN LOCATIONS",
+        es: "SOLO PUEDES VER TUS PROPIAS UBICACIONES",
+    },
+    Message {
+        code: "INVALID_MAX_POINTS",
+        en: "max_points MUST BE BETWEEN 2 AND 5000",
+        es: "max_points DEBE ESTAR ENTRE 2 Y 5000",
+    },
+    Message {
+        code: "COORDINATES_OUT_OF_RANGE",
+        en: "THE COORDINATES ARE OUT OF RANGE",
+        es: "LAS COORDENADAS ESTÁN FUERA DE RANGO",
+    },
+    Message {
+        code: "INVALID_RADIUS",
+        en: "radius_km MUST BE GREATER THAN 0 AND UP TO 50",
+        es: "radius_km DEBE SER MAYOR QUE 0 Y HASTA 50",
+    },
+    Message {
+        code: "INVALID_POSITION",
+        en: "POSITIONS MUST BE [longitude, latitude] ON THE EARTH",
+        es: "LAS POSICIONES DEBEN SER [longitud, latitud] SOBRE LA TIERRA",
+    },
+    Message {
+        code: "INVALID_RING_SIZE",
+        en: "A RING MUST HAVE BETWEEN 4 AND 1000 POSITIONS",
+        es: "UN ANILLO DEBE TENER ENTRE 4 Y 1000 POSICIONES",
+    },
+    Message {
+        code: "RING_NOT_CLOSED",
+        en: "RINGS MUST END AT THEIR FIRST POSITION",
+        es: "LOS ANILLOS DEBEN TERMINAR EN SU PRIMERA POSICIÓN",
+    },
+    Message {
+        code: "RING_WITHOUT_AREA",
+        en: "RINGS MUST ENCLOSE AN AREA",
+        es: "LOS ANILLOS DEBEN ENCERRAR UN ÁREA",
+    },
+    Message {
+        code: "RING_SELF_INTERSECTION",
+        en: "RINGS CAN'T CROSS THEMSELVES",
+        es: "LOS ANILLOS NO PUEDEN CRUZARSE A SÍ MISMOS",
+    },
+    Message {
+        code: "POLYGON_WITHOUT_BOUNDARY",
+        en: "A POLYGON NEEDS AT LEAST ITS BOUNDARY RING",
+        es: "UN POLÍGONO NECESITA AL MENOS SU ANILLO EXTERIOR",
+    },
+    Message {
+        code: "INVALID_AREA_RADIUS",
+        en: "THE RADIUS MUST BE GREATER THAN 0 AND UP TO 200000 METERS",
+        es: "EL RADIO DEBE SER MAYOR QUE 0 Y HASTA 200000 METROS",
+    },
+    Message {
+        code: "TOO_MANY_AREAS",
+        en: "A SERVICE CAN HAVE UP TO 10 AREAS",
+        es: "UN SERVICIO PUEDE TENER HASTA 10 ÁREAS",
+    },
+    Message {
+        code: "AREAS_NOT_HOME_VISIT",
+        en: "ONLY HOME VISIT SERVICES CAN HAVE AREAS",
+        es: "SOLO LOS SERVICIOS A DOMICILIO PUEDEN TENER ÁREAS",
+    },
+    Message {
+        code: "INVALID_LABEL",
+        en: "THE LABEL MUST HAVE BETWEEN 1 AND 50 CHARACTERS",
+        es: "LA ETIQUETA DEBE TENER ENTRE 1 Y 50 CARACTERES",
+    },
+    Message {
+        code: "UNSUPPORTED_CONTENT_TYPE",
+        en: "CONTENT TYPE MUST BE text/csv OR application/x-ndjson",
+        es: "EL CONTENT TYPE DEBE SER text/csv O application/x-ndjson",
+    },
+];
+
+/// Returns the language of the current request, English outside of the [`language`] middleware
+pub fn current() -> Language {
+    LANGUAGE.try_with(|language| *language).unwrap_or_default()
+}
+
+/// Runs the future in the given language
+pub async fn scope<F: Future>(language: Language, future: F) -> F::Output {
+    LANGUAGE.scope(language, future).await
+}
+
+/// Returns the code and the message in the current language of an error
+pub fn error_message(error: &(dyn Error + 'static)) -> (&'static str, String) {
+    let language = current();
+
+    let message = match error.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::RowNotFound) => Some(&NOT_FOUND),
+        Some(sqlx::Error::Database(database_error))
+            if database_error.is_foreign_key_violation() =>
+        {
+            Some(&REFERENCE_NOT_FOUND)
+        }
+        // The details of the database aren't returned to the clients
+        Some(_) => Some(&INTERNAL_ERROR),
+        None => {
+            let text = error.to_string();
+
+            CATALOGUE.iter().find(|message| message.en == text)
+        }
+    };
+
+    match message {
+        Some(message) => (message.code, message.text(language).to_owned()),
+        None => (UNKNOWN_ERROR, error.to_string()),
+    }
+}
+
+/// Returns the preferred language of an Accept-Language header among the supported ones
+///
+/// # Argument
+///
+/// * accept_language - Value of the header, for example "es-CO,es;q=0.9,en;q=0.8"
+///
+pub fn preferred_language(accept_language: &HeaderValue) -> Option<Language> {
+    let accept_language = accept_language.to_str().ok()?;
+
+    let mut languages: Vec<(Language, f32)> = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let language = Language::from_tag(parts.next()?)?;
+
+            let quality = parts
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            Some((language, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+
+    // The sort is stable, so languages with the same quality keep the order of the header
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    languages.first().map(|(language, _)| *language)
+}
+
+/// Middleware that runs the request in the language of its Accept-Language header, English
+/// when it has none of the supported ones, and tells it in the Content-Language header
+pub async fn language<B>(req: Request<B>, next: Next<B>) -> Response {
+    let language = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(preferred_language)
+        .unwrap_or_default();
+
+    let mut response = scope(language, next.run(req)).await;
+
+    let headers = response.headers_mut();
+
+    headers.insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(language.code()),
+    );
+    // Caches must keep a response per language
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+
+    response
+}
@@ -0,0 +1,27 @@
+use std::env;
+
+use tracing_subscriber::{fmt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Starts writing the logs to the standard output, filtered by RUST_LOG.
+///
+/// Every line is a JSON object with the fields of the event and the spans it happened in, like
+/// the request id. LOG_FORMAT=text writes human readable lines instead, for local development
+pub fn init() {
+    let filter = EnvFilter::new(env::var("RUST_LOG").unwrap_or_else(|_| {
+        "online_market_axum=debug,online_market_data=debug,tower_http=debug".into()
+    }));
+
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match env::var("LOG_FORMAT") {
+        Ok(format) if format == "text" => registry.with(fmt::layer()).init(),
+        _ => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .init(),
+    }
+}
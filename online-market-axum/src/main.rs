@@ -1,11 +1,40 @@
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use online_market_data::{CategoryRepository, RateRepository, UserRepository, CommentRepository};
+use online_market_data::{
+    area::ServiceAreaRepository,
+    audit::{track_context, AuditRepository},
+    availability::AvailabilityRepository,
+    booking::BookingRepository,
+    bulk::BulkRepository,
+    favorite::FavoriteRepository,
+    location::{LocationHistoryRepository, LocationPrivacyRepository},
+    migration::{self, MigrationState},
+    moderation::ModerationRepository,
+    CategoryRepository, CommentRepository, RateRepository, SellerRepository, ServiceRepository,
+    SettingsRepository, UserRepository,
+};
+use online_market_model::{LocationVisibility, UserLocation};
 use sqlx::postgres::{PgPool, PgPoolOptions};
 use std::{env, sync::Arc};
+use tokio::sync::broadcast;
 
+use auth::AuthKeys;
+
+pub mod audit;
+pub mod auth;
+pub mod caching;
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod handler;
+pub mod i18n;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod request_id;
 pub mod router;
 pub mod swagger;
+pub mod versioning;
 
 pub struct AppState {
     pub db: PgPool,
@@ -13,30 +42,128 @@ pub struct AppState {
     pub user_repository: UserRepository,
     pub rate_repository: RateRepository,
     pub comment_repository: CommentRepository,
+    pub moderation_repository: ModerationRepository,
+    pub bulk_repository: BulkRepository,
+    pub seller_repository: SellerRepository,
+    pub service_repository: ServiceRepository,
+    pub service_area_repository: ServiceAreaRepository,
+    pub settings_repository: SettingsRepository,
+    pub availability_repository: AvailabilityRepository,
+    pub booking_repository: BookingRepository,
+    pub favorite_repository: FavoriteRepository,
+    pub location_history_repository: LocationHistoryRepository,
+    pub location_privacy_repository: LocationPrivacyRepository,
+    pub audit_repository: AuditRepository,
+    pub auth_keys: AuthKeys,
+    pub graphql_schema: graphql::MarketSchema,
+    /// Locations saved through the WebSocket, for the subscribers that follow them live
+    pub location_updates: broadcast::Sender<LocationUpdate>,
+}
+
+/// Location saved through the WebSocket, with who can see it
+#[derive(Clone, Debug)]
+pub struct LocationUpdate {
+    pub location: UserLocation,
+    pub visibility: LocationVisibility,
+}
+
+/// Locations kept for subscribers that are behind before they start missing them
+const LOCATION_UPDATES_CAPACITY: usize = 1024;
+
+#[derive(Parser)]
+#[command(about = "Online market API server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the database schema
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply every pending migration
+    Up,
+    /// Show which migrations are applied and which are pending
+    Status,
 }
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
+    let cli = Cli::parse();
+
+    logging::init();
+
     let database_url = env::var("DATABASE_URL").unwrap();
 
     // Create database connection
-    let pool = match PgPoolOptions::new().connect(&database_url).await {
+    // Connections carry the actor and request id of the changes to the audit log
+    let pool = match track_context(PgPoolOptions::new())
+        .connect(&database_url)
+        .await
+    {
         Ok(pool) => {
-            println!("Database connection successfully established");
+            tracing::info!("Database connection successfully established");
             pool
         }
         Err(error) => {
-            println!(
-                "Something went wrong while creating database connection. {}",
-                error
-            );
+            tracing::error!(%error, "Something went wrong while creating database connection");
 
             std::process::exit(1);
         }
     };
 
+    match cli.command {
+        Some(Command::Migrate { action }) => match action {
+            MigrateAction::Up => migrate_up(&pool).await,
+            MigrateAction::Status => migrate_status(&pool).await,
+        },
+        None => serve(pool).await,
+    }
+}
+
+async fn serve(pool: PgPool) {
+    // Migrations are only applied on startup when AUTO_MIGRATE is enabled
+    let auto_migrate = env::var("AUTO_MIGRATE")
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(false);
+
+    if auto_migrate {
+        migrate_up(&pool).await;
+    }
+
+    // Secret used to sign the access tokens. Without one the tokens are signed with a random
+    // secret, so they stop being valid when the server restarts
+    let jwt_secret = match env::var("JWT_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret,
+        _ => {
+            tracing::warn!(
+                "JWT_SECRET isn't defined, the access tokens won't survive a restart of the server"
+            );
+
+            format!(
+                "{}{}",
+                uuid::Uuid::new_v4().simple(),
+                uuid::Uuid::new_v4().simple()
+            )
+        }
+    };
+
+    let token_ttl = env::var("TOKEN_TTL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .unwrap_or(86400);
+
+    let (location_updates, _) = broadcast::channel(LOCATION_UPDATES_CAPACITY);
+
     // Creating AppState that will be used in the whole app
     let app_state = Arc::new(AppState {
         db: pool,
@@ -44,8 +171,44 @@ async fn main() {
         user_repository: UserRepository::new(),
         rate_repository: RateRepository::new(),
         comment_repository: CommentRepository::new(),
+        moderation_repository: ModerationRepository::new(),
+        bulk_repository: BulkRepository::new(),
+        seller_repository: SellerRepository::new(),
+        service_repository: ServiceRepository::new(),
+        service_area_repository: ServiceAreaRepository::new(),
+        settings_repository: SettingsRepository::new(),
+        availability_repository: AvailabilityRepository::new(),
+        booking_repository: BookingRepository::new(),
+        favorite_repository: FavoriteRepository::new(),
+        location_history_repository: LocationHistoryRepository::new(),
+        location_privacy_repository: LocationPrivacyRepository::new(),
+        audit_repository: AuditRepository::new(),
+        auth_keys: AuthKeys::new(jwt_secret.as_bytes(), token_ttl),
+        graphql_schema: graphql::build_schema(),
+        location_updates,
     });
 
+    maintenance::spawn(app_state.db.clone());
+
+    // The gRPC API for internal consumers runs on its own port
+    #[cfg(feature = "grpc")]
+    {
+        let grpc_port = env::var("GRPC_PORT")
+            .ok()
+            .and_then(|value| value.parse::<u16>().ok())
+            .unwrap_or(50051);
+
+        let app = app_state.clone();
+
+        tokio::spawn(async move {
+            if let Err(error) = grpc::serve(app, ([0, 0, 0, 0], grpc_port).into()).await {
+                tracing::error!(%error, "Something went wrong while serving gRPC");
+
+                std::process::exit(1);
+            }
+        });
+    }
+
     // Create router and passing the AppState that will be use in the whole app
     let router = router::build_router(app_state);
 
@@ -55,3 +218,39 @@ async fn main() {
         .await
         .unwrap();
 }
+
+async fn migrate_up(pool: &PgPool) {
+    match migration::run(pool).await {
+        Ok(_) => tracing::info!("Migrations successfully applied"),
+        Err(error) => {
+            tracing::error!(%error, "Something went wrong while applying migrations");
+
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn migrate_status(pool: &PgPool) {
+    let status = match migration::status(pool).await {
+        Ok(status) => status,
+        Err(error) => {
+            tracing::error!(%error, "Something went wrong while reading migrations status");
+
+            std::process::exit(1);
+        }
+    };
+
+    for migration in status {
+        let state = match migration.state {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::ChecksumMismatch => "checksum mismatch",
+            MigrationState::Failed => "failed",
+        };
+
+        println!(
+            "{:<16} {:<20} {}",
+            migration.version, state, migration.description
+        );
+    }
+}
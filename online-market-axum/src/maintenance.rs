@@ -0,0 +1,82 @@
+use std::{env, future::Future, time::Duration};
+
+use online_market_data::{location::LocationHistoryRepository, reputation::ReputationRepository};
+use sqlx::PgPool;
+use tokio::time::MissedTickBehavior;
+
+/// Returns the period of a maintenance task from the environment variable, in seconds.
+/// None when it's 0, so the task is left to an external scheduler
+fn period(variable: &str, default_seconds: u64) -> Option<Duration> {
+    let seconds = env::var(variable)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(default_seconds);
+
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Runs the task every period, starting right away. Failures are logged and the task runs again
+/// on the next tick
+fn schedule<F, Fut>(name: &'static str, period: Duration, task: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), sqlx::Error>> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        // A slow run doesn't make the next ones pile up
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = task().await {
+                tracing::error!(%error, task = name, "Something went wrong while running maintenance");
+            }
+        }
+    });
+}
+
+/// Starts the periodic maintenance of the database in the background:
+///
+/// * LOCATION_PURGE_INTERVAL_SECONDS, 3600 by default - Removes the locations older than the
+///   retention and creates the partitions of the next days
+/// * REPUTATION_REFRESH_INTERVAL_SECONDS, 3600 by default - Computes again the reputation of
+///   every rated user, so the rates of idle sellers keep losing weight like everyone else's
+///
+/// Setting a period to 0 disables the task, for deployments that run it with the admin tool
+pub fn spawn(pool: PgPool) {
+    if let Some(period) = period("LOCATION_PURGE_INTERVAL_SECONDS", 3600) {
+        let pool = pool.clone();
+
+        schedule("purge_locations", period, move || {
+            let pool = pool.clone();
+
+            async move {
+                let report = LocationHistoryRepository::new().purge(&pool).await?;
+
+                tracing::info!(
+                    dropped_partitions = report.dropped_partitions,
+                    deleted_points = report.deleted_points,
+                    "Purged the location history"
+                );
+
+                Ok(())
+            }
+        });
+    }
+
+    if let Some(period) = period("REPUTATION_REFRESH_INTERVAL_SECONDS", 3600) {
+        schedule("refresh_reputations", period, move || {
+            let pool = pool.clone();
+
+            async move {
+                let refreshed = ReputationRepository::new().refresh_all(&pool).await?;
+
+                tracing::info!(refreshed, "Refreshed the reputations");
+
+                Ok(())
+            }
+        });
+    }
+}
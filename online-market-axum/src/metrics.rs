@@ -0,0 +1,100 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, Encoder, HistogramVec,
+    IntCounterVec, IntGauge, TextEncoder,
+};
+
+use crate::AppState;
+
+/// Requests served, labeled by method, matched route and status code
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Total number of HTTP requests",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+/// Latency of the requests, labeled by method, matched route and status code
+pub static HTTP_REQUEST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latencies in seconds",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+/// WebSocket connections currently open
+pub static WS_ACTIVE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "ws_active_connections",
+        "Number of open WebSocket connections"
+    )
+    .unwrap()
+});
+
+/// Location frames processed, labeled by result (ok or error)
+pub static LOCATION_UPDATES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "location_updates_total",
+        "Total number of location updates received through the WebSocket",
+        &["result"]
+    )
+    .unwrap()
+});
+
+/// Middleware that records the count and latency of every routed request
+pub async fn track_metrics<B>(req: Request<B>, next: Next<B>) -> impl IntoResponse {
+    let start = Instant::now();
+
+    // Use the route template instead of the raw path to keep the label cardinality bounded
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_owned(),
+        None => "unmatched".to_owned(),
+    };
+    let method = req.method().to_string();
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+
+    HTTP_REQUESTS_TOTAL.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION_SECONDS
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Exposes every registered collector in the Prometheus text format
+pub async fn metrics_handler(State(app): State<Arc<AppState>>) -> impl IntoResponse {
+    // Pool gauges are sampled on scrape
+    online_market_data::metrics::observe_pool(&app.db);
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(_) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, encoder.format_type().to_owned())],
+            buffer,
+        ),
+        Err(error) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain".to_owned())],
+            error.to_string().into_bytes(),
+        ),
+    }
+}
@@ -0,0 +1,62 @@
+use axum::{
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header with the id of the request, sent by the clients or proxies and returned in the response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Returns the request id sent by the client when it's safe to store, None otherwise
+fn client_request_id(value: &HeaderValue) -> Option<String> {
+    let value = value.to_str().ok()?;
+
+    let valid = !value.is_empty()
+        && value.len() <= 64
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    valid.then(|| value.to_owned())
+}
+
+/// Returns the id of the request the current task is serving, None outside of [`request_id`]
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Middleware that gives every request an id, taken from the x-request-id header or generated
+/// when it's missing or invalid.
+///
+/// The request runs in a span with the id, so every log line it writes carries it, and the id
+/// is returned in the x-request-id header of the response
+pub async fn request_id<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(client_request_id)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    // Only letters, digits, '-' and '_' get here, so the id is always a valid header value
+    let header = HeaderValue::from_str(&id).unwrap();
+
+    // The handlers see the id that was finally used, not the invalid one
+    req.headers_mut().insert(REQUEST_ID_HEADER, header.clone());
+
+    let span = tracing::info_span!("request", request_id = %id);
+
+    let mut response = REQUEST_ID
+        .scope(id, next.run(req))
+        .instrument(span)
+        .await;
+
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+
+    response
+}
@@ -1,50 +1,155 @@
 use std::sync::Arc;
 
 use axum::{
-    routing::{get, patch, post},
+    http::header,
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
 use tower::ServiceBuilder;
-use tower_http::trace::TraceLayer;
-use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt};
-use utoipa::OpenApi;
+use tower_http::{
+    sensitive_headers::{SetSensitiveRequestHeadersLayer, SetSensitiveResponseHeadersLayer},
+    trace::{DefaultMakeSpan, TraceLayer},
+};
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
+    audit::audit_context,
+    caching::http_cache,
+    graphql::{graphiql, graphql_handler},
+    i18n::language,
     handler::{
+        audit_handler::get_audit_log,
+        auth_handler::login,
+        availability_handler::{
+            add_availability_exception, delete_availability_exception, get_availability,
+            get_free_slots, set_availability,
+        },
+        booking_handler::{get_booking, save_booking, update_booking_status},
+        bulk_handler::{
+            export_categories, export_services, export_users, import_categories, import_services,
+            import_users,
+        },
         category_handler::{get_all_categories, get_category_by_id, save_category},
         comment_handler::{
-            get_comment, get_comments_by_commentator, get_comments_by_commented, save_comment,
-            update_comment,
+            get_comment, get_comments_by_commentator, get_comments_by_commented, reply_comment,
+            save_comment, update_comment,
+        },
+        seller_handler::{
+            get_all_sellers, leave_seller, onboard_seller, pause_seller, resume_seller,
+        },
+        service_handler::{
+            delete_service, get_covering_services, get_service_areas, get_service_by_id,
+            get_services_by_seller, save_service, search_services, set_service_areas,
+            update_service,
+        },
+        settings_handler::{get_settings, update_settings},
+        moderation_handler::{get_moderation_queue, report_comment, resolve_report},
+        label_handler::{get_labels, set_category_label},
+        favorite_handler::{
+            add_favorite_seller, add_favorite_service, get_my_favorites, remove_favorite_seller,
+            remove_favorite_service,
+        },
+        rate_handler::{
+            get_rate, get_rates_by_rated, get_rates_by_rater, get_rating_summary, save_rate,
+            update_rate,
+        },
+        user_handler::{
+            get_all_user, get_nearby_users, get_user_by_dni, get_user_locations, save_user,
+            update_location_visibility, update_user, handler_user_location,
         },
-        rate_handler::{get_rate, get_rates_by_rated, get_rates_by_rater, save_rate, update_rate},
-        user_handler::{get_all_user, get_user_by_dni, save_user, update_user, handler_user_location},
     },
-    AppState, swagger::ApiDoc,
+    metrics::{metrics_handler, track_metrics},
+    request_id::request_id,
+    swagger::versioned_openapi,
+    versioning::{deprecation_headers, ApiVersion, Deprecation},
+    AppState,
 };
 
 
 pub fn build_router(state: Arc<AppState>) -> Router {
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "online_market_axum=debug,tower_http=debug".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // The routes without prefix serve v1 to the apps released before the versions
+    let mut router = api_routes();
+
+    if let Some(deprecation) = Deprecation::unversioned() {
+        router = router.route_layer(middleware::from_fn_with_state(
+            deprecation,
+            deprecation_headers,
+        ));
+    }
+
+    let mut swagger_ui = SwaggerUi::new("/swagger-ui");
+
+    for version in ApiVersion::ALL {
+        let deprecation = version.deprecation();
+
+        // Only v1 exists for now. The first version that changes a route makes api_routes take
+        // the version and pick the handler of that route with a match on it
+        let mut routes = match version {
+            ApiVersion::V1 => api_routes(),
+        };
+
+        if let Some(deprecation) = deprecation.clone() {
+            routes = routes.route_layer(middleware::from_fn_with_state(
+                deprecation,
+                deprecation_headers,
+            ));
+        }
+
+        router = router.nest(&version.prefix(), routes);
+        swagger_ui = swagger_ui.url(
+            format!("/api-doc/{}/openapi.json", version.name()),
+            versioned_openapi(version, deprecation.is_some()),
+        );
+    }
+
+    router
+        .route("/graphql", get(graphiql).post(graphql_handler))
+        .route("/metrics", get(metrics_handler))
+        .route_layer(middleware::from_fn(http_cache))
+        .route_layer(middleware::from_fn(language))
+        .route_layer(middleware::from_fn_with_state(state.clone(), audit_context))
+        .route_layer(middleware::from_fn(track_metrics))
+        .layer(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn(request_id))
+                // The credentials are printed as "Sensitive" in the logs of the headers
+                .layer(SetSensitiveRequestHeadersLayer::new([
+                    header::AUTHORIZATION,
+                    header::COOKIE,
+                ]))
+                .layer(SetSensitiveResponseHeadersLayer::new([header::SET_COOKIE]))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(DefaultMakeSpan::new().include_headers(true)),
+                ),
+        )
+        .with_state(state)
+        .merge(swagger_ui)
+}
 
-    let router = Router::new()
+/// Returns the routes of v1 of the REST API, without its prefix
+fn api_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/auth/login", post(login))
         .route("/category", post(save_category))
         .route("/category/:id", get(get_category_by_id))
         .route("/category/all", get(get_all_categories))
+        .route("/category/:id/label/:language", put(set_category_label))
+        .route("/labels", get(get_labels))
         .route("/user", post(save_user))
         .route("/ws/user/update/location", get(handler_user_location))
         .route("/user/:dni", get(get_user_by_dni))
+        .route("/user/:dni/locations", get(get_user_locations))
         .route("/user/all", get(get_all_user))
+        .route("/user/nearby", get(get_nearby_users))
+        .route("/user/location/visibility", put(update_location_visibility))
         .route("/user/update", patch(update_user))
         .route("/rate", post(save_rate))
         .route("/rate/rater/:id_rater", get(get_rates_by_rater))
         .route("/rate/rated/:id_rated", get(get_rates_by_rated))
+        .route("/rate/summary/:dni", get(get_rating_summary))
         .route("/rate/:id_rater/:id_rated", get(get_rate))
         .route("/rate/update", patch(update_rate))
         .route("/comment", post(save_comment))
@@ -56,11 +161,59 @@ pub fn build_router(state: Arc<AppState>) -> Router {
             "/comment/commentator/:id_commentator",
             get(get_comments_by_commentator),
         )
-        .route("/comment/update", patch(update_comment))
-        .route("/comment/:id_commented/:id_commentator", get(get_comment))
-        .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
-        .with_state(state)
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", ApiDoc::openapi()));
-
-    router
+        .route("/comment/:id", get(get_comment).patch(update_comment))
+        .route("/comment/:id/reply", post(reply_comment))
+        .route("/comment/:id/report", post(report_comment))
+        .route("/moderation/queue", get(get_moderation_queue))
+        .route("/moderation/report/:id", post(resolve_report))
+        .route("/audit", get(get_audit_log))
+        .route("/seller/all", get(get_all_sellers))
+        .route("/seller/onboard", post(onboard_seller))
+        .route("/seller/pause", post(pause_seller))
+        .route("/seller/resume", post(resume_seller))
+        .route("/seller/leave", post(leave_seller))
+        .route("/seller/availability", put(set_availability))
+        .route(
+            "/seller/availability/exception",
+            post(add_availability_exception),
+        )
+        .route(
+            "/seller/availability/exception/:date",
+            delete(delete_availability_exception),
+        )
+        .route("/seller/:dni/availability", get(get_availability))
+        .route("/seller/:dni/slots", get(get_free_slots))
+        .route("/booking", post(save_booking))
+        .route("/booking/:id", get(get_booking))
+        .route("/booking/:id/status", patch(update_booking_status))
+        .route(
+            "/favorite/service/:id",
+            post(add_favorite_service).delete(remove_favorite_service),
+        )
+        .route(
+            "/favorite/seller/:dni",
+            post(add_favorite_seller).delete(remove_favorite_seller),
+        )
+        .route("/me/favorites", get(get_my_favorites))
+        .route("/service", post(save_service))
+        .route(
+            "/service/:id",
+            get(get_service_by_id)
+                .patch(update_service)
+                .delete(delete_service),
+        )
+        .route("/service/seller/:dni", get(get_services_by_seller))
+        .route(
+            "/service/:id/areas",
+            get(get_service_areas).put(set_service_areas),
+        )
+        .route("/service/covering", get(get_covering_services))
+        .route("/service/search", get(search_services))
+        .route("/settings", get(get_settings).patch(update_settings))
+        .route("/import/categories", post(import_categories))
+        .route("/import/users", post(import_users))
+        .route("/import/services", post(import_services))
+        .route("/export/categories", get(export_categories))
+        .route("/export/users", get(export_users))
+        .route("/export/services", get(export_services))
 }
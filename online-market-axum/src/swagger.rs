@@ -1,25 +1,178 @@
-use online_market_model::{User, Service, Modality, Roles, Comment, Rate, Category};
-use utoipa::OpenApi;
+use online_market_data::bulk::{ImportReport, RowError};
+use online_market_model::{
+    AuditAction, AuditEntry, AvailabilityException, AvailabilityResponse, AvailabilityWindow, Booking, BookingResponse,
+    BookingStatus, BookingStatusUpdate, Category, CategoryLabel, CategoryLabelUpdate, CategoryResponse, Comment, CommentReply, CommentReport, CommentReportResponse, CommentResponse, CommentThread,
+    CommentUpdate, FavoriteKind, FavoriteResponse, LabelsResponse, Language, LocationPoint, LocationVisibility, LocationVisibilityUpdate, Modality, ModalityLabel, ModerationAction, ModerationDecision, Money, OnboardingService, Rate,
+    RateResponse, RatingSummary, ReviewVerification, RoleLabel, Roles, SellerOnboarding, SellerOnboardingResponse, SellerSort, SellerSummary, Service, ServiceArea, ServiceResponse, Settings,
+    SettingsResponse, TimeSlot, User, UserLocation, UserResponse, WeeklySchedule, Weekday,
+};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        server::Server,
+        Deprecated,
+    },
+    Modify, OpenApi,
+};
+
+use crate::handler::{
+    auth_handler::{Credentials, TokenResponse},
+    AuditEntryListResult,
+    AvailabilityResult, BookingResult, FavoriteListResult, FavoriteResult, TimeSlotListResult,
+    CategoryLabelResult, CategoryListResult, CategoryResult, CommentListResult, CommentReportListResult, CommentReportResult,
+    CommentThreadListResult, CommentResult, ErrorResponse,
+    ImportFailure, ImportResult, LabelsResult, LocationPointListResult, RateListResult, RateResult, RatingSummaryResult, SellerOnboardingResult, SellerSummaryListResult, ServiceAreaListResult, ServiceListResult,
+    ServiceResult, SettingsResult, TokenResult, UserListResult, UserResult,
+};
+use crate::versioning::ApiVersion;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
+       crate::handler::auth_handler::login,
        crate::handler::user_handler::get_all_user,
        crate::handler::user_handler::get_user_by_dni,
        crate::handler::user_handler::save_user,
        crate::handler::user_handler::update_user,
+       crate::handler::user_handler::handler_user_location,
+       crate::handler::user_handler::get_user_locations,
+       crate::handler::user_handler::get_nearby_users,
+       crate::handler::user_handler::update_location_visibility,
        crate::handler::category_handler::save_category,
        crate::handler::category_handler::get_all_categories,
        crate::handler::category_handler::get_category_by_id,
+       crate::handler::label_handler::get_labels,
+       crate::handler::label_handler::set_category_label,
        crate::handler::rate_handler::save_rate,
        crate::handler::rate_handler::get_rate,
+       crate::handler::rate_handler::get_rating_summary,
        crate::handler::rate_handler::get_rates_by_rated,
        crate::handler::rate_handler::get_rates_by_rater,
+       crate::handler::rate_handler::update_rate,
        crate::handler::comment_handler::save_comment,
        crate::handler::comment_handler::get_comment,
+       crate::handler::comment_handler::reply_comment,
        crate::handler::comment_handler::get_comments_by_commented,
-       crate::handler::comment_handler::get_comments_by_commentator
+       crate::handler::comment_handler::get_comments_by_commentator,
+       crate::handler::comment_handler::update_comment,
+       crate::handler::moderation_handler::report_comment,
+       crate::handler::moderation_handler::get_moderation_queue,
+       crate::handler::moderation_handler::resolve_report,
+       crate::handler::audit_handler::get_audit_log,
+       crate::handler::bulk_handler::import_categories,
+       crate::handler::bulk_handler::import_users,
+       crate::handler::bulk_handler::import_services,
+       crate::handler::bulk_handler::export_categories,
+       crate::handler::bulk_handler::export_users,
+       crate::handler::bulk_handler::export_services,
+       crate::handler::seller_handler::get_all_sellers,
+       crate::handler::seller_handler::onboard_seller,
+       crate::handler::seller_handler::pause_seller,
+       crate::handler::seller_handler::resume_seller,
+       crate::handler::seller_handler::leave_seller,
+       crate::handler::service_handler::save_service,
+       crate::handler::service_handler::get_service_by_id,
+       crate::handler::service_handler::get_services_by_seller,
+       crate::handler::service_handler::update_service,
+       crate::handler::service_handler::delete_service,
+       crate::handler::service_handler::get_service_areas,
+       crate::handler::service_handler::set_service_areas,
+       crate::handler::service_handler::get_covering_services,
+       crate::handler::service_handler::search_services,
+       crate::handler::settings_handler::get_settings,
+       crate::handler::settings_handler::update_settings,
+       crate::handler::availability_handler::set_availability,
+       crate::handler::availability_handler::add_availability_exception,
+       crate::handler::availability_handler::delete_availability_exception,
+       crate::handler::availability_handler::get_availability,
+       crate::handler::availability_handler::get_free_slots,
+       crate::handler::booking_handler::save_booking,
+       crate::handler::booking_handler::get_booking,
+       crate::handler::booking_handler::update_booking_status,
+       crate::handler::favorite_handler::add_favorite_service,
+       crate::handler::favorite_handler::remove_favorite_service,
+       crate::handler::favorite_handler::add_favorite_seller,
+       crate::handler::favorite_handler::remove_favorite_seller,
+       crate::handler::favorite_handler::get_my_favorites
     ),
-    components(schemas(User, Service, Modality, Roles, Comment, Rate, Category)),
+    components(schemas(
+        User, UserResponse, UserLocation, Service, ServiceResponse, Money, Modality, Roles,
+        Comment, CommentResponse, Rate, RateResponse, Category, CategoryResponse,
+        ImportReport, RowError, Credentials, TokenResponse, ErrorResponse,
+        CategoryResult, CategoryListResult, UserResult, UserListResult, RateResult,
+        RateListResult, CommentResult, CommentListResult, ServiceResult, ImportResult,
+        ImportFailure, TokenResult, SellerOnboarding, OnboardingService, SellerOnboardingResponse,
+        SellerOnboardingResult, ServiceListResult, Settings, SettingsResponse, SettingsResult,
+        Weekday, AvailabilityWindow, WeeklySchedule, AvailabilityException, AvailabilityResponse,
+        TimeSlot, AvailabilityResult, TimeSlotListResult, Booking, BookingResponse, BookingStatus,
+        BookingStatusUpdate, BookingResult, FavoriteKind, FavoriteResponse, FavoriteResult,
+        FavoriteListResult, CommentReport, CommentReportResponse, CommentReportResult,
+        CommentReportListResult, ModerationAction, ModerationDecision, CommentReply, CommentUpdate,
+        CommentThread, CommentThreadListResult, ReviewVerification,
+        SellerSort, SellerSummary, SellerSummaryListResult, AuditAction, AuditEntry,
+        AuditEntryListResult, RatingSummary, RatingSummaryResult, Language, ModalityLabel,
+        RoleLabel, CategoryLabel, CategoryLabelUpdate, LabelsResponse, LabelsResult,
+        CategoryLabelResult, LocationPoint, LocationPointListResult, LocationVisibility,
+        LocationVisibilityUpdate, ServiceArea, ServiceAreaListResult
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Access tokens"),
+        (name = "user", description = "Users and their location"),
+        (name = "category", description = "Categories of the services"),
+        (name = "label", description = "Names of the modalities, roles and categories in the language of the request"),
+        (name = "rate", description = "Rates between users"),
+        (name = "comment", description = "Comments between users"),
+        (name = "seller", description = "Listing of the sellers and seller status of the authenticated user"),
+        (name = "service", description = "Services published by the sellers"),
+        (name = "availability", description = "Weekly availability and free slots of the sellers"),
+        (name = "booking", description = "Bookings of services"),
+        (name = "moderation", description = "Reports of comments, admin only"),
+        (name = "favorite", description = "Favorite services and sellers of the users"),
+        (name = "settings", description = "Settings of the market, admin only"),
+        (name = "audit", description = "Log of every change of the data, admin only"),
+        (name = "bulk", description = "Bulk import and export, admin only")
+    )
 )]
-pub struct ApiDoc;
\ No newline at end of file
+pub struct ApiDoc;
+
+/// Returns the OpenAPI document of a version of the REST API, whose paths are relative to the
+/// prefix of the version. Every operation is marked deprecated when the version is
+pub fn versioned_openapi(version: ApiVersion, deprecated: bool) -> utoipa::openapi::OpenApi {
+    // Versions that change a resource get their own ApiDoc with the paths of its new handlers
+    let mut openapi = match version {
+        ApiVersion::V1 => ApiDoc::openapi(),
+    };
+
+    openapi.info.version = version.name().to_owned();
+    openapi.servers = Some(vec![Server::new(version.prefix())]);
+
+    if deprecated {
+        for path in openapi.paths.paths.values_mut() {
+            for operation in path.operations.values_mut() {
+                operation.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+
+    openapi
+}
+
+/// Registers the bearer token returned by /auth/login as security scheme
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
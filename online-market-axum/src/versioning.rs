@@ -0,0 +1,116 @@
+use std::env;
+
+use axum::{
+    extract::State,
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Duration, Utc};
+
+/// Versions of the REST API, oldest first. Each one is served under its own prefix
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// Every version that is served
+    pub const ALL: [ApiVersion; 1] = [ApiVersion::V1];
+
+    /// Name of the version, for example "v1"
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+        }
+    }
+
+    /// Prefix of the routes of the version, for example "/v1"
+    pub fn prefix(self) -> String {
+        format!("/{}", self.name())
+    }
+
+    /// Returns the deprecation of the version, read from API_V1_DEPRECATED_AT and API_V1_SUNSET
+    /// for v1. None while the version isn't deprecated
+    pub fn deprecation(self) -> Option<Deprecation> {
+        Deprecation::from_env(&format!("API_{}", self.name().to_uppercase()))
+    }
+}
+
+/// Days a deprecated API is still served when its sunset isn't configured
+const DEFAULT_SUNSET_DAYS: i64 = 180;
+
+#[derive(Clone, Debug)]
+pub struct Deprecation {
+    pub deprecated_at: DateTime<Utc>,
+    /// When the API stops being served
+    pub sunset: DateTime<Utc>,
+}
+
+impl Deprecation {
+    /// Deprecation of the routes without version prefix, which serve v1 for the apps released
+    /// before the versions, read from API_UNVERSIONED_DEPRECATED_AT and API_UNVERSIONED_SUNSET.
+    /// None while they aren't deprecated
+    pub fn unversioned() -> Option<Self> {
+        Deprecation::from_env("API_UNVERSIONED")
+    }
+
+    /// Reads the deprecation from the variables {prefix}_DEPRECATED_AT and {prefix}_SUNSET.
+    /// The sunset is DEFAULT_SUNSET_DAYS after the deprecation when it's missing
+    fn from_env(prefix: &str) -> Option<Self> {
+        let deprecated_at = env_date(&format!("{}_DEPRECATED_AT", prefix))?;
+        let sunset = env_date(&format!("{}_SUNSET", prefix))
+            .unwrap_or(deprecated_at + Duration::days(DEFAULT_SUNSET_DAYS));
+
+        Some(Deprecation {
+            deprecated_at,
+            sunset,
+        })
+    }
+}
+
+/// Returns the RFC 3339 date of an environment variable, None when it's missing or invalid
+fn env_date(name: &str) -> Option<DateTime<Utc>> {
+    let value = env::var(name).ok()?;
+
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(date) => Some(date.with_timezone(&Utc)),
+        Err(error) => {
+            tracing::warn!(%error, variable = name, "Ignoring a date that isn't RFC 3339");
+
+            None
+        }
+    }
+}
+
+/// Returns the route template without its version prefix, for example "/category/all" for
+/// "/v1/category/all"
+pub fn unversioned_route(route: &str) -> &str {
+    ApiVersion::ALL
+        .iter()
+        .find_map(|version| {
+            route
+                .strip_prefix(&version.prefix())
+                .filter(|rest| rest.starts_with('/'))
+        })
+        .unwrap_or(route)
+}
+
+/// Middleware that tells the clients of a deprecated version to move to a newer one, with the
+/// Deprecation (RFC 9745) and Sunset (RFC 8594) headers
+pub async fn deprecation_headers<B>(
+    State(deprecation): State<Deprecation>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    let deprecated_at = format!("@{}", deprecation.deprecated_at.timestamp());
+    headers.insert("deprecation", HeaderValue::from_str(&deprecated_at).unwrap());
+
+    let sunset = deprecation.sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    headers.insert("sunset", HeaderValue::from_str(&sunset).unwrap());
+
+    response
+}
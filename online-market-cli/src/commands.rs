@@ -0,0 +1,248 @@
+use std::{
+    error::Error,
+    fs,
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use online_market_data::{
+    bulk::{BulkFormat, BulkRepository},
+    location::LocationHistoryRepository,
+    reputation::ReputationRepository,
+    CategoryRepository, StatisticsRepository, UserRepository,
+};
+use online_market_model::{Category, Roles, Secret, User};
+use sqlx::PgPool;
+
+use crate::Entity;
+
+/// Categories inserted by seed-categories when no file is provided
+const DEFAULT_CATEGORIES: [&str; 12] = [
+    "Plomería",
+    "Electricidad",
+    "Carpintería",
+    "Cerrajería",
+    "Jardinería",
+    "Limpieza",
+    "Pintura",
+    "Mecánica",
+    "Tecnología",
+    "Belleza",
+    "Clases particulares",
+    "Mudanzas",
+];
+
+pub async fn create_admin(
+    dni: String,
+    email: String,
+    name: String,
+    date_of_birth: chrono::NaiveDate,
+    contact_number: String,
+    password: Option<Secret>,
+    conn: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    let password = read_password(password)?;
+
+    let user = User {
+        dni,
+        email,
+        password,
+        name,
+        date_of_birth,
+        is_seller: false,
+        updated_at: None,
+        latitude: None,
+        longitude: None,
+        contact_number,
+        category_id: None,
+        rol: Roles::Admin,
+    };
+
+    let admin = UserRepository::new().save_admin(user, conn).await?;
+
+    println!("Admin {} created with id {}", admin.dni, admin.id);
+
+    Ok(())
+}
+
+pub async fn seed_categories(file: Option<PathBuf>, conn: &PgPool) -> Result<(), Box<dyn Error>> {
+    let names: Vec<String> = match file {
+        Some(file) => fs::read_to_string(file)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        None => DEFAULT_CATEGORIES
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    };
+
+    let repository = CategoryRepository::new();
+
+    for name in names {
+        match repository.get_by_name(name.clone(), conn).await {
+            Ok(category) => println!("Skipped {} (id {})", category.name, category.id),
+            Err(sqlx::Error::RowNotFound) => {
+                let category = repository.save(Category { name }, conn).await?;
+                println!("Created {} (id {})", category.name, category.id);
+            }
+            Err(error) => return Err(Box::new(error)),
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn reset_password(
+    dni: String,
+    password: Option<Secret>,
+    conn: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    let password = read_password(password)?;
+
+    let user = UserRepository::new()
+        .update_password(dni, password, conn)
+        .await?;
+
+    println!("Password of {} updated", user.dni);
+
+    Ok(())
+}
+
+pub async fn promote_seller(
+    dni: String,
+    category_id: i64,
+    conn: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    let category = CategoryRepository::new()
+        .get_by_id(category_id, conn)
+        .await?;
+
+    let user = UserRepository::new()
+        .promote_to_seller(dni, category.id, conn)
+        .await?;
+
+    println!("{} is now a seller of {}", user.dni, category.name);
+
+    Ok(())
+}
+
+pub async fn stats(conn: &PgPool) -> Result<(), Box<dyn Error>> {
+    let statistics = StatisticsRepository::new()
+        .get_table_statistics(conn)
+        .await?;
+
+    println!("{:<24} {:>12} {:>14}", "TABLE", "ROWS", "SIZE (BYTES)");
+
+    for table in statistics {
+        println!(
+            "{:<24} {:>12} {:>14}",
+            table.table, table.rows, table.total_bytes
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn refresh_reputations(conn: &PgPool) -> Result<(), Box<dyn Error>> {
+    let refreshed = ReputationRepository::new().refresh_all(conn).await?;
+
+    println!("Refreshed the reputation of {} users", refreshed);
+
+    Ok(())
+}
+
+pub async fn purge_locations(conn: &PgPool) -> Result<(), Box<dyn Error>> {
+    let report = LocationHistoryRepository::new().purge(conn).await?;
+
+    println!(
+        "Dropped {} partitions and deleted {} locations older than the retention",
+        report.dropped_partitions, report.deleted_points
+    );
+
+    Ok(())
+}
+
+pub async fn import(
+    entity: Entity,
+    file: PathBuf,
+    format: Option<BulkFormat>,
+    conn: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    let format = match format {
+        Some(format) => format,
+        None => file
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .parse::<BulkFormat>()?,
+    };
+
+    let data = fs::read(file)?;
+    let repository = BulkRepository::new();
+
+    let report = match entity {
+        Entity::Categories => repository.import_categories(format, &data, conn).await?,
+        Entity::Users => repository.import_users(format, &data, conn).await?,
+        Entity::Services => repository.import_services(format, &data, conn).await?,
+    };
+
+    if report.errors.is_empty() {
+        println!("{} rows imported", report.inserted);
+
+        return Ok(());
+    }
+
+    for error in report.errors.iter() {
+        eprintln!("Row {}: {}", error.row, error.message);
+    }
+
+    Err(format!(
+        "Nothing was imported, {} rows have errors",
+        report.errors.len()
+    )
+    .into())
+}
+
+pub async fn export(
+    entity: Entity,
+    format: BulkFormat,
+    output: Option<PathBuf>,
+    conn: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    let repository = BulkRepository::new();
+
+    let data = match entity {
+        Entity::Categories => repository.export_categories(format, conn).await?,
+        Entity::Users => repository.export_users(format, conn).await?,
+        Entity::Services => repository.export_services(format, conn).await?,
+    };
+
+    match output {
+        Some(output) => fs::write(output, data)?,
+        None => std::io::stdout().write_all(&data)?,
+    }
+
+    Ok(())
+}
+
+/// Returns the given password or reads it from the first line of the standard input
+fn read_password(password: Option<Secret>) -> Result<Secret, Box<dyn Error>> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+
+    eprint!("Password: ");
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+
+    if password.is_empty() {
+        return Err("The password can't be empty".into());
+    }
+
+    Ok(Secret::from(password))
+}
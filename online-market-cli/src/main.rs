@@ -0,0 +1,183 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use dotenv::dotenv;
+use online_market_data::{
+    audit::{self, AuditContext},
+    bulk::BulkFormat,
+};
+use online_market_model::Secret;
+use sqlx::postgres::PgPoolOptions;
+use std::{env, path::PathBuf};
+
+mod commands;
+
+#[derive(Parser)]
+#[command(about = "Bootstrapping and maintenance tool for the online market")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a user with the Admin role
+    CreateAdmin {
+        #[arg(long)]
+        dni: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        name: String,
+        /// Date of birth with the format YYYY-MM-DD
+        #[arg(long)]
+        date_of_birth: chrono::NaiveDate,
+        #[arg(long)]
+        contact_number: String,
+        /// Read from the standard input when it isn't provided
+        #[arg(long)]
+        password: Option<Secret>,
+    },
+    /// Insert the categories of the catalogue that don't exist yet
+    SeedCategories {
+        /// File with one category name per line. The default catalogue is used when it isn't provided
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Replace the password of a user
+    ResetPassword {
+        #[arg(long)]
+        dni: String,
+        /// Read from the standard input when it isn't provided
+        #[arg(long)]
+        password: Option<Secret>,
+    },
+    /// Mark a user as seller of the given category
+    PromoteSeller {
+        #[arg(long)]
+        dni: String,
+        #[arg(long)]
+        category_id: i64,
+    },
+    /// Print the row count and size of every table
+    Stats,
+    /// Compute again the reputation of every rated user, so the older rates lose weight.
+    ///
+    /// The server does it every REPUTATION_REFRESH_INTERVAL_SECONDS. When that's 0, run this
+    /// command at least once a day
+    RefreshReputations,
+    /// Remove the locations older than the retention and create the partitions of the next days.
+    ///
+    /// The server does it every LOCATION_PURGE_INTERVAL_SECONDS. When that's 0, run this command
+    /// at least once a day, or the new locations land in the default partition and are never
+    /// removed
+    PurgeLocations,
+    /// Validate and insert every row of a CSV or NDJSON file in a single transaction
+    Import {
+        #[arg(value_enum)]
+        entity: Entity,
+        #[arg(long)]
+        file: PathBuf,
+        /// csv or ndjson. Inferred from the file extension when it isn't provided
+        #[arg(long)]
+        format: Option<BulkFormat>,
+    },
+    /// Write every row as CSV or NDJSON
+    Export {
+        #[arg(value_enum)]
+        entity: Entity,
+        /// csv or ndjson
+        #[arg(long, default_value = "csv")]
+        format: BulkFormat,
+        /// Printed to the standard output when it isn't provided
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Entity {
+    Categories,
+    Users,
+    Services,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    let cli = Cli::parse();
+
+    let database_url = env::var("DATABASE_URL").unwrap();
+
+    // Create database connection
+    let pool = match audit::track_context(PgPoolOptions::new())
+        .connect(&database_url)
+        .await
+    {
+        Ok(pool) => pool,
+        Err(error) => {
+            eprintln!(
+                "Something went wrong while creating database connection. {}",
+                error
+            );
+
+            std::process::exit(1);
+        }
+    };
+
+    // The changes made by the tool are recorded in the audit log without actor
+    let context = AuditContext {
+        actor: None,
+        request_id: Some("online-market-admin".to_owned()),
+    };
+
+    let result = audit::scope(context, async {
+        match cli.command {
+            Command::CreateAdmin {
+                dni,
+                email,
+                name,
+                date_of_birth,
+                contact_number,
+                password,
+            } => {
+                commands::create_admin(
+                    dni,
+                    email,
+                    name,
+                    date_of_birth,
+                    contact_number,
+                    password,
+                    &pool,
+                )
+                .await
+            }
+            Command::SeedCategories { file } => commands::seed_categories(file, &pool).await,
+            Command::ResetPassword { dni, password } => {
+                commands::reset_password(dni, password, &pool).await
+            }
+            Command::PromoteSeller { dni, category_id } => {
+                commands::promote_seller(dni, category_id, &pool).await
+            }
+            Command::Stats => commands::stats(&pool).await,
+            Command::RefreshReputations => commands::refresh_reputations(&pool).await,
+            Command::PurgeLocations => commands::purge_locations(&pool).await,
+            Command::Import {
+                entity,
+                file,
+                format,
+            } => commands::import(entity, file, format, &pool).await,
+            Command::Export {
+                entity,
+                format,
+                output,
+            } => commands::export(entity, format, output, &pool).await,
+        }
+    })
+    .await;
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,4 @@
+// Rebuild the crate when a migration changes so the embedded migrations stay up to date
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE users ADD COLUMN seller_paused_at TIMESTAMP WITH TIME ZONE;
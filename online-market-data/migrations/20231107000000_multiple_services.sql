@@ -0,0 +1,13 @@
+-- A seller can publish several services
+ALTER TABLE services DROP CONSTRAINT services_user_id_key;
+
+CREATE INDEX services_user_id_idx ON services (user_id);
+
+-- Settings of the market configurable by admins, it only has one row
+CREATE TABLE settings (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    max_services_per_seller INTEGER NOT NULL DEFAULT 5 CHECK (max_services_per_seller > 0),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+INSERT INTO settings DEFAULT VALUES;
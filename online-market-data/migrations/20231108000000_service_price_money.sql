@@ -0,0 +1,11 @@
+-- Prices are exact decimals with the currency they are expressed in.
+-- The real is read as double precision, a direct cast keeps only 6 significant digits
+ALTER TABLE services
+    ALTER COLUMN price TYPE NUMERIC(14, 2) USING round(price::double precision::numeric, 2);
+
+ALTER TABLE services
+    ADD COLUMN currency CHAR(3) NOT NULL DEFAULT 'COP';
+
+ALTER TABLE services
+    ADD CONSTRAINT services_price_positive CHECK (price >= 0),
+    ADD CONSTRAINT services_currency_iso CHECK (currency ~ '^[A-Z]{3}$');
@@ -0,0 +1,67 @@
+-- Weekly availability of the sellers, the hours are in the timezone of the seller
+CREATE TYPE weekday AS ENUM ('monday', 'tuesday', 'wednesday', 'thursday', 'friday', 'saturday', 'sunday');
+
+CREATE TABLE seller_schedules (
+    user_id VARCHAR(10) PRIMARY KEY,
+    timezone VARCHAR(64) NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT fk_seller_schedules_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE availability_windows (
+    id BIGSERIAL PRIMARY KEY,
+    user_id VARCHAR(10) NOT NULL,
+    weekday weekday NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    CHECK (start_time < end_time),
+    CONSTRAINT fk_availability_windows_schedules
+        FOREIGN KEY (user_id)
+            REFERENCES seller_schedules (user_id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX availability_windows_user_id_idx ON availability_windows (user_id, weekday);
+
+-- Dates the seller doesn't work, like holidays
+CREATE TABLE availability_exceptions (
+    user_id VARCHAR(10) NOT NULL,
+    date DATE NOT NULL,
+    reason VARCHAR(200),
+    PRIMARY KEY (user_id, date),
+    CONSTRAINT fk_availability_exceptions_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE
+);
+
+CREATE TYPE booking_status AS ENUM ('pending', 'confirmed', 'completed', 'cancelled');
+
+-- The service is kept as NULL when it's deleted so the history of the seller remains
+CREATE TABLE bookings (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    service_id UUID,
+    customer_id VARCHAR(10) NOT NULL,
+    seller_id VARCHAR(10) NOT NULL,
+    starts_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ends_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    status booking_status NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CHECK (starts_at < ends_at),
+    CONSTRAINT fk_bookings_services
+        FOREIGN KEY (service_id)
+            REFERENCES services (id)
+            ON DELETE SET NULL,
+    CONSTRAINT fk_bookings_customers
+        FOREIGN KEY (customer_id)
+            REFERENCES users (dni),
+    CONSTRAINT fk_bookings_sellers
+        FOREIGN KEY (seller_id)
+            REFERENCES users (dni)
+);
+
+CREATE INDEX bookings_seller_id_idx ON bookings (seller_id, starts_at);
+CREATE INDEX bookings_customer_id_idx ON bookings (customer_id, starts_at);
@@ -0,0 +1,33 @@
+-- Services and sellers bookmarked by the users. Favorites go away with the service or seller
+CREATE TABLE favorite_services (
+    user_id VARCHAR(10) NOT NULL,
+    service_id UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, service_id),
+    CONSTRAINT fk_favorite_services_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_favorite_services_services
+        FOREIGN KEY (service_id)
+            REFERENCES services (id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX favorite_services_service_id_idx ON favorite_services (service_id);
+
+CREATE TABLE favorite_sellers (
+    user_id VARCHAR(10) NOT NULL,
+    seller_id VARCHAR(10) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, seller_id),
+    CHECK (user_id <> seller_id),
+    CONSTRAINT fk_favorite_sellers_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_favorite_sellers_sellers
+        FOREIGN KEY (seller_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE
+);
@@ -0,0 +1,56 @@
+-- Hidden comments stay in the table so admins can restore them
+ALTER TABLE comments ADD COLUMN hidden_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TYPE moderation_action AS ENUM ('hide', 'restore', 'dismiss', 'warn');
+
+-- A report is open until an admin resolves it with an action
+CREATE TABLE comment_reports (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    commentator VARCHAR(10) NOT NULL,
+    commented VARCHAR(10) NOT NULL,
+    reporter VARCHAR(10) NOT NULL,
+    reason VARCHAR(200) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    action moderation_action,
+    resolved_by VARCHAR(10),
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    CHECK ((action IS NULL) = (resolved_at IS NULL)),
+    CONSTRAINT fk_comment_reports_comments
+        FOREIGN KEY (commentator, commented)
+            REFERENCES comments (commentator, commented)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_comment_reports_reporters
+        FOREIGN KEY (reporter)
+            REFERENCES users (dni),
+    CONSTRAINT fk_comment_reports_admins
+        FOREIGN KEY (resolved_by)
+            REFERENCES users (dni)
+);
+
+-- A user can have only one open report of the same comment
+CREATE UNIQUE INDEX comment_reports_open_idx ON comment_reports (commentator, commented, reporter)
+    WHERE resolved_at IS NULL;
+CREATE INDEX comment_reports_created_at_idx ON comment_reports (created_at)
+    WHERE resolved_at IS NULL;
+
+CREATE TABLE user_warnings (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id VARCHAR(10) NOT NULL,
+    report_id UUID,
+    reason VARCHAR(200) NOT NULL,
+    issued_by VARCHAR(10) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT fk_user_warnings_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_user_warnings_comment_reports
+        FOREIGN KEY (report_id)
+            REFERENCES comment_reports (id)
+            ON DELETE SET NULL,
+    CONSTRAINT fk_user_warnings_admins
+        FOREIGN KEY (issued_by)
+            REFERENCES users (dni)
+);
+
+CREATE INDEX user_warnings_user_id_idx ON user_warnings (user_id);
@@ -0,0 +1,33 @@
+-- Comments get their own id so a user can comment more than once and replies can point to them
+ALTER TABLE comments ADD COLUMN id UUID NOT NULL DEFAULT uuid_generate_v4();
+ALTER TABLE comments ADD PRIMARY KEY (id);
+
+-- Replies point to a top level comment and are removed with it
+ALTER TABLE comments ADD COLUMN parent_id UUID;
+ALTER TABLE comments ADD CONSTRAINT fk_comments_parents
+    FOREIGN KEY (parent_id)
+        REFERENCES comments (id)
+        ON DELETE CASCADE;
+
+-- Reports point to the comment by its id
+ALTER TABLE comment_reports ADD COLUMN comment_id UUID;
+UPDATE comment_reports r SET comment_id = c.id
+    FROM comments c
+    WHERE c.commentator = r.commentator AND c.commented = r.commented;
+ALTER TABLE comment_reports ALTER COLUMN comment_id SET NOT NULL;
+
+DROP INDEX comment_reports_open_idx;
+ALTER TABLE comment_reports DROP CONSTRAINT fk_comment_reports_comments;
+ALTER TABLE comment_reports DROP COLUMN commentator, DROP COLUMN commented;
+ALTER TABLE comment_reports ADD CONSTRAINT fk_comment_reports_comments
+    FOREIGN KEY (comment_id)
+        REFERENCES comments (id)
+        ON DELETE CASCADE;
+CREATE UNIQUE INDEX comment_reports_open_idx ON comment_reports (comment_id, reporter)
+    WHERE resolved_at IS NULL;
+
+ALTER TABLE comments DROP CONSTRAINT comments_commentator_commented_key;
+
+CREATE INDEX comments_commented_idx ON comments (commented, created_at) WHERE parent_id IS NULL;
+CREATE INDEX comments_commentator_idx ON comments (commentator, created_at);
+CREATE INDEX comments_parent_id_idx ON comments (parent_id, created_at);
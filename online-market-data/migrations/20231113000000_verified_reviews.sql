@@ -0,0 +1,19 @@
+-- off: reviews are never verified, flag: every review is accepted and marked when verified,
+-- require: only verified reviews are accepted
+CREATE TYPE review_verification AS ENUM ('off', 'flag', 'require');
+
+ALTER TABLE settings ADD COLUMN review_verification review_verification NOT NULL DEFAULT 'flag';
+
+-- A review is verified when the reviewer has a completed booking with the reviewed seller
+ALTER TABLE rates ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE comments ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE rates SET verified = EXISTS (
+    SELECT 1 FROM bookings
+    WHERE customer_id = rates.rater AND seller_id = rates.rated AND status = 'completed'
+);
+UPDATE comments SET verified = EXISTS (
+    SELECT 1 FROM bookings
+    WHERE customer_id = comments.commentator AND seller_id = comments.commented AND status = 'completed'
+)
+WHERE parent_id IS NULL;
@@ -0,0 +1,42 @@
+-- The reputation is the average of the rates weighted by their age, pulled towards the prior mean
+-- as if the seller had prior_weight rates of prior_mean
+ALTER TABLE settings
+    ADD COLUMN reputation_prior_mean REAL NOT NULL DEFAULT 3 CHECK (reputation_prior_mean >= 0),
+    ADD COLUMN reputation_prior_weight REAL NOT NULL DEFAULT 10 CHECK (reputation_prior_weight > 0),
+    ADD COLUMN reputation_half_life_days INTEGER NOT NULL DEFAULT 180 CHECK (reputation_half_life_days > 0);
+
+-- Stored reputation of the rated users, refreshed when their rates change
+CREATE TABLE reputations (
+    user_id VARCHAR(10) PRIMARY KEY,
+    score DOUBLE PRECISION NOT NULL,
+    average_rate DOUBLE PRECISION,
+    rates_count BIGINT NOT NULL,
+    refreshed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT fk_reputations_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX reputations_score_idx ON reputations (score DESC);
+
+INSERT INTO reputations (user_id, score, average_rate, rates_count)
+SELECT
+    r.rated,
+    (s.reputation_prior_weight * s.reputation_prior_mean + SUM(r.weight * r.rate))
+        / (s.reputation_prior_weight + SUM(r.weight)),
+    AVG(r.rate),
+    COUNT(*)
+FROM settings s
+CROSS JOIN LATERAL (
+    SELECT
+        rated,
+        rate::DOUBLE PRECISION as rate,
+        power(
+            0.5::DOUBLE PRECISION,
+            EXTRACT(EPOCH FROM now() - COALESCE(updated_at, created_at))::DOUBLE PRECISION
+                / 86400 / s.reputation_half_life_days
+        ) as weight
+    FROM rates
+) r
+GROUP BY r.rated, s.reputation_prior_weight, s.reputation_prior_mean;
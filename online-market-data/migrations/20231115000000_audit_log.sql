@@ -0,0 +1,100 @@
+CREATE TYPE audit_action AS ENUM ('insert', 'update', 'delete');
+
+-- Append-only record of every change of the audited tables
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    -- Dni of the authenticated user, NULL for anonymous requests and maintenance tasks
+    actor TEXT,
+    action audit_action NOT NULL,
+    entity TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    before JSONB,
+    after JSONB,
+    request_id TEXT
+);
+
+CREATE INDEX audit_log_entity_idx ON audit_log (entity, entity_id, id);
+CREATE INDEX audit_log_actor_idx ON audit_log (actor, id);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+
+-- Records the change of a row. The arguments of the trigger are the columns that identify
+-- the row, joined with ':' in entity_id. The actor and request id are the audit.actor and
+-- audit.request_id settings of the connection
+CREATE FUNCTION audit_row() RETURNS trigger AS $$
+DECLARE
+    old_row JSONB;
+    new_row JSONB;
+    key_row JSONB;
+    key_values TEXT[] := '{}';
+    i INTEGER;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        old_row := to_jsonb(OLD) - 'password';
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        new_row := to_jsonb(NEW) - 'password';
+    END IF;
+
+    -- Upserts that keep the row as it was don't change anything
+    IF TG_OP = 'UPDATE' AND to_jsonb(OLD) = to_jsonb(NEW) THEN
+        RETURN NULL;
+    END IF;
+
+    key_row := COALESCE(new_row, old_row);
+    FOR i IN 0 .. TG_NARGS - 1 LOOP
+        key_values := key_values || (key_row ->> TG_ARGV[i]);
+    END LOOP;
+
+    INSERT INTO audit_log (actor, action, entity, entity_id, before, after, request_id)
+    VALUES (
+        NULLIF(current_setting('audit.actor', true), ''),
+        lower(TG_OP)::audit_action,
+        TG_TABLE_NAME,
+        array_to_string(key_values, ':'),
+        old_row,
+        new_row,
+        NULLIF(current_setting('audit.request_id', true), '')
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_users AFTER INSERT OR UPDATE OR DELETE ON users
+    FOR EACH ROW EXECUTE FUNCTION audit_row('dni');
+CREATE TRIGGER audit_categories AFTER INSERT OR UPDATE OR DELETE ON categories
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_services AFTER INSERT OR UPDATE OR DELETE ON services
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_rates AFTER INSERT OR UPDATE OR DELETE ON rates
+    FOR EACH ROW EXECUTE FUNCTION audit_row('rater', 'rated');
+CREATE TRIGGER audit_comments AFTER INSERT OR UPDATE OR DELETE ON comments
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_comment_reports AFTER INSERT OR UPDATE OR DELETE ON comment_reports
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_user_warnings AFTER INSERT OR UPDATE OR DELETE ON user_warnings
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_settings AFTER INSERT OR UPDATE OR DELETE ON settings
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_seller_schedules AFTER INSERT OR UPDATE OR DELETE ON seller_schedules
+    FOR EACH ROW EXECUTE FUNCTION audit_row('user_id');
+CREATE TRIGGER audit_availability_windows AFTER INSERT OR UPDATE OR DELETE ON availability_windows
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_availability_exceptions AFTER INSERT OR UPDATE OR DELETE ON availability_exceptions
+    FOR EACH ROW EXECUTE FUNCTION audit_row('user_id', 'date');
+CREATE TRIGGER audit_bookings AFTER INSERT OR UPDATE OR DELETE ON bookings
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
+CREATE TRIGGER audit_favorite_services AFTER INSERT OR UPDATE OR DELETE ON favorite_services
+    FOR EACH ROW EXECUTE FUNCTION audit_row('user_id', 'service_id');
+CREATE TRIGGER audit_favorite_sellers AFTER INSERT OR UPDATE OR DELETE ON favorite_sellers
+    FOR EACH ROW EXECUTE FUNCTION audit_row('user_id', 'seller_id');
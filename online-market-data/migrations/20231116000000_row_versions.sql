@@ -0,0 +1,26 @@
+-- Version of the rows that clients edit, used as their ETag. Updates with an outdated
+-- version are rejected, so concurrent edits can't overwrite each other
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE services ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE rates ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE comments ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Every change of the row increases its version, whatever the statement that makes it
+CREATE FUNCTION bump_version() RETURNS trigger AS $$
+BEGIN
+    IF NEW IS DISTINCT FROM OLD THEN
+        NEW.version := OLD.version + 1;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER version_users BEFORE UPDATE ON users
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER version_services BEFORE UPDATE ON services
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER version_rates BEFORE UPDATE ON rates
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
+CREATE TRIGGER version_comments BEFORE UPDATE ON comments
+    FOR EACH ROW EXECUTE FUNCTION bump_version();
@@ -0,0 +1,15 @@
+-- Names of the categories in the languages of the API. Categories without a label in a
+-- language are shown with their name
+CREATE TABLE category_labels (
+    category_id INTEGER NOT NULL,
+    language VARCHAR(2) NOT NULL,
+    label VARCHAR(50) NOT NULL,
+    PRIMARY KEY (category_id, language),
+    CONSTRAINT fk_category_labels_categories
+        FOREIGN KEY (category_id)
+            REFERENCES categories (id)
+            ON DELETE CASCADE
+);
+
+CREATE TRIGGER audit_category_labels AFTER INSERT OR UPDATE OR DELETE ON category_labels
+    FOR EACH ROW EXECUTE FUNCTION audit_row('category_id', 'language');
@@ -0,0 +1,40 @@
+-- Every location received from the users. The table is partitioned by day, so the points older
+-- than the retention are dropped with their partition instead of deleted one by one
+CREATE TABLE location_history (
+    user_id VARCHAR(10) NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT fk_location_history_users
+        FOREIGN KEY (user_id)
+            REFERENCES users (dni)
+            ON DELETE CASCADE
+) PARTITION BY RANGE (recorded_at);
+
+CREATE INDEX location_history_user_id_recorded_at_idx ON location_history (user_id, recorded_at);
+
+-- Points of the days whose partition wasn't created in time, so no location is lost
+CREATE TABLE location_history_default PARTITION OF location_history DEFAULT;
+
+-- Creates the partition of the given UTC day when it doesn't exist. When the default partition
+-- already has points of that day the partition isn't created and they stay there
+CREATE FUNCTION create_location_history_partition(day DATE) RETURNS void AS $$
+BEGIN
+    EXECUTE format(
+        'CREATE TABLE IF NOT EXISTS %I PARTITION OF location_history FOR VALUES FROM (%L) TO (%L)',
+        'location_history_' || to_char(day, 'YYYYMMDD'),
+        day::timestamp AT TIME ZONE 'UTC',
+        (day + 1)::timestamp AT TIME ZONE 'UTC'
+    );
+EXCEPTION
+    WHEN check_violation THEN
+        NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT create_location_history_partition((now() AT TIME ZONE 'UTC')::date + days)
+FROM generate_series(0, 7) days;
+
+-- Days the location history is kept
+ALTER TABLE settings
+    ADD COLUMN location_retention_days INTEGER NOT NULL DEFAULT 30 CHECK (location_retention_days > 0);
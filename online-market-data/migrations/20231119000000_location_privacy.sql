@@ -0,0 +1,10 @@
+-- Who can see the location of each user
+CREATE TYPE location_visibility AS ENUM ('hidden', 'approximate', 'exact_to_booked_customers');
+
+-- Until users choose, their location is only shown approximately
+ALTER TABLE users
+    ADD COLUMN location_visibility location_visibility NOT NULL DEFAULT 'approximate';
+
+-- Size of the cells of the grid the approximate locations are snapped to
+ALTER TABLE settings
+    ADD COLUMN location_grid_meters INTEGER NOT NULL DEFAULT 1000 CHECK (location_grid_meters > 0);
@@ -0,0 +1,23 @@
+-- Zones a seller travels to for a home-visit service, a GeoJSON polygon or a circle. The
+-- bounding box of the zone finds the candidates of a point before the exact check
+CREATE TABLE service_areas (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    service_id UUID NOT NULL,
+    geometry JSONB NOT NULL,
+    min_latitude DOUBLE PRECISION NOT NULL,
+    max_latitude DOUBLE PRECISION NOT NULL,
+    min_longitude DOUBLE PRECISION NOT NULL,
+    max_longitude DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CHECK (min_latitude <= max_latitude AND min_longitude <= max_longitude),
+    CONSTRAINT fk_service_areas_services
+        FOREIGN KEY (service_id)
+            REFERENCES services (id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX service_areas_service_id_idx ON service_areas (service_id);
+CREATE INDEX service_areas_bounds_idx ON service_areas (min_latitude, max_latitude, min_longitude, max_longitude);
+
+CREATE TRIGGER audit_service_areas AFTER INSERT OR UPDATE OR DELETE ON service_areas
+    FOR EACH ROW EXECUTE FUNCTION audit_row('id');
@@ -0,0 +1,12 @@
+-- The location of a user streams in through the WebSocket, it isn't an edit of the profile, so
+-- it doesn't change the version. Otherwise a client sending its location could never update its
+-- own profile with the ETag it read
+CREATE OR REPLACE FUNCTION bump_version() RETURNS trigger AS $$
+BEGIN
+    IF to_jsonb(NEW) - 'latitude' - 'longitude' IS DISTINCT FROM to_jsonb(OLD) - 'latitude' - 'longitude' THEN
+        NEW.version := OLD.version + 1;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,60 @@
+-- The locations of the users are kept in location_history, which is purged after the retention
+-- and shown following the privacy of each user. The audit log is append-only, so it doesn't
+-- record them: updates that only move a user aren't audited and the rest leave the location out
+CREATE OR REPLACE FUNCTION audit_row() RETURNS trigger AS $$
+DECLARE
+    old_row JSONB;
+    new_row JSONB;
+    key_row JSONB;
+    key_values TEXT[] := '{}';
+    i INTEGER;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        old_row := to_jsonb(OLD) - 'password' - 'latitude' - 'longitude';
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        new_row := to_jsonb(NEW) - 'password' - 'latitude' - 'longitude';
+    END IF;
+
+    -- Upserts that keep the row as it was and location updates don't change anything audited
+    IF TG_OP = 'UPDATE'
+        AND to_jsonb(OLD) - 'latitude' - 'longitude' = to_jsonb(NEW) - 'latitude' - 'longitude' THEN
+        RETURN NULL;
+    END IF;
+
+    key_row := COALESCE(new_row, old_row);
+    FOR i IN 0 .. TG_NARGS - 1 LOOP
+        key_values := key_values || (key_row ->> TG_ARGV[i]);
+    END LOOP;
+
+    INSERT INTO audit_log (actor, action, entity, entity_id, before, after, request_id)
+    VALUES (
+        NULLIF(current_setting('audit.actor', true), ''),
+        lower(TG_OP)::audit_action,
+        TG_TABLE_NAME,
+        array_to_string(key_values, ':'),
+        old_row,
+        new_row,
+        NULLIF(current_setting('audit.request_id', true), '')
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Removes the locations already recorded. Only this migration may change the audit log.
+-- Location updates used to increase the version, so it's left out of the comparison
+ALTER TABLE audit_log DISABLE TRIGGER audit_log_append_only;
+
+DELETE FROM audit_log
+WHERE entity = 'users'
+AND action = 'update'
+AND before - 'latitude' - 'longitude' - 'version' = after - 'latitude' - 'longitude' - 'version';
+
+UPDATE audit_log
+SET before = before - 'latitude' - 'longitude',
+    after = after - 'latitude' - 'longitude'
+WHERE entity = 'users'
+AND (before ?| ARRAY['latitude', 'longitude'] OR after ?| ARRAY['latitude', 'longitude']);
+
+ALTER TABLE audit_log ENABLE TRIGGER audit_log_append_only;
@@ -0,0 +1,583 @@
+use std::{collections::HashSet, error::Error};
+
+use online_market_model::{Modality, ServiceArea, ServiceResponse};
+use serde::Deserialize;
+use sqlx::{types::Json, PgConnection, PgPool};
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+    errors::InvalidInput,
+    location::{distance_km, valid_coordinates, METERS_PER_DEGREE},
+    metrics, ServiceRecord,
+};
+
+const MAX_AREAS_PER_SERVICE: usize = 10;
+const MIN_RING_POSITIONS: usize = 4;
+const MAX_RING_POSITIONS: usize = 1000;
+const MAX_RADIUS_METERS: f64 = 200_000.0;
+
+#[derive(Deserialize, IntoParams)]
+pub struct PointRequest {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Position of a geometry as (longitude, latitude)
+type Position = (f64, f64);
+
+/// Smallest box that contains an area
+struct Bounds {
+    min_latitude: f64,
+    max_latitude: f64,
+    min_longitude: f64,
+    max_longitude: f64,
+}
+
+/// Reads a GeoJSON position, [longitude, latitude] with an optional altitude
+fn position(position: &[f64]) -> Result<Position, InvalidInput> {
+    match position {
+        [longitude, latitude] | [longitude, latitude, _]
+            if valid_coordinates(*latitude, *longitude) =>
+        {
+            Ok((*longitude, *latitude))
+        }
+        _ => Err(InvalidInput::new(
+            "POSITIONS MUST BE [longitude, latitude] ON THE EARTH",
+        )),
+    }
+}
+
+/// Which side of the line from a to b the point c is: positive on the left, negative on the
+/// right and zero on the line
+fn orientation(a: Position, b: Position, c: Position) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Returns if the point, already on the line of the segment, is between its ends
+fn within_segment(a: Position, b: Position, point: Position) -> bool {
+    a.0.min(b.0) <= point.0
+        && point.0 <= a.0.max(b.0)
+        && a.1.min(b.1) <= point.1
+        && point.1 <= a.1.max(b.1)
+}
+
+fn segments_intersect(a: (Position, Position), b: (Position, Position)) -> bool {
+    let d1 = orientation(b.0, b.1, a.0);
+    let d2 = orientation(b.0, b.1, a.1);
+    let d3 = orientation(a.0, a.1, b.0);
+    let d4 = orientation(a.0, a.1, b.1);
+
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+
+    (d1 == 0.0 && within_segment(b.0, b.1, a.0))
+        || (d2 == 0.0 && within_segment(b.0, b.1, a.1))
+        || (d3 == 0.0 && within_segment(a.0, a.1, b.0))
+        || (d4 == 0.0 && within_segment(a.0, a.1, b.1))
+}
+
+/// Checks that the ring is closed, encloses an area and doesn't cross itself
+fn validate_ring(ring: &[Vec<f64>]) -> Result<Vec<Position>, InvalidInput> {
+    if !(MIN_RING_POSITIONS..=MAX_RING_POSITIONS).contains(&ring.len()) {
+        return Err(InvalidInput::new(
+            "A RING MUST HAVE BETWEEN 4 AND 1000 POSITIONS",
+        ));
+    }
+
+    let ring = ring
+        .iter()
+        .map(|value| position(value))
+        .collect::<Result<Vec<Position>, InvalidInput>>()?;
+
+    if ring.first() != ring.last() {
+        return Err(InvalidInput::new("RINGS MUST END AT THEIR FIRST POSITION"));
+    }
+
+    // Shoelace formula, zero when every position is on the same line
+    let area: f64 = ring
+        .windows(2)
+        .map(|edge| edge[0].0 * edge[1].1 - edge[1].0 * edge[0].1)
+        .sum();
+
+    if area == 0.0 {
+        return Err(InvalidInput::new("RINGS MUST ENCLOSE AN AREA"));
+    }
+
+    let edges: Vec<(Position, Position)> = ring.windows(2).map(|edge| (edge[0], edge[1])).collect();
+
+    // Consecutive edges share a position, so only the rest are compared
+    for i in 0..edges.len() {
+        for j in i + 2..edges.len() {
+            if i == 0 && j == edges.len() - 1 {
+                continue;
+            }
+
+            if segments_intersect(edges[i], edges[j]) {
+                return Err(InvalidInput::new("RINGS CAN'T CROSS THEMSELVES"));
+            }
+        }
+    }
+
+    Ok(ring)
+}
+
+/// Checks the geometry of the area and returns its bounds
+fn validate(area: &ServiceArea) -> Result<Bounds, InvalidInput> {
+    match area {
+        ServiceArea::Polygon { coordinates } => {
+            let Some(boundary) = coordinates.first() else {
+                return Err(InvalidInput::new(
+                    "A POLYGON NEEDS AT LEAST ITS BOUNDARY RING",
+                ));
+            };
+
+            let boundary = validate_ring(boundary)?;
+
+            for hole in &coordinates[1..] {
+                validate_ring(hole)?;
+            }
+
+            Ok(Bounds {
+                min_latitude: boundary.iter().map(|p| p.1).fold(f64::INFINITY, f64::min),
+                max_latitude: boundary
+                    .iter()
+                    .map(|p| p.1)
+                    .fold(f64::NEG_INFINITY, f64::max),
+                min_longitude: boundary.iter().map(|p| p.0).fold(f64::INFINITY, f64::min),
+                max_longitude: boundary
+                    .iter()
+                    .map(|p| p.0)
+                    .fold(f64::NEG_INFINITY, f64::max),
+            })
+        }
+        ServiceArea::Circle {
+            center,
+            radius_meters,
+        } => {
+            let (longitude, latitude) = position(center)?;
+
+            if !(*radius_meters > 0.0 && *radius_meters <= MAX_RADIUS_METERS) {
+                return Err(InvalidInput::new(
+                    "THE RADIUS MUST BE GREATER THAN 0 AND UP TO 200000 METERS",
+                ));
+            }
+
+            let latitude_delta = radius_meters / METERS_PER_DEGREE;
+            let longitude_delta = latitude_delta / latitude.to_radians().cos().max(0.01);
+
+            Ok(Bounds {
+                min_latitude: (latitude - latitude_delta).max(-90.0),
+                max_latitude: (latitude + latitude_delta).min(90.0),
+                min_longitude: (longitude - longitude_delta).max(-180.0),
+                max_longitude: (longitude + longitude_delta).min(180.0),
+            })
+        }
+    }
+}
+
+/// Returns if the point is inside the ring, casting a ray towards the east and counting the
+/// edges it crosses
+fn ring_contains(ring: &[Vec<f64>], point: Position) -> bool {
+    let mut inside = false;
+
+    for edge in ring.windows(2) {
+        let (Ok(a), Ok(b)) = (position(&edge[0]), position(&edge[1])) else {
+            continue;
+        };
+
+        if (a.1 > point.1) != (b.1 > point.1) {
+            let longitude = a.0 + (point.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+
+            if point.0 < longitude {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+/// Returns if the area covers the point. Polygons are treated as flat, which is accurate for
+/// the size of a city
+fn covers(area: &ServiceArea, latitude: f64, longitude: f64) -> bool {
+    match area {
+        ServiceArea::Polygon { coordinates } => match coordinates.split_first() {
+            Some((boundary, holes)) => {
+                ring_contains(boundary, (longitude, latitude))
+                    && !holes
+                        .iter()
+                        .any(|hole| ring_contains(hole, (longitude, latitude)))
+            }
+            None => false,
+        },
+        ServiceArea::Circle {
+            center,
+            radius_meters,
+        } => match position(center) {
+            Ok((center_longitude, center_latitude)) => {
+                distance_km((latitude, longitude), (center_latitude, center_longitude)) * 1000.0
+                    <= *radius_meters
+            }
+            Err(_) => false,
+        },
+    }
+}
+
+/// Returns the ids of the services with an area that covers the point
+pub(crate) async fn covering(
+    latitude: f64,
+    longitude: f64,
+    conn: &mut PgConnection,
+) -> Result<HashSet<Uuid>, sqlx::Error> {
+    let candidates = sqlx::query!(
+        r#"SELECT service_id, geometry as "geometry: Json<ServiceArea>" FROM service_areas
+        WHERE $1 BETWEEN min_latitude AND max_latitude
+        AND $2 BETWEEN min_longitude AND max_longitude"#,
+        latitude,
+        longitude
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(candidates
+        .into_iter()
+        .filter(|candidate| covers(&candidate.geometry, latitude, longitude))
+        .map(|candidate| candidate.service_id)
+        .collect())
+}
+
+#[derive(Default)]
+pub struct ServiceAreaRepository {}
+
+impl ServiceAreaRepository {
+    pub fn new() -> Self {
+        ServiceAreaRepository {}
+    }
+
+    /// Returns the areas of the service in the order they were saved
+    #[tracing::instrument(name = "service_area.get_areas", skip_all)]
+    pub async fn get_areas(
+        &self,
+        service_id: Uuid,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceArea>, sqlx::Error> {
+        let _timer = metrics::query_timer("service_area", "get_areas");
+
+        let areas = sqlx::query_scalar!(
+            r#"SELECT geometry as "geometry: Json<ServiceArea>" FROM service_areas
+            WHERE service_id = $1
+            ORDER BY created_at, id"#,
+            service_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(areas.into_iter().map(|area| area.0).collect())
+    }
+
+    /// Replaces the areas of a home-visit service. Every geometry is checked before any is
+    /// saved
+    #[tracing::instrument(name = "service_area.set_areas", skip_all)]
+    pub async fn set_areas(
+        &self,
+        service_id: Uuid,
+        areas: Vec<ServiceArea>,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceArea>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("service_area", "set_areas");
+
+        if areas.len() > MAX_AREAS_PER_SERVICE {
+            return Err(Box::new(InvalidInput::new(
+                "A SERVICE CAN HAVE UP TO 10 AREAS",
+            )));
+        }
+
+        let bounds = areas
+            .iter()
+            .map(validate)
+            .collect::<Result<Vec<Bounds>, InvalidInput>>()?;
+
+        let mut tx = conn.begin().await?;
+
+        let modality = sqlx::query_scalar!(
+            r#"SELECT modality as "modality: Modality" FROM services WHERE id = $1 FOR UPDATE"#,
+            service_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match modality {
+            None => return Err(Box::new(sqlx::Error::RowNotFound)),
+            Some(Modality::Presencial) => {
+                return Err(Box::new(InvalidInput::new(
+                    "ONLY HOME VISIT SERVICES CAN HAVE AREAS",
+                )))
+            }
+            Some(_) => {}
+        }
+
+        sqlx::query!(
+            "DELETE FROM service_areas WHERE service_id = $1",
+            service_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (area, bounds) in areas.iter().zip(bounds) {
+            sqlx::query!(
+                r#"INSERT INTO service_areas
+                (service_id, geometry, min_latitude, max_latitude, min_longitude, max_longitude)
+                VALUES ($1, $2, $3, $4, $5, $6)"#,
+                service_id,
+                Json(area) as _,
+                bounds.min_latitude,
+                bounds.max_latitude,
+                bounds.min_longitude,
+                bounds.max_longitude
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(areas)
+    }
+
+    /// Returns the services of the active sellers with an area that covers the point, ordered
+    /// by id
+    #[tracing::instrument(name = "service_area.covering_services", skip_all)]
+    pub async fn covering_services(
+        &self,
+        request: PointRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("service_area", "covering_services");
+
+        if !valid_coordinates(request.latitude, request.longitude) {
+            return Err(Box::new(InvalidInput::new(
+                "THE COORDINATES ARE OUT OF RANGE",
+            )));
+        }
+
+        let mut conn = conn.acquire().await?;
+
+        let ids: Vec<Uuid> = covering(request.latitude, request.longitude, &mut conn)
+            .await?
+            .into_iter()
+            .collect();
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"SELECT s.id, s.user_id, s.category_id, s.price, s.currency, s.description, s.modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services f WHERE f.service_id = s.id) as "favorites_count!", s.version FROM services s
+            JOIN users u ON u.dni = s.user_id
+            WHERE s.id = ANY($1) AND u.is_seller AND u.seller_paused_at IS NULL
+            ORDER BY s.id"#,
+            &ids
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(positions: &[(f64, f64)]) -> Vec<Vec<f64>> {
+        positions
+            .iter()
+            .map(|&(longitude, latitude)| vec![longitude, latitude])
+            .collect()
+    }
+
+    /// Square of the given side in degrees with its south-west corner at the position
+    fn square(longitude: f64, latitude: f64, side: f64) -> Vec<Vec<f64>> {
+        ring(&[
+            (longitude, latitude),
+            (longitude + side, latitude),
+            (longitude + side, latitude + side),
+            (longitude, latitude + side),
+            (longitude, latitude),
+        ])
+    }
+
+    fn polygon(rings: Vec<Vec<Vec<f64>>>) -> ServiceArea {
+        ServiceArea::Polygon { coordinates: rings }
+    }
+
+    fn circle(longitude: f64, latitude: f64, radius_meters: f64) -> ServiceArea {
+        ServiceArea::Circle {
+            center: vec![longitude, latitude],
+            radius_meters,
+        }
+    }
+
+    fn error(area: &ServiceArea) -> String {
+        match validate(area) {
+            Ok(_) => panic!("the area is valid"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn simple_polygons_are_valid() {
+        let bounds = validate(&polygon(vec![square(-74.1, 4.6, 0.1)])).unwrap();
+
+        assert_eq!(bounds.min_longitude, -74.1);
+        assert_eq!(bounds.min_latitude, 4.6);
+        assert!((bounds.max_longitude - -74.0).abs() < 1e-9);
+        assert!((bounds.max_latitude - 4.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn polygons_need_a_boundary() {
+        assert_eq!(
+            error(&polygon(Vec::new())),
+            "A POLYGON NEEDS AT LEAST ITS BOUNDARY RING"
+        );
+        assert_eq!(
+            error(&polygon(vec![Vec::new()])),
+            "A RING MUST HAVE BETWEEN 4 AND 1000 POSITIONS"
+        );
+    }
+
+    #[test]
+    fn rings_need_four_positions() {
+        let triangle = ring(&[(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)]);
+
+        assert_eq!(
+            error(&polygon(vec![triangle])),
+            "A RING MUST HAVE BETWEEN 4 AND 1000 POSITIONS"
+        );
+    }
+
+    #[test]
+    fn rings_must_be_closed() {
+        let open = ring(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+
+        assert_eq!(
+            error(&polygon(vec![open])),
+            "RINGS MUST END AT THEIR FIRST POSITION"
+        );
+    }
+
+    #[test]
+    fn collinear_rings_have_no_area() {
+        let line = ring(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 0.0)]);
+
+        assert_eq!(error(&polygon(vec![line])), "RINGS MUST ENCLOSE AN AREA");
+    }
+
+    #[test]
+    fn rings_cant_cross_themselves() {
+        // A lopsided bow tie, so its area isn't zero. The second and fourth edges cross
+        let bow_tie = ring(&[(0.0, 0.0), (2.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 0.0)]);
+
+        assert_eq!(
+            error(&polygon(vec![bow_tie])),
+            "RINGS CAN'T CROSS THEMSELVES"
+        );
+    }
+
+    #[test]
+    fn holes_are_validated_too() {
+        let open_hole = ring(&[(0.2, 0.2), (0.4, 0.2), (0.4, 0.4), (0.2, 0.4)]);
+
+        assert_eq!(
+            error(&polygon(vec![square(0.0, 0.0, 1.0), open_hole])),
+            "RINGS MUST END AT THEIR FIRST POSITION"
+        );
+    }
+
+    #[test]
+    fn positions_must_be_on_the_earth() {
+        assert_eq!(
+            error(&polygon(vec![square(179.5, 0.0, 1.0)])),
+            "POSITIONS MUST BE [longitude, latitude] ON THE EARTH"
+        );
+        assert_eq!(
+            error(&circle(-74.0, 91.0, 1000.0)),
+            "POSITIONS MUST BE [longitude, latitude] ON THE EARTH"
+        );
+    }
+
+    #[test]
+    fn radius_must_be_positive_and_up_to_200_km() {
+        let message = "THE RADIUS MUST BE GREATER THAN 0 AND UP TO 200000 METERS";
+
+        assert_eq!(error(&circle(-74.0, 4.6, 0.0)), message);
+        assert_eq!(error(&circle(-74.0, 4.6, -1.0)), message);
+        assert_eq!(error(&circle(-74.0, 4.6, 200_001.0)), message);
+        assert_eq!(error(&circle(-74.0, 4.6, f64::NAN)), message);
+        assert!(validate(&circle(-74.0, 4.6, 200_000.0)).is_ok());
+    }
+
+    #[test]
+    fn circle_bounds_contain_the_circle() {
+        let bounds = validate(&circle(-74.0, 4.6, 10_000.0)).unwrap();
+
+        assert!(bounds.min_latitude < 4.6 - 0.089 && 4.6 + 0.089 < bounds.max_latitude);
+        assert!(bounds.min_longitude < -74.0 - 0.089 && -74.0 + 0.089 < bounds.max_longitude);
+    }
+
+    #[test]
+    fn polygons_cover_the_points_inside_their_boundary() {
+        let area = polygon(vec![square(-74.1, 4.6, 0.1)]);
+
+        assert!(covers(&area, 4.65, -74.05));
+        assert!(!covers(&area, 4.75, -74.05));
+        assert!(!covers(&area, 4.65, -73.95));
+    }
+
+    #[test]
+    fn polygons_dont_cover_their_holes() {
+        let area = polygon(vec![square(0.0, 0.0, 1.0), square(0.25, 0.25, 0.5)]);
+
+        assert!(!covers(&area, 0.5, 0.5));
+        assert!(covers(&area, 0.1, 0.1));
+        assert!(covers(&area, 0.9, 0.5));
+    }
+
+    #[test]
+    fn concave_polygons_dont_cover_their_notch() {
+        // A U open to the north
+        let u = ring(&[
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (2.0, 3.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 3.0),
+            (0.0, 3.0),
+            (0.0, 0.0),
+        ]);
+        let area = polygon(vec![u]);
+
+        assert!(validate(&area).is_ok());
+        assert!(!covers(&area, 2.0, 1.5));
+        assert!(covers(&area, 2.0, 0.5));
+        assert!(covers(&area, 2.0, 2.5));
+    }
+
+    #[test]
+    fn empty_polygons_cover_nothing() {
+        assert!(!covers(&polygon(Vec::new()), 0.0, 0.0));
+    }
+
+    #[test]
+    fn circles_cover_the_points_within_the_radius() {
+        let area = circle(-74.0, 4.6, 1000.0);
+        // A degree of latitude is about 111 km
+        let step = 1.0 / 111.0;
+
+        assert!(covers(&area, 4.6, -74.0));
+        assert!(covers(&area, 4.6 + 0.9 * step, -74.0));
+        assert!(!covers(&area, 4.6 + 1.1 * step, -74.0));
+    }
+}
@@ -0,0 +1,121 @@
+use std::future::Future;
+
+use online_market_model::{AuditAction, AuditEntry};
+use serde::Deserialize;
+use sqlx::{postgres::PgPoolOptions, PgConnection, PgPool};
+use utoipa::IntoParams;
+
+use crate::{metrics, Pagination, PaginationRequest};
+
+/// Who is making the changes of the current task and in which request
+#[derive(Clone, Debug, Default)]
+pub struct AuditContext {
+    /// Dni of the authenticated user
+    pub actor: Option<String>,
+    pub request_id: Option<String>,
+}
+
+tokio::task_local! {
+    static AUDIT_CONTEXT: AuditContext;
+}
+
+/// Runs the future with the given audit context. The changes it makes through a pool
+/// configured with [`track_context`] are recorded with the actor and request id of the context
+pub async fn scope<F: Future>(context: AuditContext, future: F) -> F::Output {
+    AUDIT_CONTEXT.scope(context, future).await
+}
+
+/// Returns the audit context of the current task, an empty one outside of [`scope`]
+pub fn current() -> AuditContext {
+    AUDIT_CONTEXT
+        .try_with(|context| context.clone())
+        .unwrap_or_default()
+}
+
+/// Copies the audit context of the task to the audit.actor and audit.request_id settings
+/// of the connection, which the audit triggers read
+async fn apply_context(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    let context = current();
+
+    sqlx::query!(
+        r#"SELECT set_config('audit.actor', $1, false) as actor,
+        set_config('audit.request_id', $2, false) as request_id"#,
+        context.actor.unwrap_or_default(),
+        context.request_id.unwrap_or_default()
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Makes every connection given out by the pool carry the audit context of the task that
+/// acquires it
+pub fn track_context(options: PgPoolOptions) -> PgPoolOptions {
+    options
+        .after_connect(|conn, _| Box::pin(apply_context(conn)))
+        .before_acquire(|conn, _| {
+            Box::pin(async move {
+                apply_context(conn).await?;
+
+                Ok(true)
+            })
+        })
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditLogRequest {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Name of the table, for example users or rates
+    pub entity: Option<String>,
+    /// Identifier of the row, with the values of composite keys joined by ':'
+    pub entity_id: Option<String>,
+    /// Dni of the user who made the change
+    pub actor: Option<String>,
+}
+
+#[derive(Default)]
+pub struct AuditRepository {}
+
+impl AuditRepository {
+    pub fn new() -> Self {
+        AuditRepository {}
+    }
+
+    /// Returns the changes that match the filters, the newest first
+    #[tracing::instrument(name = "audit.get", skip_all)]
+    pub async fn get(
+        &self,
+        request: AuditLogRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let _timer = metrics::query_timer("audit", "get");
+
+        let pagination = Pagination::new(PaginationRequest {
+            page: request.page,
+            per_page: request.per_page,
+        });
+
+        let entries = sqlx::query_as!(
+            AuditEntry,
+            r#"SELECT id, occurred_at, actor, action as "action: AuditAction", entity, entity_id,
+            before, after, request_id
+            FROM audit_log
+            WHERE ($1::TEXT IS NULL OR entity = $1)
+            AND ($2::TEXT IS NULL OR entity_id = $2)
+            AND ($3::TEXT IS NULL OR actor = $3)
+            ORDER BY id DESC
+            LIMIT $4 OFFSET $5"#,
+            request.entity,
+            request.entity_id,
+            request.actor,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(entries)
+    }
+}
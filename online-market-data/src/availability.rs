@@ -0,0 +1,563 @@
+use std::{collections::HashSet, error::Error};
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use online_market_model::{
+    AvailabilityException, AvailabilityResponse, AvailabilityWindow, TimeSlot, Weekday,
+    WeeklySchedule,
+};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgPool};
+use utoipa::IntoParams;
+
+use crate::{
+    errors::{Conflict, InvalidInput},
+    metrics,
+};
+
+/// Longest range of dates the free slots can be asked for
+const MAX_SLOT_RANGE_DAYS: i64 = 31;
+const DEFAULT_SLOT_MINUTES: i64 = 60;
+const MIN_SLOT_MINUTES: i64 = 15;
+const MAX_SLOT_MINUTES: i64 = 720;
+
+#[derive(Deserialize, IntoParams)]
+pub struct SlotsRequest {
+    /// First date of the range, in the timezone of the seller
+    pub from: NaiveDate,
+    /// Last date of the range, included
+    pub to: NaiveDate,
+    /// Length of the slots in minutes, 60 by default
+    pub duration_minutes: Option<i64>,
+}
+
+/// Schedule of a seller ready to be checked against instants of time
+pub(crate) struct Schedule {
+    timezone: Tz,
+    windows: Vec<AvailabilityWindow>,
+    exceptions: HashSet<NaiveDate>,
+}
+
+impl Schedule {
+    /// Returns the windows of the given local date converted to UTC, joining the ones
+    /// that touch each other. Windows with a bound that doesn't exist because of a DST
+    /// change are skipped
+    fn windows_on(&self, date: NaiveDate) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        if self.exceptions.contains(&date) {
+            return Vec::new();
+        }
+
+        let weekday = Weekday::from(chrono::Datelike::weekday(&date));
+
+        let mut windows: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+
+        // The windows are sorted by start time
+        for window in self
+            .windows
+            .iter()
+            .filter(|window| window.weekday == weekday)
+        {
+            let (Some(start), Some(end)) = (
+                self.to_utc(date, window.start_time),
+                self.to_utc(date, window.end_time),
+            ) else {
+                continue;
+            };
+
+            match windows.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => windows.push((start, end)),
+            }
+        }
+
+        windows
+    }
+
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        self.timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|instant| instant.with_timezone(&Utc))
+    }
+
+    /// Returns true if the range is inside one of the windows of the seller
+    pub(crate) fn contains(&self, starts_at: DateTime<Utc>, ends_at: DateTime<Utc>) -> bool {
+        let date = starts_at.with_timezone(&self.timezone).date_naive();
+
+        self.windows_on(date)
+            .iter()
+            .any(|(start, end)| *start <= starts_at && ends_at <= *end)
+    }
+
+    /// Returns the slots of the given duration between the dates, both included, that start
+    /// after now and don't overlap the booked ranges
+    fn free_slots(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        duration: Duration,
+        booked: &[(DateTime<Utc>, DateTime<Utc>)],
+        now: DateTime<Utc>,
+    ) -> Vec<TimeSlot> {
+        let mut slots = Vec::new();
+
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            for (window_start, window_end) in self.windows_on(date) {
+                let mut starts_at = window_start;
+
+                while starts_at + duration <= window_end {
+                    let ends_at = starts_at + duration;
+
+                    let overlaps = booked.iter().any(|(booked_start, booked_end)| {
+                        *booked_start < ends_at && starts_at < *booked_end
+                    });
+
+                    if starts_at > now && !overlaps {
+                        slots.push(TimeSlot { starts_at, ends_at });
+                    }
+
+                    starts_at = ends_at;
+                }
+            }
+        }
+
+        slots.sort_by_key(|slot| slot.starts_at);
+
+        slots
+    }
+}
+
+fn parse_timezone(timezone: &str) -> Result<Tz, InvalidInput> {
+    timezone
+        .parse::<Tz>()
+        .map_err(|_| InvalidInput::new("TIMEZONE MUST BE AN IANA TIMEZONE"))
+}
+
+/// Checks that every window ends after it starts and that windows of the same day don't overlap
+fn validate_windows(windows: &[AvailabilityWindow]) -> Result<(), InvalidInput> {
+    if windows
+        .iter()
+        .any(|window| window.start_time >= window.end_time)
+    {
+        return Err(InvalidInput::new("WINDOWS MUST START BEFORE THEY END"));
+    }
+
+    let mut sorted: Vec<&AvailabilityWindow> = windows.iter().collect();
+    sorted.sort_by_key(|window| (window.weekday as u8, window.start_time));
+
+    // Windows can touch each other, like 08:00-12:00 and 12:00-14:00
+    let overlaps = sorted
+        .windows(2)
+        .any(|pair| pair[0].weekday == pair[1].weekday && pair[1].start_time < pair[0].end_time);
+
+    if overlaps {
+        return Err(InvalidInput::new("AVAILABILITY WINDOWS CAN'T OVERLAP"));
+    }
+
+    Ok(())
+}
+
+/// Returns the schedule of a seller, RowNotFound when the seller has no schedule
+pub(crate) async fn load_schedule(
+    dni: &str,
+    conn: &mut PgConnection,
+) -> Result<Schedule, Box<dyn Error + Send + Sync>> {
+    let availability = fetch_availability(dni, conn).await?;
+
+    Ok(Schedule {
+        timezone: parse_timezone(&availability.timezone)?,
+        windows: availability.windows,
+        exceptions: availability
+            .exceptions
+            .into_iter()
+            .map(|exception| exception.date)
+            .collect(),
+    })
+}
+
+async fn fetch_availability(
+    dni: &str,
+    conn: &mut PgConnection,
+) -> Result<AvailabilityResponse, sqlx::Error> {
+    let timezone = sqlx::query_scalar!(
+        r#"SELECT timezone FROM seller_schedules WHERE user_id = $1"#,
+        dni
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let windows = sqlx::query_as!(
+        AvailabilityWindow,
+        r#"SELECT weekday as "weekday: Weekday", start_time, end_time FROM availability_windows
+        WHERE user_id = $1 ORDER BY weekday, start_time"#,
+        dni
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    // Past exceptions don't affect the availability anymore
+    let exceptions = sqlx::query_as!(
+        AvailabilityException,
+        r#"SELECT date, reason FROM availability_exceptions
+        WHERE user_id = $1 AND date >= CURRENT_DATE - 1 ORDER BY date"#,
+        dni
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(AvailabilityResponse {
+        seller_id: dni.to_string(),
+        timezone,
+        windows,
+        exceptions,
+    })
+}
+
+/// Returns an error unless the user exists and is a seller
+async fn check_seller(
+    dni: &str,
+    conn: &mut PgConnection,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let is_seller = sqlx::query_scalar!(r#"SELECT is_seller FROM users WHERE dni = $1"#, dni)
+        .fetch_optional(conn)
+        .await?;
+
+    match is_seller {
+        None => Err(Box::new(sqlx::Error::RowNotFound)),
+        Some(false) => Err(Box::new(Conflict::new("USER IS NOT A SELLER"))),
+        Some(true) => Ok(()),
+    }
+}
+
+#[derive(Default)]
+pub struct AvailabilityRepository {}
+
+impl AvailabilityRepository {
+    pub fn new() -> Self {
+        AvailabilityRepository {}
+    }
+
+    #[tracing::instrument(name = "availability.get", skip_all)]
+    pub async fn get(
+        &self,
+        dni: String,
+        conn: &PgPool,
+    ) -> Result<AvailabilityResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("availability", "get");
+
+        let mut conn = conn.acquire().await?;
+
+        fetch_availability(&dni, &mut conn).await
+    }
+
+    /// Replaces the weekly schedule of a seller
+    #[tracing::instrument(name = "availability.set_schedule", skip_all)]
+    pub async fn set_schedule(
+        &self,
+        dni: String,
+        schedule: WeeklySchedule,
+        conn: &PgPool,
+    ) -> Result<AvailabilityResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("availability", "set_schedule");
+
+        parse_timezone(&schedule.timezone)?;
+        validate_windows(&schedule.windows)?;
+
+        let mut tx = conn.begin().await?;
+
+        check_seller(&dni, &mut tx).await?;
+
+        sqlx::query!(
+            r#"INSERT INTO seller_schedules (user_id, timezone) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET timezone = $2, updated_at = now()"#,
+            dni.clone(),
+            schedule.timezone
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"DELETE FROM availability_windows WHERE user_id = $1"#,
+            dni.clone()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for window in schedule.windows {
+            sqlx::query!(
+                r#"INSERT INTO availability_windows (user_id, weekday, start_time, end_time)
+                VALUES ($1, $2, $3, $4)"#,
+                dni.clone(),
+                window.weekday as Weekday,
+                window.start_time,
+                window.end_time
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let availability = fetch_availability(&dni, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(availability)
+    }
+
+    /// Marks a date as not available, replacing the reason if it was already marked
+    #[tracing::instrument(name = "availability.add_exception", skip_all)]
+    pub async fn add_exception(
+        &self,
+        dni: String,
+        exception: AvailabilityException,
+        conn: &PgPool,
+    ) -> Result<AvailabilityResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("availability", "add_exception");
+
+        let mut conn = conn.acquire().await?;
+
+        check_seller(&dni, &mut conn).await?;
+
+        sqlx::query!(
+            r#"INSERT INTO availability_exceptions (user_id, date, reason) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, date) DO UPDATE SET reason = $3"#,
+            dni.clone(),
+            exception.date,
+            exception.reason
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(fetch_availability(&dni, &mut conn).await?)
+    }
+
+    #[tracing::instrument(name = "availability.remove_exception", skip_all)]
+    pub async fn remove_exception(
+        &self,
+        dni: String,
+        date: NaiveDate,
+        conn: &PgPool,
+    ) -> Result<AvailabilityResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("availability", "remove_exception");
+
+        let mut conn = conn.acquire().await?;
+
+        let result = sqlx::query!(
+            r#"DELETE FROM availability_exceptions WHERE user_id = $1 AND date = $2"#,
+            dni.clone(),
+            date
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Box::new(sqlx::Error::RowNotFound));
+        }
+
+        Ok(fetch_availability(&dni, &mut conn).await?)
+    }
+
+    /// Returns the slots of the seller in the range of dates that are in the future
+    /// and don't overlap pending or confirmed bookings
+    #[tracing::instrument(name = "availability.free_slots", skip_all)]
+    pub async fn free_slots(
+        &self,
+        dni: String,
+        request: SlotsRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<TimeSlot>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("availability", "free_slots");
+
+        if request.to < request.from {
+            return Err(Box::new(InvalidInput::new("from MUST BE BEFORE to")));
+        }
+        if (request.to - request.from).num_days() >= MAX_SLOT_RANGE_DAYS {
+            return Err(Box::new(InvalidInput::new(
+                "THE RANGE OF DATES CAN'T BE LONGER THAN 31 DAYS",
+            )));
+        }
+
+        let minutes = request.duration_minutes.unwrap_or(DEFAULT_SLOT_MINUTES);
+        if !(MIN_SLOT_MINUTES..=MAX_SLOT_MINUTES).contains(&minutes) {
+            return Err(Box::new(InvalidInput::new(
+                "duration_minutes MUST BE BETWEEN 15 AND 720",
+            )));
+        }
+        let duration = Duration::minutes(minutes);
+
+        let mut conn = conn.acquire().await?;
+
+        let schedule = load_schedule(&dni, &mut conn).await?;
+
+        // A day of margin covers any timezone offset
+        let range_start = request.from.and_time(NaiveTime::MIN).and_utc() - Duration::days(1);
+        let range_end = request.to.and_time(NaiveTime::MIN).and_utc() + Duration::days(2);
+
+        let bookings = sqlx::query!(
+            r#"SELECT starts_at, ends_at FROM bookings
+            WHERE seller_id = $1 AND status IN ('pending', 'confirmed')
+            AND starts_at < $3 AND ends_at > $2"#,
+            dni,
+            range_start,
+            range_end
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let booked: Vec<(DateTime<Utc>, DateTime<Utc>)> = bookings
+            .into_iter()
+            .map(|booking| (booking.starts_at, booking.ends_at))
+            .collect();
+
+        Ok(schedule.free_slots(request.from, request.to, duration, &booked, Utc::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2030-01-07 is a Monday, Bogota is UTC-5 all year
+    const MONDAY: NaiveDate = match NaiveDate::from_ymd_opt(2030, 1, 7) {
+        Some(date) => date,
+        None => unreachable!(),
+    };
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn window(weekday: Weekday, start: u32, end: u32) -> AvailabilityWindow {
+        AvailabilityWindow {
+            weekday,
+            start_time: time(start),
+            end_time: time(end),
+        }
+    }
+
+    fn schedule(windows: Vec<AvailabilityWindow>) -> Schedule {
+        Schedule {
+            timezone: chrono_tz::America::Bogota,
+            windows,
+            exceptions: HashSet::new(),
+        }
+    }
+
+    /// Instant of the local hour of the date in Bogota
+    fn at(date: NaiveDate, hour: u32) -> DateTime<Utc> {
+        date.and_time(time(hour)).and_utc() + Duration::hours(5)
+    }
+
+    fn starts(slots: &[TimeSlot]) -> Vec<DateTime<Utc>> {
+        slots.iter().map(|slot| slot.starts_at).collect()
+    }
+
+    fn long_ago() -> DateTime<Utc> {
+        at(MONDAY, 0) - Duration::days(365)
+    }
+
+    #[test]
+    fn slots_fill_the_window_exactly() {
+        let schedule = schedule(vec![window(Weekday::Monday, 8, 11)]);
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::hours(1), &[], long_ago());
+
+        assert_eq!(
+            starts(&slots),
+            vec![at(MONDAY, 8), at(MONDAY, 9), at(MONDAY, 10)]
+        );
+        assert_eq!(slots.last().unwrap().ends_at, at(MONDAY, 11));
+    }
+
+    #[test]
+    fn slots_that_dont_fit_the_window_are_left_out() {
+        let schedule = schedule(vec![window(Weekday::Monday, 8, 11)]);
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::hours(2), &[], long_ago());
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 8)]);
+    }
+
+    #[test]
+    fn touching_windows_are_joined() {
+        let schedule = schedule(vec![
+            window(Weekday::Monday, 8, 9),
+            window(Weekday::Monday, 9, 10),
+        ]);
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::minutes(90), &[], long_ago());
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 8)]);
+        assert!(schedule.contains(at(MONDAY, 8), at(MONDAY, 10)));
+    }
+
+    #[test]
+    fn bookings_on_the_boundary_dont_take_the_next_slot() {
+        let schedule = schedule(vec![window(Weekday::Monday, 8, 11)]);
+        let booked = [(at(MONDAY, 8), at(MONDAY, 9))];
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::hours(1), &booked, long_ago());
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 9), at(MONDAY, 10)]);
+    }
+
+    #[test]
+    fn overlapping_bookings_take_every_slot_they_touch() {
+        let schedule = schedule(vec![window(Weekday::Monday, 8, 11)]);
+        let booked = [(
+            at(MONDAY, 8) + Duration::minutes(30),
+            at(MONDAY, 9) + Duration::minutes(30),
+        )];
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::hours(1), &booked, long_ago());
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 10)]);
+    }
+
+    #[test]
+    fn exceptions_and_other_weekdays_have_no_slots() {
+        let mut schedule = schedule(vec![window(Weekday::Monday, 8, 9)]);
+        let next_monday = MONDAY + Duration::days(7);
+        schedule.exceptions.insert(MONDAY);
+
+        let slots = schedule.free_slots(MONDAY, next_monday, Duration::hours(1), &[], long_ago());
+
+        assert_eq!(starts(&slots), vec![at(next_monday, 8)]);
+    }
+
+    #[test]
+    fn slots_that_already_started_are_left_out() {
+        let schedule = schedule(vec![window(Weekday::Monday, 8, 11)]);
+
+        let slots = schedule.free_slots(MONDAY, MONDAY, Duration::hours(1), &[], at(MONDAY, 9));
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 10)]);
+    }
+
+    #[test]
+    fn slots_are_sorted_across_days() {
+        let schedule = schedule(vec![
+            window(Weekday::Tuesday, 8, 9),
+            window(Weekday::Monday, 14, 15),
+        ]);
+        let tuesday = MONDAY + Duration::days(1);
+
+        let slots = schedule.free_slots(MONDAY, tuesday, Duration::hours(1), &[], long_ago());
+
+        assert_eq!(starts(&slots), vec![at(MONDAY, 14), at(tuesday, 8)]);
+    }
+
+    #[test]
+    fn overlapping_windows_are_rejected() {
+        assert!(validate_windows(&[
+            window(Weekday::Monday, 8, 12),
+            window(Weekday::Monday, 12, 14),
+        ])
+        .is_ok());
+        assert!(validate_windows(&[
+            window(Weekday::Monday, 8, 12),
+            window(Weekday::Monday, 11, 14),
+        ])
+        .is_err());
+        assert!(validate_windows(&[window(Weekday::Monday, 12, 12)]).is_err());
+    }
+}
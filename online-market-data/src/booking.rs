@@ -0,0 +1,190 @@
+use std::error::Error;
+
+use chrono::Utc;
+use online_market_model::{Booking, BookingResponse, BookingStatus};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    availability::load_schedule,
+    errors::{Conflict, InvalidInput},
+    metrics,
+};
+
+/// Returns true if a booking can go from the current status to the next one.
+/// Cancelling is allowed to both parties, the rest of the changes only to the seller
+pub fn allowed_transition(current: BookingStatus, next: BookingStatus, as_seller: bool) -> bool {
+    match (current, next) {
+        (BookingStatus::Pending, BookingStatus::Confirmed) => as_seller,
+        (BookingStatus::Confirmed, BookingStatus::Completed) => as_seller,
+        (BookingStatus::Pending | BookingStatus::Confirmed, BookingStatus::Cancelled) => true,
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+pub struct BookingRepository {}
+
+impl BookingRepository {
+    pub fn new() -> Self {
+        BookingRepository {}
+    }
+
+    /// Books a service for the customer, as long as the time is inside the availability
+    /// of the seller and doesn't overlap another active booking
+    #[tracing::instrument(name = "booking.save", skip_all)]
+    pub async fn save(
+        &self,
+        customer: String,
+        booking: Booking,
+        conn: &PgPool,
+    ) -> Result<BookingResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("booking", "save");
+
+        if booking.starts_at >= booking.ends_at {
+            return Err(Box::new(InvalidInput::new(
+                "THE BOOKING MUST START BEFORE IT ENDS",
+            )));
+        }
+        if booking.starts_at <= Utc::now() {
+            return Err(Box::new(InvalidInput::new(
+                "THE BOOKING MUST START IN THE FUTURE",
+            )));
+        }
+
+        let mut tx = conn.begin().await?;
+
+        let seller = sqlx::query_scalar!(
+            r#"SELECT user_id FROM services WHERE id = $1"#,
+            booking.service_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if seller == customer {
+            return Err(Box::new(Conflict::new("YOU CAN'T BOOK YOUR OWN SERVICE")));
+        }
+
+        // Lock the seller so two bookings can't take the same slot
+        let paused_at = sqlx::query_scalar!(
+            r#"SELECT seller_paused_at FROM users WHERE dni = $1 FOR UPDATE"#,
+            seller.clone()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if paused_at.is_some() {
+            return Err(Box::new(Conflict::new("THE SELLER IS PAUSED")));
+        }
+
+        let schedule = match load_schedule(&seller, &mut tx).await {
+            Ok(schedule) => schedule,
+            Err(error) if matches!(error.downcast_ref(), Some(sqlx::Error::RowNotFound)) => {
+                return Err(Box::new(Conflict::new("THE SELLER HAS NO AVAILABILITY")));
+            }
+            Err(error) => return Err(error),
+        };
+
+        if !schedule.contains(booking.starts_at, booking.ends_at) {
+            return Err(Box::new(Conflict::new(
+                "THE BOOKING IS OUTSIDE THE AVAILABILITY OF THE SELLER",
+            )));
+        }
+
+        let taken = sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM bookings
+                WHERE seller_id = $1 AND status IN ('pending', 'confirmed')
+                AND starts_at < $3 AND ends_at > $2
+            ) as "taken!""#,
+            seller.clone(),
+            booking.starts_at,
+            booking.ends_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if taken {
+            return Err(Box::new(Conflict::new("THE SLOT IS ALREADY BOOKED")));
+        }
+
+        let booking = sqlx::query_as!(
+            BookingResponse,
+            r#"INSERT INTO bookings (service_id, customer_id, seller_id, starts_at, ends_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, service_id, customer_id, seller_id, starts_at, ends_at, status as "status: BookingStatus", created_at"#,
+            booking.service_id,
+            customer,
+            seller,
+            booking.starts_at,
+            booking.ends_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(booking)
+    }
+
+    #[tracing::instrument(name = "booking.get_by_id", skip_all)]
+    pub async fn get_by_id(&self, id: Uuid, conn: &PgPool) -> Result<BookingResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("booking", "get_by_id");
+
+        let booking = sqlx::query_as!(
+            BookingResponse,
+            r#"SELECT id, service_id, customer_id, seller_id, starts_at, ends_at, status as "status: BookingStatus", created_at
+            FROM bookings WHERE id = $1"#,
+            id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(booking)
+    }
+
+    /// Changes the status of a booking if the transition is allowed from its current status
+    #[tracing::instrument(name = "booking.update_status", skip_all)]
+    pub async fn update_status(
+        &self,
+        id: Uuid,
+        status: BookingStatus,
+        as_seller: bool,
+        conn: &PgPool,
+    ) -> Result<BookingResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("booking", "update_status");
+
+        let mut tx = conn.begin().await?;
+
+        let current = sqlx::query_scalar!(
+            r#"SELECT status as "status: BookingStatus" FROM bookings WHERE id = $1 FOR UPDATE"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !allowed_transition(current, status, as_seller) {
+            return Err(Box::new(Conflict::new(
+                &format!(
+                    "THE BOOKING CAN'T CHANGE FROM {:?} TO {:?}",
+                    current, status
+                )
+                .to_uppercase(),
+            )));
+        }
+
+        let booking = sqlx::query_as!(
+            BookingResponse,
+            r#"UPDATE bookings SET status = $1 WHERE id = $2
+            RETURNING id, service_id, customer_id, seller_id, starts_at, ends_at, status as "status: BookingStatus", created_at"#,
+            status as BookingStatus,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(booking)
+    }
+}
@@ -0,0 +1,523 @@
+use std::{collections::HashSet, error::Error, str::FromStr};
+
+use futures::future::BoxFuture;
+use online_market_model::{CategoryResponse, Modality, Money, Secret, DEFAULT_CURRENCY};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::{
+    errors::{Conflict, InvalidInput},
+    metrics, password, ServiceRecord,
+};
+
+/// Formats accepted by the import and produced by the export
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BulkFormat {
+    Csv,
+    Ndjson,
+}
+
+impl BulkFormat {
+    /// Returns the format matching a Content-Type header, ignoring its parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        match mime {
+            "text/csv" => Some(BulkFormat::Csv),
+            "application/x-ndjson" | "application/ndjson" => Some(BulkFormat::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BulkFormat::Csv => "text/csv",
+            BulkFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+impl FromStr for BulkFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(BulkFormat::Csv),
+            "ndjson" | "jsonl" => Ok(BulkFormat::Ndjson),
+            _ => Err(format!("UNSUPPORTED FORMAT {}, USE csv OR ndjson", value)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CategoryRow {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UserRow {
+    pub dni: String,
+    pub email: String,
+    pub password: Secret,
+    pub name: String,
+    pub date_of_birth: chrono::NaiveDate,
+    pub contact_number: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ServiceRow {
+    pub user_id: String,
+    pub category_id: i64,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub price: Decimal,
+    /// Files without the currency column use the default currency
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub description: String,
+    pub modality: Modality,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+/// Exported users never include the password
+#[derive(Serialize, Debug)]
+pub struct UserExportRow {
+    pub id: uuid::Uuid,
+    pub dni: String,
+    pub email: String,
+    pub name: String,
+    pub date_of_birth: chrono::NaiveDate,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    pub is_seller: bool,
+    pub contact_number: String,
+    pub category_id: Option<i64>,
+}
+
+/// Problem found in a row. Rows are numbered from 1 and the CSV header is not counted
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub errors: Vec<RowError>,
+}
+
+impl ImportReport {
+    fn rejected(errors: Vec<RowError>) -> Self {
+        ImportReport {
+            inserted: 0,
+            errors,
+        }
+    }
+}
+
+/// Parses every row of the payload, returning the valid rows and the errors of the invalid ones
+fn parse<T: DeserializeOwned>(format: BulkFormat, data: &[u8]) -> (Vec<(usize, T)>, Vec<RowError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    match format {
+        BulkFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(data);
+
+            for (index, record) in reader.deserialize::<T>().enumerate() {
+                match record {
+                    Ok(row) => rows.push((index + 1, row)),
+                    Err(error) => errors.push(RowError {
+                        row: index + 1,
+                        message: error.to_string(),
+                    }),
+                }
+            }
+        }
+        BulkFormat::Ndjson => {
+            let lines = String::from_utf8_lossy(data);
+
+            for (index, line) in lines.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<T>(line) {
+                    Ok(row) => rows.push((index + 1, row)),
+                    Err(error) => errors.push(RowError {
+                        row: index + 1,
+                        message: error.to_string(),
+                    }),
+                }
+            }
+        }
+    }
+
+    (rows, errors)
+}
+
+fn check_length(field: &str, value: &str, max: usize, problems: &mut Vec<String>) {
+    if value.trim().is_empty() {
+        problems.push(format!("{} IS REQUIRED", field));
+    } else if value.chars().count() > max {
+        problems.push(format!("{} MUST HAVE AT MOST {} CHARACTERS", field, max));
+    }
+}
+
+fn push_problems(row: usize, problems: Vec<String>, errors: &mut Vec<RowError>) {
+    if !problems.is_empty() {
+        errors.push(RowError {
+            row,
+            message: problems.join(", "),
+        });
+    }
+}
+
+fn validate_categories(rows: &[(usize, CategoryRow)], errors: &mut Vec<RowError>) {
+    let mut names = HashSet::new();
+
+    for (row, category) in rows {
+        let mut problems = Vec::new();
+
+        check_length("name", &category.name, 50, &mut problems);
+
+        if !names.insert(category.name.to_lowercase()) {
+            problems.push("name IS DUPLICATED IN THE FILE".to_string());
+        }
+
+        push_problems(*row, problems, errors);
+    }
+}
+
+fn validate_users(rows: &[(usize, UserRow)], errors: &mut Vec<RowError>) {
+    let mut dnis = HashSet::new();
+    let mut emails = HashSet::new();
+
+    for (row, user) in rows {
+        let mut problems = Vec::new();
+
+        check_length("dni", &user.dni, 10, &mut problems);
+        check_length("email", &user.email, 50, &mut problems);
+        check_length("password", user.password.expose(), 250, &mut problems);
+        check_length("name", &user.name, 50, &mut problems);
+        check_length("contact_number", &user.contact_number, 10, &mut problems);
+
+        if !user.email.contains('@') {
+            problems.push("email IS NOT VALID".to_string());
+        }
+        if !dnis.insert(user.dni.clone()) {
+            problems.push("dni IS DUPLICATED IN THE FILE".to_string());
+        }
+        if !emails.insert(user.email.to_lowercase()) {
+            problems.push("email IS DUPLICATED IN THE FILE".to_string());
+        }
+
+        push_problems(*row, problems, errors);
+    }
+}
+
+fn validate_services(rows: &[(usize, ServiceRow)], errors: &mut Vec<RowError>) {
+    for (row, service) in rows {
+        let mut problems = Vec::new();
+
+        check_length("user_id", &service.user_id, 10, &mut problems);
+        check_length("description", &service.description, 200, &mut problems);
+
+        if let Err(problem) = Money::new(service.price, service.currency.clone()) {
+            problems.push(problem.to_string());
+        }
+
+        push_problems(*row, problems, errors);
+    }
+}
+
+/// Serializes the rows in the given format
+fn serialize<T: Serialize>(format: BulkFormat, rows: &[T]) -> Result<Vec<u8>, Box<dyn Error>> {
+    match format {
+        BulkFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+
+            for row in rows {
+                writer.serialize(row)?;
+            }
+
+            Ok(writer.into_inner()?)
+        }
+        BulkFormat::Ndjson => {
+            let mut buffer = Vec::new();
+
+            for row in rows {
+                serde_json::to_writer(&mut buffer, row)?;
+                buffer.push(b'\n');
+            }
+
+            Ok(buffer)
+        }
+    }
+}
+
+/// Inserts a single row inside the import transaction. The message of the error is the one
+/// reported for the row
+type InsertRow<T> = for<'t> fn(
+    &'t mut Transaction<'_, Postgres>,
+    &'t T,
+) -> BoxFuture<'t, Result<(), Box<dyn Error + Send + Sync>>>;
+
+#[derive(Default)]
+pub struct BulkRepository {}
+
+impl BulkRepository {
+    pub fn new() -> Self {
+        BulkRepository {}
+    }
+
+    /// Inserts every valid row in a single transaction to collect the database errors too.
+    /// Nothing is committed when at least one row is invalid or fails to insert
+    async fn import<T>(
+        &self,
+        rows: Vec<(usize, T)>,
+        mut errors: Vec<RowError>,
+        conn: &PgPool,
+        insert: InsertRow<T>,
+    ) -> Result<ImportReport, sqlx::Error> {
+        let invalid_rows: HashSet<usize> = errors.iter().map(|error| error.row).collect();
+        let mut tx = conn.begin().await?;
+
+        for (row, value) in rows.iter() {
+            if invalid_rows.contains(row) {
+                continue;
+            }
+
+            // Each row runs in a savepoint so one failure doesn't hide the errors of the next rows
+            let mut savepoint = tx.begin().await?;
+
+            match insert(&mut savepoint, value).await {
+                Ok(_) => savepoint.commit().await?,
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    errors.push(RowError {
+                        row: *row,
+                        message: error.to_string(),
+                    });
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            tx.rollback().await?;
+            errors.sort_by_key(|error| error.row);
+
+            return Ok(ImportReport::rejected(errors));
+        }
+
+        tx.commit().await?;
+
+        Ok(ImportReport {
+            inserted: rows.len(),
+            errors,
+        })
+    }
+
+    #[tracing::instrument(name = "bulk.import_categories", skip_all)]
+    pub async fn import_categories(
+        &self,
+        format: BulkFormat,
+        data: &[u8],
+        conn: &PgPool,
+    ) -> Result<ImportReport, sqlx::Error> {
+        let _timer = metrics::query_timer("bulk", "import_categories");
+
+        let (rows, mut errors) = parse::<CategoryRow>(format, data);
+        validate_categories(&rows, &mut errors);
+
+        self.import(rows, errors, conn, |tx, category| {
+            Box::pin(async move {
+                // The categories have no unique name in the database, so the ones already saved
+                // are reported here instead of being duplicated
+                let exists = sqlx::query_scalar!(
+                    r#"SELECT EXISTS (SELECT 1 FROM categories WHERE lower(name) = lower($1)) as "exists!""#,
+                    category.name.trim()
+                )
+                .fetch_one(&mut **tx)
+                .await?;
+
+                if exists {
+                    return Err(Box::new(Conflict::new("name ALREADY EXISTS")) as _);
+                }
+
+                sqlx::query!(
+                    r#"INSERT INTO categories (name) VALUES ($1)"#,
+                    category.name.trim()
+                )
+                .execute(&mut **tx)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "bulk.import_users", skip_all)]
+    pub async fn import_users(
+        &self,
+        format: BulkFormat,
+        data: &[u8],
+        conn: &PgPool,
+    ) -> Result<ImportReport, sqlx::Error> {
+        let _timer = metrics::query_timer("bulk", "import_users");
+
+        let (rows, mut errors) = parse::<UserRow>(format, data);
+        validate_users(&rows, &mut errors);
+
+        self.import(rows, errors, conn, |tx, user| {
+            Box::pin(async move {
+                let password = password::hash(user.password.expose())?;
+
+                sqlx::query!(
+                    r#"INSERT INTO users (dni, email, password, name, date_of_birth, registered_at, contact_number, rol)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, 'user')"#,
+                    user.dni,
+                    user.email,
+                    password,
+                    user.name,
+                    user.date_of_birth,
+                    chrono::Utc::now(),
+                    user.contact_number
+                )
+                .execute(&mut **tx)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "bulk.import_services", skip_all)]
+    pub async fn import_services(
+        &self,
+        format: BulkFormat,
+        data: &[u8],
+        conn: &PgPool,
+    ) -> Result<ImportReport, sqlx::Error> {
+        let _timer = metrics::query_timer("bulk", "import_services");
+
+        let (rows, mut errors) = parse::<ServiceRow>(format, data);
+        validate_services(&rows, &mut errors);
+
+        self.import(rows, errors, conn, |tx, service| {
+            Box::pin(async move {
+                // The same checks of ServiceRepository::save. The seller is locked so concurrent
+                // requests can't exceed the limit, and the services of the previous rows count
+                let is_seller = sqlx::query_scalar!(
+                    r#"SELECT is_seller FROM users WHERE dni = $1 FOR UPDATE"#,
+                    service.user_id
+                )
+                .fetch_optional(&mut **tx)
+                .await?;
+
+                match is_seller {
+                    None => return Err(Box::new(InvalidInput::new("user_id DOESN'T EXIST")) as _),
+                    Some(false) => return Err(Box::new(Conflict::new("USER IS NOT A SELLER")) as _),
+                    Some(true) => {}
+                }
+
+                let usage = sqlx::query!(
+                    r#"SELECT
+                    (SELECT COUNT(*) FROM services WHERE user_id = $1) as "services!",
+                    (SELECT max_services_per_seller FROM settings) as "limit!"
+                    "#,
+                    service.user_id
+                )
+                .fetch_one(&mut **tx)
+                .await?;
+
+                if usage.services >= usage.limit as i64 {
+                    return Err(Box::new(Conflict::new(
+                        "THE SELLER REACHED THE LIMIT OF SERVICES",
+                    )) as _);
+                }
+
+                sqlx::query!(
+                    r#"INSERT INTO services (user_id, category_id, price, currency, description, modality)
+                    VALUES ($1, $2, $3, $4, $5, $6)"#,
+                    service.user_id,
+                    service.category_id,
+                    service.price,
+                    service.currency,
+                    service.description,
+                    &service.modality as &Modality
+                )
+                .execute(&mut **tx)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "bulk.export_categories", skip_all)]
+    pub async fn export_categories(
+        &self,
+        format: BulkFormat,
+        conn: &PgPool,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let _timer = metrics::query_timer("bulk", "export_categories");
+
+        let categories = sqlx::query_as!(
+            CategoryResponse,
+            r#"SELECT id, name FROM categories ORDER BY id"#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        serialize(format, &categories)
+    }
+
+    #[tracing::instrument(name = "bulk.export_users", skip_all)]
+    pub async fn export_users(
+        &self,
+        format: BulkFormat,
+        conn: &PgPool,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let _timer = metrics::query_timer("bulk", "export_users");
+
+        let users = sqlx::query_as!(
+            UserExportRow,
+            r#"SELECT id, dni, email, name, date_of_birth, registered_at, is_seller, contact_number, category_id
+            FROM users ORDER BY registered_at"#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        serialize(format, &users)
+    }
+
+    #[tracing::instrument(name = "bulk.export_services", skip_all)]
+    pub async fn export_services(
+        &self,
+        format: BulkFormat,
+        conn: &PgPool,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let _timer = metrics::query_timer("bulk", "export_services");
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality",
+            (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version
+            FROM services ORDER BY user_id"#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        serialize(format, &services)
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::metrics::CACHE_REQUESTS_TOTAL;
+
+/// Time the values are kept when nothing invalidates them before. It bounds how long the
+/// changes made by other processes, like the admin tool, take to be seen
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Values kept in memory by a repository, shared by every request of the server.
+///
+/// The repository invalidates the values its writes change. Values loaded while an
+/// invalidation happens aren't stored, so a slow read can't put back an outdated value
+pub struct Cache<K, V> {
+    /// Label of the cache in the metrics
+    name: &'static str,
+    ttl: Duration,
+    entries: RwLock<HashMap<K, (Instant, V)>>,
+    /// Increased by every invalidation
+    generation: AtomicU64,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    pub fn new(name: &'static str, ttl: Duration) -> Self {
+        Cache {
+            name,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached value of the key, loading and storing it when it's missing or expired
+    pub async fn get_or_try_load<E, F, Fut>(&self, key: K, load: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some((stored_at, value)) = self.entries.read().unwrap().get(&key) {
+            if stored_at.elapsed() < self.ttl {
+                CACHE_REQUESTS_TOTAL
+                    .with_label_values(&[self.name, "hit"])
+                    .inc();
+
+                return Ok(value.clone());
+            }
+        }
+
+        CACHE_REQUESTS_TOTAL
+            .with_label_values(&[self.name, "miss"])
+            .inc();
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let value = load().await?;
+
+        let mut entries = self.entries.write().unwrap();
+
+        if self.generation.load(Ordering::SeqCst) == generation {
+            entries.insert(key, (Instant::now(), value.clone()));
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the cached values of the keys, loading the missing and expired ones at once.
+    /// Keys the loader doesn't return are left out
+    pub async fn get_many_or_try_load<E, F, Fut>(
+        &self,
+        keys: &[K],
+        load: F,
+    ) -> Result<HashMap<K, V>, E>
+    where
+        K: Clone,
+        F: FnOnce(Vec<K>) -> Fut,
+        Fut: Future<Output = Result<HashMap<K, V>, E>>,
+    {
+        let mut values = HashMap::with_capacity(keys.len());
+        let mut missing = Vec::new();
+
+        {
+            let entries = self.entries.read().unwrap();
+
+            for key in keys {
+                match entries.get(key) {
+                    Some((stored_at, value)) if stored_at.elapsed() < self.ttl => {
+                        values.insert(key.clone(), value.clone());
+                    }
+                    _ => missing.push(key.clone()),
+                }
+            }
+        }
+
+        CACHE_REQUESTS_TOTAL
+            .with_label_values(&[self.name, "hit"])
+            .inc_by(values.len() as u64);
+        CACHE_REQUESTS_TOTAL
+            .with_label_values(&[self.name, "miss"])
+            .inc_by(missing.len() as u64);
+
+        if missing.is_empty() {
+            return Ok(values);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let loaded = load(missing).await?;
+
+        let mut entries = self.entries.write().unwrap();
+
+        if self.generation.load(Ordering::SeqCst) == generation {
+            for (key, value) in &loaded {
+                entries.insert(key.clone(), (Instant::now(), value.clone()));
+            }
+        }
+
+        values.extend(loaded);
+
+        Ok(values)
+    }
+
+    /// Removes the value of the key
+    pub fn invalidate(&self, key: &K) {
+        let mut entries = self.entries.write().unwrap();
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.remove(key);
+    }
+
+    /// Removes every value
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.clear();
+    }
+}
@@ -24,4 +24,82 @@ impl std::error::Error for NoIdProvided {
     fn description(&self) -> &str {
         &self.details
     }
-}
\ No newline at end of file
+}
+
+/// The operation conflicts with the current state of the resource
+#[derive(Debug)]
+pub struct Conflict {
+    details: String
+}
+
+impl Conflict {
+    pub fn new(message: &str) -> Self {
+        Conflict {
+            details: message.to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl std::error::Error for Conflict {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+/// The data sent doesn't pass the validations of the resource
+#[derive(Debug)]
+pub struct InvalidInput {
+    details: String
+}
+
+impl InvalidInput {
+    pub fn new(message: &str) -> Self {
+        InvalidInput {
+            details: message.to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl std::error::Error for InvalidInput {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+/// The resource changed since the version the client based its update on
+#[derive(Debug)]
+pub struct PreconditionFailed {
+    details: String
+}
+
+impl PreconditionFailed {
+    pub fn new(message: &str) -> Self {
+        PreconditionFailed {
+            details: message.to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for PreconditionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl std::error::Error for PreconditionFailed {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
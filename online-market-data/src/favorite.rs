@@ -0,0 +1,220 @@
+use std::{collections::HashMap, error::Error};
+
+use chrono::{DateTime, Utc};
+use online_market_model::{FavoriteKind, FavoriteResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{errors::Conflict, metrics, Pagination, ServiceRepository};
+
+/// Turns the missing service or seller of a new favorite into a not found error
+fn missing_reference(error: sqlx::Error) -> sqlx::Error {
+    match &error {
+        sqlx::Error::Database(database_error) if database_error.is_foreign_key_violation() => {
+            sqlx::Error::RowNotFound
+        }
+        _ => error,
+    }
+}
+
+#[derive(Default)]
+pub struct FavoriteRepository {}
+
+impl FavoriteRepository {
+    pub fn new() -> Self {
+        FavoriteRepository {}
+    }
+
+    /// Adds a service to the favorites of the user. Adding it again keeps the original date
+    #[tracing::instrument(name = "favorite.add_service", skip_all)]
+    pub async fn add_service(
+        &self,
+        dni: String,
+        service_id: Uuid,
+        conn: &PgPool,
+    ) -> Result<FavoriteResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("favorite", "add_service");
+
+        let created_at = sqlx::query_scalar!(
+            r#"INSERT INTO favorite_services (user_id, service_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, service_id) DO UPDATE SET created_at = favorite_services.created_at
+            RETURNING created_at"#,
+            dni,
+            service_id
+        )
+        .fetch_one(conn)
+        .await
+        .map_err(missing_reference)?;
+
+        let service = ServiceRepository::new().get_by_id(service_id, conn).await?;
+
+        Ok(FavoriteResponse {
+            kind: FavoriteKind::Service,
+            service: Some(service),
+            seller_id: None,
+            created_at,
+        })
+    }
+
+    #[tracing::instrument(name = "favorite.remove_service", skip_all)]
+    pub async fn remove_service(
+        &self,
+        dni: String,
+        service_id: Uuid,
+        conn: &PgPool,
+    ) -> Result<FavoriteResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("favorite", "remove_service");
+
+        let created_at = sqlx::query_scalar!(
+            r#"DELETE FROM favorite_services WHERE user_id = $1 AND service_id = $2
+            RETURNING created_at"#,
+            dni,
+            service_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        let service = ServiceRepository::new().get_by_id(service_id, conn).await?;
+
+        Ok(FavoriteResponse {
+            kind: FavoriteKind::Service,
+            service: Some(service),
+            seller_id: None,
+            created_at,
+        })
+    }
+
+    /// Adds a seller to the favorites of the user. Adding it again keeps the original date
+    #[tracing::instrument(name = "favorite.add_seller", skip_all)]
+    pub async fn add_seller(
+        &self,
+        dni: String,
+        seller: String,
+        conn: &PgPool,
+    ) -> Result<FavoriteResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("favorite", "add_seller");
+
+        if dni == seller {
+            return Err(Box::new(Conflict::new(
+                "YOU CAN'T ADD YOURSELF TO YOUR FAVORITES",
+            )));
+        }
+
+        let is_seller =
+            sqlx::query_scalar!(r#"SELECT is_seller FROM users WHERE dni = $1"#, seller)
+                .fetch_one(conn)
+                .await?;
+
+        if !is_seller {
+            return Err(Box::new(Conflict::new("USER IS NOT A SELLER")));
+        }
+
+        let created_at = sqlx::query_scalar!(
+            r#"INSERT INTO favorite_sellers (user_id, seller_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, seller_id) DO UPDATE SET created_at = favorite_sellers.created_at
+            RETURNING created_at"#,
+            dni,
+            seller.clone()
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(FavoriteResponse {
+            kind: FavoriteKind::Seller,
+            service: None,
+            seller_id: Some(seller),
+            created_at,
+        })
+    }
+
+    #[tracing::instrument(name = "favorite.remove_seller", skip_all)]
+    pub async fn remove_seller(
+        &self,
+        dni: String,
+        seller: String,
+        conn: &PgPool,
+    ) -> Result<FavoriteResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("favorite", "remove_seller");
+
+        let created_at = sqlx::query_scalar!(
+            r#"DELETE FROM favorite_sellers WHERE user_id = $1 AND seller_id = $2
+            RETURNING created_at"#,
+            dni,
+            seller.clone()
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(FavoriteResponse {
+            kind: FavoriteKind::Seller,
+            service: None,
+            seller_id: Some(seller),
+            created_at,
+        })
+    }
+
+    /// Returns the favorite services and sellers of the user, the newest first
+    #[tracing::instrument(name = "favorite.get_by_user", skip_all)]
+    pub async fn get_by_user(
+        &self,
+        dni: String,
+        pagination: Pagination,
+        conn: &PgPool,
+    ) -> Result<Vec<FavoriteResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("favorite", "get_by_user");
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT service_id as "service_id?", NULL::VARCHAR as "seller_id?", created_at as "created_at!"
+            FROM favorite_services WHERE user_id = $1
+            UNION ALL
+            SELECT NULL::UUID, seller_id, created_at
+            FROM favorite_sellers WHERE user_id = $1
+            ORDER BY 3 DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            dni,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        )
+        .fetch_all(conn)
+        .await?;
+
+        if rows.is_empty() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let service_ids: Vec<Uuid> = rows.iter().filter_map(|row| row.service_id).collect();
+
+        let mut services: HashMap<Uuid, _> = ServiceRepository::new()
+            .get_by_ids(&service_ids, conn)
+            .await?
+            .into_iter()
+            .map(|service| (service.id, service))
+            .collect();
+
+        let favorites = rows
+            .into_iter()
+            .map(|row| {
+                let created_at: DateTime<Utc> = row.created_at;
+
+                match row.service_id {
+                    Some(service_id) => FavoriteResponse {
+                        kind: FavoriteKind::Service,
+                        service: services.remove(&service_id),
+                        seller_id: None,
+                        created_at,
+                    },
+                    None => FavoriteResponse {
+                        kind: FavoriteKind::Seller,
+                        service: None,
+                        seller_id: row.seller_id,
+                        created_at,
+                    },
+                }
+            })
+            .collect();
+
+        Ok(favorites)
+    }
+}
@@ -1,17 +1,37 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use chrono::{DateTime, Utc};
 
 use online_market_model::{
-    Category, CategoryResponse, Comment, CommentResponse, Modality, Rate, RateResponse, Roles,
-    Service, ServiceResponse, User, UserResponse, UserLocation,
+    Category, CategoryLabel, CategoryResponse, Comment, CommentReply, CommentResponse, CommentThread,
+    CommentUpdate, Language, LocationVisibility, Modality, Money, Rate, RateResponse, RatingSummary, ReviewVerification, Roles,
+    Secret, SellerOnboarding, SellerOnboardingResponse, SellerSort, SellerSummary, Service, ServiceResponse, Settings,
+    SettingsResponse, User, UserLocation, UserResponse,
 };
-use serde::Deserialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use utoipa::IntoParams;
 use uuid::Uuid;
 
-use errors::NoIdProvided;
+use cache::Cache;
+use errors::{Conflict, InvalidInput, NoIdProvided, PreconditionFailed};
+use reputation::refresh_reputations;
 
-mod errors;
+pub mod area;
+pub mod audit;
+pub mod availability;
+pub mod booking;
+pub mod bulk;
+pub mod cache;
+pub mod errors;
+pub mod favorite;
+pub mod location;
+pub mod metrics;
+pub mod migration;
+pub mod moderation;
+pub mod password;
+pub mod reputation;
 
 #[derive(Deserialize, IntoParams)]
 pub struct PaginationRequest {
@@ -19,6 +39,26 @@ pub struct PaginationRequest {
     pub per_page: Option<i64>,
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct SellerListRequest {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Reputation by default
+    pub sort: Option<SellerSort>,
+    pub category_id: Option<i64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ServiceSearchRequest {
+    /// Latitude of the address of the buyer
+    pub latitude: f64,
+    /// Longitude of the address of the buyer
+    pub longitude: f64,
+    pub category_id: Option<i64>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
 #[derive(Debug)]
 pub struct Pagination {
     pub page: i64,
@@ -34,18 +74,32 @@ impl Pagination {
     }
 }
 
-pub struct CategoryRepository {}
+pub struct CategoryRepository {
+    /// Every category, ordered by id. The catalogue is small and read by every screen
+    catalogue: Cache<(), Arc<Vec<CategoryResponse>>>,
+}
+
+impl Default for CategoryRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl CategoryRepository {
     pub fn new() -> Self {
-        CategoryRepository {}
+        CategoryRepository {
+            catalogue: Cache::new("categories", cache::DEFAULT_TTL),
+        }
     }
 
+    #[tracing::instrument(name = "category.save", skip_all)]
     pub async fn save(
         &self,
         category: Category,
         conn: &PgPool,
     ) -> Result<CategoryResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("category", "save");
+
         // saving it to the database
         let category = sqlx::query_as!(
             CategoryResponse,
@@ -56,19 +110,79 @@ impl CategoryRepository {
         .fetch_one(conn)
         .await?;
 
+        self.invalidate();
+
         Ok(category)
     }
 
+    /// Drops the cached catalogue, so the next read sees the categories saved by other
+    /// repositories, like the bulk import
+    pub fn invalidate(&self) {
+        self.catalogue.clear();
+    }
+
+    /// Returns every category from the cache, loading them when they aren't cached
+    #[tracing::instrument(name = "category.get_catalogue", skip_all)]
+    async fn get_catalogue(
+        &self,
+        conn: &PgPool,
+    ) -> Result<Arc<Vec<CategoryResponse>>, sqlx::Error> {
+        self.catalogue
+            .get_or_try_load((), || async {
+                let _timer = metrics::query_timer("category", "get_catalogue");
+
+                let categories = sqlx::query_as!(
+                    CategoryResponse,
+                    r#"SELECT id, name FROM categories ORDER BY id"#
+                )
+                .fetch_all(conn)
+                .await?;
+
+                Ok(Arc::new(categories))
+            })
+            .await
+    }
+
     pub async fn get_by_id(
         &self,
         category_id: i64,
         conn: &PgPool,
     ) -> Result<CategoryResponse, sqlx::Error> {
-        // saving it to the database
+        let catalogue = self.get_catalogue(conn).await?;
+
+        match catalogue.iter().find(|category| category.id == category_id) {
+            Some(category) => Ok(category.clone()),
+            None => Err(sqlx::Error::RowNotFound),
+        }
+    }
+
+    /// Returns the categories with the given ids, ordered by id
+    pub async fn get_by_ids(
+        &self,
+        ids: &[i64],
+        conn: &PgPool,
+    ) -> Result<Vec<CategoryResponse>, sqlx::Error> {
+        let catalogue = self.get_catalogue(conn).await?;
+
+        Ok(catalogue
+            .iter()
+            .filter(|category| ids.contains(&category.id))
+            .cloned()
+            .collect())
+    }
+
+    #[tracing::instrument(name = "category.get_by_name", skip_all)]
+    pub async fn get_by_name(
+        &self,
+        name: String,
+        conn: &PgPool,
+    ) -> Result<CategoryResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("category", "get_by_name");
+
         let category = sqlx::query_as!(
             CategoryResponse,
-            r#"SELECT * FROM categories WHERE id = $1"#,
-            category_id as i64
+            r#"SELECT * FROM categories WHERE lower(name) = lower($1)"#,
+            name as String
         )
         .fetch_optional(conn)
         .await?;
@@ -79,22 +193,84 @@ impl CategoryRepository {
         }
     }
 
-    pub async fn get_all(
+    /// Returns every category with its label in the given language, ordered by id
+    #[tracing::instrument(name = "category.get_labels", skip_all)]
+    pub async fn get_labels(
         &self,
-        pagination: Pagination,
+        language: Language,
         conn: &PgPool,
-    ) -> Result<Vec<CategoryResponse>, sqlx::Error> {
-        // saving it to the database
-        let categories: Vec<CategoryResponse> = sqlx::query_as!(
-            CategoryResponse,
-            r#"SELECT * FROM categories LIMIT $1 OFFSET $2"#,
-            pagination.per_page as i64,
-            (pagination.page - 1) * pagination.per_page as i64
+    ) -> Result<Vec<CategoryLabel>, sqlx::Error> {
+        let _timer = metrics::query_timer("category", "get_labels");
+
+        sqlx::query_as!(
+            CategoryLabel,
+            r#"SELECT c.id as "id!: i64", c.name, COALESCE(l.label, c.name) as "label!"
+            FROM categories c
+            LEFT JOIN category_labels l ON l.category_id = c.id AND l.language = $1
+            ORDER BY c.id"#,
+            language.code()
         )
         .fetch_all(conn)
+        .await
+    }
+
+    /// Sets the name of a category in the given language
+    #[tracing::instrument(name = "category.set_label", skip_all)]
+    pub async fn set_label(
+        &self,
+        category_id: i64,
+        language: Language,
+        label: String,
+        conn: &PgPool,
+    ) -> Result<CategoryLabel, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("category", "set_label");
+
+        let length = label.trim().chars().count();
+
+        if length == 0 || length > 50 {
+            return Err(Box::new(InvalidInput::new(
+                "THE LABEL MUST HAVE BETWEEN 1 AND 50 CHARACTERS",
+            )));
+        }
+
+        let label = sqlx::query_as!(
+            CategoryLabel,
+            r#"WITH label AS (
+                INSERT INTO category_labels (category_id, language, label)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (category_id, language) DO UPDATE SET label = EXCLUDED.label
+                RETURNING category_id, label
+            )
+            SELECT c.id as "id!: i64", c.name, label.label as "label!"
+            FROM label
+            JOIN categories c ON c.id = label.category_id"#,
+            category_id as i32,
+            language.code(),
+            label.trim()
+        )
+        .fetch_one(conn)
         .await?;
 
-        if categories.len() == 0 {
+        Ok(label)
+    }
+
+    pub async fn get_all(
+        &self,
+        pagination: Pagination,
+        conn: &PgPool,
+    ) -> Result<Vec<CategoryResponse>, sqlx::Error> {
+        let catalogue = self.get_catalogue(conn).await?;
+
+        let offset = ((pagination.page - 1) * pagination.per_page).max(0) as usize;
+
+        let categories: Vec<CategoryResponse> = catalogue
+            .iter()
+            .skip(offset)
+            .take(pagination.per_page.max(0) as usize)
+            .cloned()
+            .collect();
+
+        if categories.is_empty() {
             return Err(sqlx::Error::RowNotFound);
         }
 
@@ -102,6 +278,7 @@ impl CategoryRepository {
     }
 }
 
+#[derive(Default)]
 pub struct UserRepository {}
 
 impl UserRepository {
@@ -109,21 +286,47 @@ impl UserRepository {
         UserRepository {}
     }
 
+    #[tracing::instrument(name = "user.save", skip_all)]
     pub async fn save(&self, user: User, conn: &PgPool) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "save");
+
+        self.insert(user, Roles::User, conn).await
+    }
+
+    /// Saves a user with the Admin role. Only meant to be used by the maintenance tools
+    #[tracing::instrument(name = "user.save_admin", skip_all)]
+    pub async fn save_admin(
+        &self,
+        user: User,
+        conn: &PgPool,
+    ) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "save_admin");
+
+        self.insert(user, Roles::Admin, conn).await
+    }
+
+    async fn insert(
+        &self,
+        user: User,
+        rol: Roles,
+        conn: &PgPool,
+    ) -> Result<UserResponse, sqlx::Error> {
+        let password = password::hash(user.password.expose())?;
+
         let user = sqlx::query_as!(
             UserResponse,
             r#"INSERT INTO users (dni, email, password, name, date_of_birth, registered_at, contact_number, rol)
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles"
+            RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
             "#,
             user.dni as String,
             user.email as String,
-            user.password as String,
+            password,
             user.name as String,
             user.date_of_birth,
             chrono::Utc::now(),
             user.contact_number as String,
-            Roles::User as Roles
+            rol as Roles
         )
         .fetch_one(conn)
         .await?;
@@ -131,14 +334,17 @@ impl UserRepository {
         Ok(user)
     }
 
+    #[tracing::instrument(name = "user.get_by_dni", skip_all)]
     pub async fn get_by_dni(
         &self,
         dni: String,
         conn: &PgPool,
     ) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "get_by_dni");
+
         let user = sqlx::query_as!(
             UserResponse,
-            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles" FROM users WHERE dni = $1"#,
+            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version FROM users WHERE dni = $1"#,
             dni.to_string()
         ).fetch_optional(conn)
         .await?;
@@ -149,32 +355,86 @@ impl UserRepository {
         }
     }
 
+    #[tracing::instrument(name = "user.get_all", skip_all)]
     pub async fn get_all(
         &self,
         pagination: Pagination,
         conn: &PgPool,
     ) -> Result<Vec<UserResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "get_all");
+
         let user = sqlx::query_as!(
             UserResponse,
-            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles" FROM users LIMIT $1 OFFSET $2"#,
+            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version FROM users LIMIT $1 OFFSET $2"#,
             pagination.per_page as i64,
             (pagination.page - 1) * pagination.per_page as i64
         ).fetch_all(conn)
         .await?;
 
-        if user.len() == 0 {
+        if user.is_empty() {
             return Err(sqlx::Error::RowNotFound);
         }
 
         Ok(user)
     }
 
+    #[tracing::instrument(name = "user.get_by_dnis", skip_all)]
+    pub async fn get_by_dnis(
+        &self,
+        dnis: &[String],
+        conn: &PgPool,
+    ) -> Result<Vec<UserResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "get_by_dnis");
+
+        let users = sqlx::query_as!(
+            UserResponse,
+            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version FROM users WHERE dni = ANY($1)"#,
+            dnis
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Returns up to limit users ordered by dni, starting after the given dni
+    #[tracing::instrument(name = "user.get_page", skip_all)]
+    pub async fn get_page(
+        &self,
+        after: Option<String>,
+        limit: i64,
+        conn: &PgPool,
+    ) -> Result<Vec<UserResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "get_page");
+
+        let users = sqlx::query_as!(
+            UserResponse,
+            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version FROM users
+            WHERE $1::VARCHAR IS NULL OR dni > $1
+            ORDER BY dni
+            LIMIT $2"#,
+            after,
+            limit
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Updates the user as long as it's still in the given version
+    #[tracing::instrument(name = "user.update_user", skip_all)]
     pub async fn update_user(
         &self,
         user: User,
+        version: i32,
         conn: &PgPool,
-    ) -> Result<UserResponse, sqlx::Error> {
-        let user = sqlx::query_as!(
+    ) -> Result<UserResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("user", "update_user");
+
+        let password = password::hash(user.password.expose())?;
+
+        let updated = sqlx::query_as!(
             UserResponse,
             r#"
                 UPDATE users
@@ -185,197 +445,1054 @@ impl UserRepository {
                 date_of_birth = $4,  
                 updated_at = $5, 
                 contact_number = $6
-                WHERE dni = $7 
-                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles"
+                WHERE dni = $7
+                AND version = $8
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
             "#,
             user.email as String,
-            user.password as String,
+            password,
             user.name as String,
             user.date_of_birth as chrono::NaiveDate,
             chrono::Utc::now() as chrono::DateTime<chrono::Utc>,
             user.contact_number as String,
-            user.dni as String
+            user.dni.clone(),
+            version
         ).fetch_optional(conn)
         .await?;
 
-        match user {
-            Some(user) => Ok(user),
-            None => Err(sqlx::Error::RowNotFound),
+        match updated {
+            Some(updated) => Ok(updated),
+            None => {
+                let exists = sqlx::query_scalar!(
+                    r#"SELECT EXISTS (SELECT 1 FROM users WHERE dni = $1) as "exists!""#,
+                    user.dni
+                )
+                .fetch_one(conn)
+                .await?;
+
+                Err(outdated_or_missing(exists))
+            }
         }
     }
 
+    /// Saves the current location of the user and appends it to their location history.
+    /// Returns who can see the location, None when the user doesn't exist
+    #[tracing::instrument(name = "user.update_location", skip_all)]
     pub async fn update_location(
         &self,
         user_location: UserLocation,
         conn: &PgPool
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        
+    ) -> Result<Option<LocationVisibility>, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "update_location");
+
+        let mut tx = conn.begin().await?;
+
         let sql = r#"UPDATE users
         SET 
         latitude = $1, 
         longitude = $2
-        WHERE dni = $3"#;
+        WHERE dni = $3
+        RETURNING location_visibility"#;
 
-        sqlx::query(sql)
+        let visibility = sqlx::query_scalar::<_, LocationVisibility>(sql)
             .bind(user_location.latitude)
             .bind(user_location.longitude)
-            .bind(user_location.dni)
-            .execute(conn).await?;
+            .bind(&user_location.dni)
+            .fetch_optional(&mut *tx).await?;
 
-        Ok(())
-    }
-}
+        // Locations of users that don't exist aren't kept
+        if visibility.is_some() {
+            location::record(&user_location, &mut tx).await?;
+        }
 
-pub struct ServiceRepository {}
+        tx.commit().await?;
 
-impl ServiceRepository {
-    pub fn new() -> Self {
-        ServiceRepository {}
+        Ok(visibility)
     }
 
-    pub async fn save(
+    /// Changes who can see the location of the user
+    #[tracing::instrument(name = "user.update_location_visibility", skip_all)]
+    pub async fn update_location_visibility(
         &self,
-        service: Service,
+        dni: String,
+        visibility: LocationVisibility,
         conn: &PgPool,
-    ) -> Result<ServiceResponse, sqlx::Error> {
-        let service = sqlx::query_as!(
-            ServiceResponse,
-            r#"INSERT INTO services (user_id, category_id, price, description, modality) VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, user_id, category_id, price, description, modality as "modality: Modality"
+    ) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "update_location_visibility");
+
+        let user = sqlx::query_as!(
+            UserResponse,
+            r#"
+                UPDATE users
+                SET
+                location_visibility = $1,
+                updated_at = $2
+                WHERE dni = $3
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
             "#,
-            service.user_id as String,
-            service.category_id as i64,
-            service.price as f32,
-            service.description as String,
-            service.modality as Modality
-        ).fetch_one(conn)
+            visibility as LocationVisibility,
+            chrono::Utc::now(),
+            dni
+        )
+        .fetch_one(conn)
         .await?;
 
-        Ok(service)
+        Ok(user)
     }
 
-    pub async fn get_by_dni(
+    #[tracing::instrument(name = "user.update_password", skip_all)]
+    pub async fn update_password(
         &self,
         dni: String,
+        password: Secret,
         conn: &PgPool,
-    ) -> Result<ServiceResponse, sqlx::Error> {
-        let service = sqlx::query_as!(
-            ServiceResponse,
+    ) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "update_password");
+
+        let password = password::hash(password.expose())?;
+
+        let user = sqlx::query_as!(
+            UserResponse,
             r#"
-            SELECT id, user_id, category_id, price, description, modality as "modality: Modality" FROM services WHERE user_id = $1 
+                UPDATE users
+                SET
+                password = $1,
+                updated_at = $2
+                WHERE dni = $3
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
             "#,
+            password,
+            chrono::Utc::now(),
             dni as String
-        ).fetch_one(conn)
+        ).fetch_optional(conn)
         .await?;
 
-        Ok(service)
+        match user {
+            Some(user) => Ok(user),
+            None => Err(sqlx::Error::RowNotFound),
+        }
     }
 
-    pub async fn update_service(
+    #[tracing::instrument(name = "user.promote_to_seller", skip_all)]
+    pub async fn promote_to_seller(
         &self,
-        service: Service,
+        dni: String,
+        category_id: i64,
         conn: &PgPool,
-    ) -> Result<ServiceResponse, Box<dyn Error>> {
-        match service.id {
-            Some(id) => {
-                let service = sqlx::query_as!(
-                    ServiceResponse,
-                    r#"UPDATE services
-                    SET 
-                    category_id = $1,
-                    price = $2,
-                    description = $3,
-                    modality = $4
-                    WHERE id = $5
-                    RETURNING id, user_id, category_id, price, description, modality as "modality: Modality"
-                    "#,
-                    service.category_id as i64,
-                    service.price as f64,
-                    service.description as String,
-                    service.modality as Modality,
-                    id as Uuid
-                ).fetch_one(conn)
-                .await?;
+    ) -> Result<UserResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("user", "promote_to_seller");
 
-                Ok(service)
-            }
-            None => Err(Box::new(NoIdProvided::new(
-                "NO ID PROVIDED TO UPDATE THE SERVICE",
-            ))),
+        let user = sqlx::query_as!(
+            UserResponse,
+            r#"
+                UPDATE users
+                SET
+                is_seller = TRUE,
+                category_id = $1,
+                updated_at = $2
+                WHERE dni = $3
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
+            "#,
+            category_id,
+            chrono::Utc::now(),
+            dni as String
+        ).fetch_optional(conn)
+        .await?;
+
+        match user {
+            Some(user) => Ok(user),
+            None => Err(sqlx::Error::RowNotFound),
         }
     }
 }
 
-pub struct RateRepository {}
+/// Row of the services table, where the price is split in amount and currency
+#[derive(Serialize, Debug)]
+pub struct ServiceRecord {
+    pub id: Uuid,
+    pub user_id: String,
+    pub category_id: i64,
+    pub price: Decimal,
+    pub currency: String,
+    pub description: String,
+    pub modality: Modality,
+    pub favorites_count: i64,
+    /// Not exported, the version only identifies the row of this database
+    #[serde(skip_serializing)]
+    pub version: i32,
+}
 
-impl RateRepository {
-    pub fn new() -> Self {
-        RateRepository {}
+impl From<ServiceRecord> for ServiceResponse {
+    fn from(record: ServiceRecord) -> Self {
+        ServiceResponse {
+            id: record.id,
+            user_id: record.user_id,
+            category_id: record.category_id,
+            price: Money {
+                amount: record.price,
+                currency: record.currency,
+            },
+            description: record.description,
+            modality: record.modality,
+            favorites_count: record.favorites_count,
+            version: record.version,
+        }
     }
+}
 
-    pub async fn save(&self, rate: Rate, conn: &PgPool) -> Result<RateResponse, sqlx::Error> {
-        let rate = sqlx::query_as!(
-            RateResponse,
-            r#"INSERT INTO rates (rater, rated, rate, created_at)VALUES ($1, $2, $3, $4)
-            RETURNING rater, rated, rate, created_at, updated_at"#,
-            rate.rater,
-            rate.rated,
-            rate.rate,
-            chrono::Utc::now()
-        )
-        .fetch_one(conn)
-        .await?;
+#[derive(Default)]
+pub struct ServiceRepository {}
 
-        Ok(rate)
+impl ServiceRepository {
+    pub fn new() -> Self {
+        ServiceRepository {}
     }
 
-    pub async fn get_rate(
+    /// Publishes a service of a seller, as long as the seller hasn't reached the limit
+    /// of services configured in the settings
+    #[tracing::instrument(name = "service.save", skip_all)]
+    pub async fn save(
         &self,
-        rater: String,
-        rated: String,
+        service: Service,
         conn: &PgPool,
-    ) -> Result<RateResponse, sqlx::Error> {
-        let rates = sqlx::query_as!(
-            RateResponse,
-            r#"SELECT * FROM rates WHERE rated = $1 AND rater = $2"#,
-            rated as String,
-            rater as String
+    ) -> Result<ServiceResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("service", "save");
+
+        let mut tx = conn.begin().await?;
+
+        // Lock the seller so concurrent requests can't exceed the limit
+        let is_seller = sqlx::query_scalar!(
+            r#"SELECT is_seller FROM users WHERE dni = $1 FOR UPDATE"#,
+            service.user_id.clone()
         )
-        .fetch_optional(conn)
+        .fetch_optional(&mut *tx)
         .await?;
 
-        match rates {
-            Some(rates) => Ok(rates),
-            None => Err(sqlx::Error::RowNotFound),
+        match is_seller {
+            None => return Err(Box::new(sqlx::Error::RowNotFound)),
+            Some(false) => return Err(Box::new(Conflict::new("USER IS NOT A SELLER"))),
+            Some(true) => {}
         }
-    }
 
-    pub async fn get_rates_by_rated(
-        &self,
-        rated: String,
-        pagination: Pagination,
-        conn: &PgPool,
-    ) -> Result<Vec<RateResponse>, sqlx::Error> {
-        let rates = sqlx::query_as!(
-            RateResponse,
-            r#"SELECT * FROM rates WHERE rated = $1
-            LIMIT $2 OFFSET $3"#,
-            rated as String,
-            pagination.per_page as i64,
-            (pagination.page - 1) * pagination.per_page as i64
+        let usage = sqlx::query!(
+            r#"SELECT
+            (SELECT COUNT(*) FROM services WHERE user_id = $1) as "services!",
+            (SELECT max_services_per_seller FROM settings) as "limit!"
+            "#,
+            service.user_id.clone()
         )
-        .fetch_all(conn)
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(rates)
-    }
+        if usage.services >= usage.limit as i64 {
+            return Err(Box::new(Conflict::new(
+                "THE SELLER REACHED THE LIMIT OF SERVICES",
+            )));
+        }
 
-    pub async fn get_rates_by_rater(
-        &self,
+        let service = sqlx::query_as!(
+            ServiceRecord,
+            r#"INSERT INTO services (user_id, category_id, price, currency, description, modality) VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, category_id, price, currency, description, modality as "modality: Modality", 0::BIGINT as "favorites_count!", version
+            "#,
+            service.user_id as String,
+            service.category_id as i64,
+            service.price.amount,
+            service.price.currency,
+            service.description as String,
+            service.modality as Modality
+        ).fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(service.into())
+    }
+
+    #[tracing::instrument(name = "service.get_by_id", skip_all)]
+    pub async fn get_by_id(&self, id: Uuid, conn: &PgPool) -> Result<ServiceResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("service", "get_by_id");
+
+        let service = sqlx::query_as!(
+            ServiceRecord,
+            r#"
+            SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version FROM services WHERE id = $1
+            "#,
+            id as Uuid
+        ).fetch_one(conn)
+        .await?;
+
+        Ok(service.into())
+    }
+
+    #[tracing::instrument(name = "service.get_by_seller", skip_all)]
+    pub async fn get_by_seller(
+        &self,
+        dni: String,
+        pagination: Pagination,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("service", "get_by_seller");
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"
+            SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version FROM services WHERE user_id = $1
+            ORDER BY id
+            LIMIT $2 OFFSET $3
+            "#,
+            dni as String,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        ).fetch_all(conn)
+        .await?;
+
+        if services.is_empty() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+
+    /// Returns the services of the given sellers, ordered by id
+    #[tracing::instrument(name = "service.get_by_sellers", skip_all)]
+    pub async fn get_by_sellers(
+        &self,
+        dnis: &[String],
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("service", "get_by_sellers");
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version FROM services WHERE user_id = ANY($1) ORDER BY id"#,
+            dnis
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+
+    /// Returns up to limit services, optionally of a category, ordered by id and starting
+    /// after the given id
+    #[tracing::instrument(name = "service.get_page", skip_all)]
+    pub async fn get_page(
+        &self,
+        after: Option<Uuid>,
+        category_id: Option<i64>,
+        limit: i64,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("service", "get_page");
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version FROM services
+            WHERE ($1::UUID IS NULL OR id > $1)
+            AND ($2::BIGINT IS NULL OR category_id = $2)
+            ORDER BY id
+            LIMIT $3"#,
+            after,
+            category_id,
+            limit
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+
+    #[tracing::instrument(name = "service.get_by_ids", skip_all)]
+    pub async fn get_by_ids(
+        &self,
+        ids: &[Uuid],
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("service", "get_by_ids");
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"
+            SELECT id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version FROM services WHERE id = ANY($1)
+            "#,
+            ids
+        ).fetch_all(conn)
+        .await?;
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+
+    /// Updates the service as long as it's still in the given version
+    #[tracing::instrument(name = "service.update_service", skip_all)]
+    pub async fn update_service(
+        &self,
+        service: Service,
+        version: i32,
+        conn: &PgPool,
+    ) -> Result<ServiceResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("service", "update_service");
+
+        match service.id {
+            Some(id) => {
+                let service = sqlx::query_as!(
+                    ServiceRecord,
+                    r#"UPDATE services
+                    SET 
+                    category_id = $1,
+                    price = $2,
+                    currency = $3,
+                    description = $4,
+                    modality = $5
+                    WHERE id = $6
+                    AND version = $7
+                    RETURNING id, user_id, category_id, price, currency, description, modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services WHERE service_id = services.id) as "favorites_count!", version
+                    "#,
+                    service.category_id as i64,
+                    service.price.amount,
+                    service.price.currency,
+                    service.description as String,
+                    service.modality as Modality,
+                    id as Uuid,
+                    version
+                ).fetch_optional(conn)
+                .await?;
+
+                match service {
+                    Some(service) => Ok(service.into()),
+                    None => {
+                        let exists = sqlx::query_scalar!(
+                            r#"SELECT EXISTS (SELECT 1 FROM services WHERE id = $1) as "exists!""#,
+                            id
+                        )
+                        .fetch_one(conn)
+                        .await?;
+
+                        Err(outdated_or_missing(exists))
+                    }
+                }
+            }
+            None => Err(Box::new(NoIdProvided::new(
+                "NO ID PROVIDED TO UPDATE THE SERVICE",
+            ))),
+        }
+    }
+
+    /// Returns the services of the active sellers a buyer at the point can get, optionally of a
+    /// category, ordered by id. Home-visit services are only included when one of their areas
+    /// covers the point
+    #[tracing::instrument(name = "service.search", skip_all)]
+    pub async fn search(
+        &self,
+        request: ServiceSearchRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<ServiceResponse>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("service", "search");
+
+        if !location::valid_coordinates(request.latitude, request.longitude) {
+            return Err(Box::new(InvalidInput::new(
+                "THE COORDINATES ARE OUT OF RANGE",
+            )));
+        }
+
+        let pagination = Pagination::new(PaginationRequest {
+            page: request.page,
+            per_page: request.per_page,
+        });
+
+        let mut conn = conn.acquire().await?;
+
+        let covering: Vec<Uuid> = area::covering(request.latitude, request.longitude, &mut conn)
+            .await?
+            .into_iter()
+            .collect();
+
+        let services = sqlx::query_as!(
+            ServiceRecord,
+            r#"SELECT s.id, s.user_id, s.category_id, s.price, s.currency, s.description, s.modality as "modality: Modality", (SELECT COUNT(*) FROM favorite_services f WHERE f.service_id = s.id) as "favorites_count!", s.version FROM services s
+            JOIN users u ON u.dni = s.user_id
+            WHERE u.is_seller AND u.seller_paused_at IS NULL
+            AND ($1::BIGINT IS NULL OR s.category_id = $1)
+            AND (s.modality <> 'domicilio' OR s.id = ANY($2))
+            ORDER BY s.id
+            LIMIT $3 OFFSET $4"#,
+            request.category_id,
+            &covering,
+            pagination.per_page,
+            (pagination.page - 1) * pagination.per_page
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(services.into_iter().map(ServiceResponse::from).collect())
+    }
+
+    #[tracing::instrument(name = "service.delete_service", skip_all)]
+    pub async fn delete_service(&self, id: Uuid, conn: &PgPool) -> Result<(), sqlx::Error> {
+        let _timer = metrics::query_timer("service", "delete_service");
+
+        let result = sqlx::query!(r#"DELETE FROM services WHERE id = $1"#, id as Uuid)
+            .execute(conn)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct SellerRepository {}
+
+impl SellerRepository {
+    pub fn new() -> Self {
+        SellerRepository {}
+    }
+
+    /// Returns the active sellers in the given order. Sellers without rates have the prior
+    /// mean as reputation
+    #[tracing::instrument(name = "seller.get_all", skip_all)]
+    pub async fn get_all(
+        &self,
+        request: SellerListRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<SellerSummary>, sqlx::Error> {
+        let _timer = metrics::query_timer("seller", "get_all");
+
+        let sort = request.sort.unwrap_or_default();
+        let pagination = Pagination::new(PaginationRequest {
+            page: request.page,
+            per_page: request.per_page,
+        });
+
+        let sellers = sqlx::query_as!(
+            SellerSummary,
+            r#"SELECT
+                u.dni,
+                u.name,
+                u.category_id,
+                COALESCE(p.score, s.reputation_prior_mean) as "reputation!",
+                p.average_rate as "average_rate?",
+                COALESCE(p.rates_count, 0) as "rates_count!"
+            FROM users u
+            CROSS JOIN settings s
+            LEFT JOIN reputations p ON p.user_id = u.dni
+            WHERE u.is_seller AND u.seller_paused_at IS NULL
+            AND ($1::BIGINT IS NULL OR u.category_id = $1)
+            ORDER BY
+                CASE WHEN $2 THEN p.average_rate END DESC NULLS LAST,
+                CASE WHEN $3 THEN u.registered_at END DESC,
+                COALESCE(p.score, s.reputation_prior_mean) DESC,
+                u.dni
+            LIMIT $4 OFFSET $5"#,
+            request.category_id,
+            sort == SellerSort::Rating,
+            sort == SellerSort::Newest,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(sellers)
+    }
+
+    /// Turns a user into a seller of the given category and publishes its first service.
+    /// Both changes are applied in the same transaction
+    #[tracing::instrument(name = "seller.onboard", skip_all)]
+    pub async fn onboard(
+        &self,
+        dni: String,
+        onboarding: SellerOnboarding,
+        conn: &PgPool,
+    ) -> Result<SellerOnboardingResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("seller", "onboard");
+
+        let mut tx = conn.begin().await?;
+
+        // Lock the user so two onboarding requests can't run at the same time
+        let is_seller = sqlx::query_scalar!(
+            r#"SELECT is_seller FROM users WHERE dni = $1 FOR UPDATE"#,
+            dni.clone()
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match is_seller {
+            None => return Err(Box::new(sqlx::Error::RowNotFound)),
+            Some(true) => return Err(Box::new(Conflict::new("USER IS ALREADY A SELLER"))),
+            Some(false) => {}
+        }
+
+        let user = sqlx::query_as!(
+            UserResponse,
+            r#"
+                UPDATE users
+                SET
+                is_seller = TRUE,
+                category_id = $1,
+                contact_number = $2,
+                seller_paused_at = NULL,
+                updated_at = $3
+                WHERE dni = $4
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
+            "#,
+            onboarding.category_id,
+            onboarding.contact_number as String,
+            chrono::Utc::now(),
+            dni.clone()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let service = sqlx::query_as!(
+            ServiceRecord,
+            r#"INSERT INTO services (user_id, category_id, price, currency, description, modality) VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, category_id, price, currency, description, modality as "modality: Modality", 0::BIGINT as "favorites_count!", version
+            "#,
+            dni as String,
+            onboarding.category_id,
+            onboarding.service.price.amount,
+            onboarding.service.price.currency,
+            onboarding.service.description as String,
+            onboarding.service.modality as Modality
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(SellerOnboardingResponse {
+            user,
+            service: service.into(),
+        })
+    }
+
+    /// Pauses or resumes a seller. Paused sellers keep their services but stop being listed
+    #[tracing::instrument(name = "seller.set_paused", skip_all)]
+    pub async fn set_paused(
+        &self,
+        dni: String,
+        paused: bool,
+        conn: &PgPool,
+    ) -> Result<UserResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("seller", "set_paused");
+
+        let paused_at = if paused { Some(chrono::Utc::now()) } else { None };
+
+        let user = sqlx::query_as!(
+            UserResponse,
+            r#"
+                UPDATE users
+                SET
+                seller_paused_at = $1,
+                updated_at = $2
+                WHERE dni = $3
+                AND is_seller
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
+            "#,
+            paused_at,
+            chrono::Utc::now(),
+            dni as String
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match user {
+            Some(user) => Ok(user),
+            None => Err(Box::new(Conflict::new("USER IS NOT A SELLER"))),
+        }
+    }
+
+    /// Removes the seller status of a user together with its services and schedule.
+    /// Its pending and confirmed bookings are cancelled
+    #[tracing::instrument(name = "seller.leave", skip_all)]
+    pub async fn leave(
+        &self,
+        dni: String,
+        conn: &PgPool,
+    ) -> Result<UserResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("seller", "leave");
+
+        let mut tx = conn.begin().await?;
+
+        let user = sqlx::query_as!(
+            UserResponse,
+            r#"
+                UPDATE users
+                SET
+                is_seller = FALSE,
+                category_id = NULL,
+                seller_paused_at = NULL,
+                updated_at = $1
+                WHERE dni = $2
+                AND is_seller
+                RETURNING id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version
+            "#,
+            chrono::Utc::now(),
+            dni.clone()
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(Box::new(Conflict::new("USER IS NOT A SELLER"))),
+        };
+
+        // The bookings keep the history of the seller, but the ones to come won't happen
+        sqlx::query!(
+            r#"UPDATE bookings SET status = 'cancelled'
+            WHERE seller_id = $1 AND status IN ('pending', 'confirmed')"#,
+            dni.clone()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(r#"DELETE FROM seller_schedules WHERE user_id = $1"#, dni.clone())
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(r#"DELETE FROM services WHERE user_id = $1"#, dni as String)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(user)
+    }
+}
+
+#[derive(Default)]
+pub struct SettingsRepository {}
+
+impl SettingsRepository {
+    pub fn new() -> Self {
+        SettingsRepository {}
+    }
+
+    #[tracing::instrument(name = "settings.get", skip_all)]
+    pub async fn get(&self, conn: &PgPool) -> Result<SettingsResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("settings", "get");
+
+        let settings = sqlx::query_as!(
+            SettingsResponse,
+            r#"SELECT max_services_per_seller, review_verification as "review_verification: ReviewVerification",
+            reputation_prior_mean, reputation_prior_weight, reputation_half_life_days, location_retention_days,
+            location_grid_meters, updated_at
+            FROM settings"#
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(settings)
+    }
+
+    /// Updates the given settings. The reputations are computed again when their parameters change
+    #[tracing::instrument(name = "settings.update", skip_all)]
+    pub async fn update(
+        &self,
+        settings: Settings,
+        conn: &PgPool,
+    ) -> Result<SettingsResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("settings", "update");
+
+        let reputation_changed = settings.reputation_prior_mean.is_some()
+            || settings.reputation_prior_weight.is_some()
+            || settings.reputation_half_life_days.is_some();
+
+        let mut tx = conn.begin().await?;
+
+        let settings = sqlx::query_as!(
+            SettingsResponse,
+            r#"UPDATE settings
+            SET
+            max_services_per_seller = COALESCE($1, max_services_per_seller),
+            review_verification = COALESCE($2, review_verification),
+            reputation_prior_mean = COALESCE($3, reputation_prior_mean),
+            reputation_prior_weight = COALESCE($4, reputation_prior_weight),
+            reputation_half_life_days = COALESCE($5, reputation_half_life_days),
+            location_retention_days = COALESCE($6, location_retention_days),
+            location_grid_meters = COALESCE($7, location_grid_meters),
+            updated_at = now()
+            RETURNING max_services_per_seller, review_verification as "review_verification: ReviewVerification",
+            reputation_prior_mean, reputation_prior_weight, reputation_half_life_days, location_retention_days,
+            location_grid_meters, updated_at
+            "#,
+            settings.max_services_per_seller,
+            settings.review_verification as Option<ReviewVerification>,
+            settings.reputation_prior_mean,
+            settings.reputation_prior_weight,
+            settings.reputation_half_life_days,
+            settings.location_retention_days,
+            settings.location_grid_meters
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if reputation_changed {
+            refresh_reputations(None, &mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(settings)
+    }
+}
+
+/// Returns the error of an update that matched no row, either because the row doesn't exist
+/// or because it isn't in the version the update expected anymore
+fn outdated_or_missing(exists: bool) -> Box<dyn Error + Send + Sync> {
+    if exists {
+        Box::new(PreconditionFailed::new(
+            "THE RESOURCE WAS CHANGED SINCE THE GIVEN VERSION",
+        ))
+    } else {
+        Box::new(sqlx::Error::RowNotFound)
+    }
+}
+
+/// Returns if the reviewer has a completed booking with the reviewed user, as long as
+/// the verification of reviews is on. When it's required unverified reviews are rejected
+async fn verify_review(
+    reviewer: &str,
+    reviewed: &str,
+    conn: &PgPool,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let mode = sqlx::query_scalar!(
+        r#"SELECT review_verification as "review_verification: ReviewVerification" FROM settings"#
+    )
+    .fetch_one(conn)
+    .await?;
+
+    if mode == ReviewVerification::Off {
+        return Ok(false);
+    }
+
+    let verified = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM bookings
+            WHERE customer_id = $1 AND seller_id = $2 AND status = 'completed'
+        ) as "verified!""#,
+        reviewer,
+        reviewed
+    )
+    .fetch_one(conn)
+    .await?;
+
+    if !verified && mode == ReviewVerification::Require {
+        return Err(Box::new(Conflict::new(
+            "ONLY CUSTOMERS WITH A COMPLETED BOOKING CAN REVIEW THE SELLER",
+        )));
+    }
+
+    Ok(verified)
+}
+
+pub struct RateRepository {
+    /// Rating summaries by dni of the rated user
+    summaries: Cache<String, RatingSummary>,
+}
+
+impl Default for RateRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateRepository {
+    pub fn new() -> Self {
+        RateRepository {
+            summaries: Cache::new("rating_summaries", cache::DEFAULT_TTL),
+        }
+    }
+
+    #[tracing::instrument(name = "rate.save", skip_all)]
+    pub async fn save(
+        &self,
+        rate: Rate,
+        conn: &PgPool,
+    ) -> Result<RateResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("rate", "save");
+
+        let verified = verify_review(&rate.rater, &rate.rated, conn).await?;
+
+        let mut tx = conn.begin().await?;
+
+        let rate = sqlx::query_as!(
+            RateResponse,
+            r#"INSERT INTO rates (rater, rated, rate, created_at, verified)VALUES ($1, $2, $3, $4, $5)
+            RETURNING rater, rated, rate, created_at, updated_at, verified, version"#,
+            rate.rater,
+            rate.rated,
+            rate.rate,
+            chrono::Utc::now(),
+            verified
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        refresh_reputations(Some(&rate.rated), &mut tx).await?;
+
+        tx.commit().await?;
+
+        self.summaries.invalidate(&rate.rated);
+
+        Ok(rate)
+    }
+
+    #[tracing::instrument(name = "rate.get_rate", skip_all)]
+    pub async fn get_rate(
+        &self,
         rater: String,
+        rated: String,
+        conn: &PgPool,
+    ) -> Result<RateResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("rate", "get_rate");
+
+        let rates = sqlx::query_as!(
+            RateResponse,
+            r#"SELECT * FROM rates WHERE rated = $1 AND rater = $2"#,
+            rated as String,
+            rater as String
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        match rates {
+            Some(rates) => Ok(rates),
+            None => Err(sqlx::Error::RowNotFound),
+        }
+    }
+
+    /// Returns the number and average of the rates received by the user
+    #[tracing::instrument(name = "rate.get_summary", skip_all)]
+    pub async fn get_summary(
+        &self,
+        dni: String,
+        conn: &PgPool,
+    ) -> Result<RatingSummary, sqlx::Error> {
+        self.summaries
+            .get_or_try_load(dni.clone(), || async {
+                let _timer = metrics::query_timer("rate", "get_summary");
+
+                sqlx::query_as!(
+                    RatingSummary,
+                    r#"SELECT
+                        u.dni,
+                        COUNT(r.rate) as "rates_count!",
+                        AVG(r.rate)::DOUBLE PRECISION as "average_rate",
+                        COUNT(r.rate) FILTER (WHERE r.verified) as "verified_rates_count!"
+                    FROM users u
+                    LEFT JOIN rates r ON r.rated = u.dni
+                    WHERE u.dni = $1
+                    GROUP BY u.dni"#,
+                    dni
+                )
+                .fetch_one(conn)
+                .await
+            })
+            .await
+    }
+
+    /// Returns the rating summaries of the given users. Users that don't exist are left out
+    #[tracing::instrument(name = "rate.get_summaries", skip_all)]
+    pub async fn get_summaries(
+        &self,
+        dnis: &[String],
+        conn: &PgPool,
+    ) -> Result<HashMap<String, RatingSummary>, sqlx::Error> {
+        self.summaries
+            .get_many_or_try_load(dnis, |missing| async move {
+                let _timer = metrics::query_timer("rate", "get_summaries");
+
+                let summaries = sqlx::query_as!(
+                    RatingSummary,
+                    r#"SELECT
+                        u.dni,
+                        COUNT(r.rate) as "rates_count!",
+                        AVG(r.rate)::DOUBLE PRECISION as "average_rate",
+                        COUNT(r.rate) FILTER (WHERE r.verified) as "verified_rates_count!"
+                    FROM users u
+                    LEFT JOIN rates r ON r.rated = u.dni
+                    WHERE u.dni = ANY($1)
+                    GROUP BY u.dni"#,
+                    &missing
+                )
+                .fetch_all(conn)
+                .await?;
+
+                Ok(summaries
+                    .into_iter()
+                    .map(|summary| (summary.dni.clone(), summary))
+                    .collect())
+            })
+            .await
+    }
+
+    /// Returns up to limit rates received by the user, ordered by rater and starting after
+    /// the given rater
+    #[tracing::instrument(name = "rate.get_page_by_rated", skip_all)]
+    pub async fn get_page_by_rated(
+        &self,
+        rated: String,
+        after: Option<String>,
+        limit: i64,
+        conn: &PgPool,
+    ) -> Result<Vec<RateResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("rate", "get_page_by_rated");
+
+        let rates = sqlx::query_as!(
+            RateResponse,
+            r#"SELECT rater, rated, rate, created_at, updated_at, verified, version FROM rates
+            WHERE rated = $1
+            AND ($2::VARCHAR IS NULL OR rater > $2)
+            ORDER BY rater
+            LIMIT $3"#,
+            rated,
+            after,
+            limit
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rates)
+    }
+
+    #[tracing::instrument(name = "rate.get_rates_by_rated", skip_all)]
+    pub async fn get_rates_by_rated(
+        &self,
+        rated: String,
         pagination: Pagination,
         conn: &PgPool,
     ) -> Result<Vec<RateResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("rate", "get_rates_by_rated");
+
+        let rates = sqlx::query_as!(
+            RateResponse,
+            r#"SELECT * FROM rates WHERE rated = $1
+            LIMIT $2 OFFSET $3"#,
+            rated as String,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rates)
+    }
+
+    #[tracing::instrument(name = "rate.get_rates_by_rater", skip_all)]
+    pub async fn get_rates_by_rater(
+        &self,
+        rater: String,
+        pagination: Pagination,
+        conn: &PgPool,
+    ) -> Result<Vec<RateResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("rate", "get_rates_by_rater");
+
         let rates = sqlx::query_as!(
             RateResponse,
             r#"SELECT * FROM rates WHERE rater = $1 LIMIT $2 OFFSET $3"#,
@@ -389,32 +1506,67 @@ impl RateRepository {
         Ok(rates)
     }
 
+    /// Updates the rate as long as it's still in the given version
+    #[tracing::instrument(name = "rate.update_rate", skip_all)]
     pub async fn update_rate(
         &self,
         rate: Rate,
+        version: i32,
         conn: &PgPool,
-    ) -> Result<RateResponse, sqlx::Error> {
-        let rate = sqlx::query_as!(
+    ) -> Result<RateResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("rate", "update_rate");
+
+        let verified = verify_review(&rate.rater, &rate.rated, conn).await?;
+
+        let mut tx = conn.begin().await?;
+
+        let updated = sqlx::query_as!(
             RateResponse,
             r#"UPDATE rates
             SET
             rate = $1,
-            updated_at = $2
+            updated_at = $2,
+            verified = $5
             WHERE rater = $3
             AND rated = $4
-            RETURNING rater, rated, rate, created_at, updated_at"#,
+            AND version = $6
+            RETURNING rater, rated, rate, created_at, updated_at, verified, version"#,
             rate.rate as f32,
             chrono::Utc::now(),
-            rate.rater as String,
-            rate.rated as String
+            rate.rater.clone(),
+            rate.rated.clone(),
+            verified,
+            version
         )
-        .fetch_one(conn)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        let rate = match updated {
+            Some(updated) => updated,
+            None => {
+                let exists = sqlx::query_scalar!(
+                    r#"SELECT EXISTS (SELECT 1 FROM rates WHERE rater = $1 AND rated = $2) as "exists!""#,
+                    rate.rater,
+                    rate.rated
+                )
+                .fetch_one(&mut *tx)
+                .await?;
+
+                return Err(outdated_or_missing(exists));
+            }
+        };
+
+        refresh_reputations(Some(&rate.rated), &mut tx).await?;
+
+        tx.commit().await?;
+
+        self.summaries.invalidate(&rate.rated);
+
         Ok(rate)
     }
 }
 
+#[derive(Default)]
 pub struct CommentRepository {}
 
 impl CommentRepository {
@@ -422,19 +1574,60 @@ impl CommentRepository {
         CommentRepository {}
     }
 
+    #[tracing::instrument(name = "comment.save", skip_all)]
     pub async fn save(
         &self,
         comment: Comment,
         conn: &PgPool,
-    ) -> Result<CommentResponse, sqlx::Error> {
+    ) -> Result<CommentResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("comment", "save");
+
+        let verified = verify_review(&comment.commentator, &comment.commented, conn).await?;
+
         let comment = sqlx::query_as!(
             CommentResponse,
-            r#"INSERT INTO comments (commentator, commented, comment, created_at)
-            VALUES ($1, $2, $3, $4) 
-            RETURNING commentator, commented, comment, created_at, updated_at"#,
+            r#"INSERT INTO comments (commentator, commented, comment, created_at, verified)
+            VALUES ($1, $2, $3, $4, $5) 
+            RETURNING id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version"#,
             comment.commentator as String,
             comment.commented as String,
             comment.comment as String,
+            chrono::Utc::now(),
+            verified
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(comment)
+    }
+
+    /// Replies to a top level comment. The reply is about the same user as the comment
+    #[tracing::instrument(name = "comment.reply", skip_all)]
+    pub async fn reply(
+        &self,
+        parent_id: Uuid,
+        reply: CommentReply,
+        conn: &PgPool,
+    ) -> Result<CommentResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("comment", "reply");
+
+        let parent = self.get_comment(parent_id, false, conn).await?;
+
+        if parent.parent_id.is_some() {
+            return Err(Box::new(InvalidInput::new(
+                "YOU CAN ONLY REPLY TO A TOP LEVEL COMMENT",
+            )));
+        }
+
+        let comment = sqlx::query_as!(
+            CommentResponse,
+            r#"INSERT INTO comments (parent_id, commentator, commented, comment, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version"#,
+            parent_id,
+            reply.commentator,
+            parent.commented,
+            reply.comment,
             chrono::Utc::now()
         )
         .fetch_one(conn)
@@ -443,17 +1636,23 @@ impl CommentRepository {
         Ok(comment)
     }
 
+    /// Returns the comment. Comments hidden by the moderation are only returned with
+    /// include_hidden, meant for the admins
+    #[tracing::instrument(name = "comment.get_comment", skip_all)]
     pub async fn get_comment(
         &self,
-        commentator: String,
-        commented: String,
+        id: Uuid,
+        include_hidden: bool,
         conn: &PgPool,
     ) -> Result<CommentResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("comment", "get_comment");
+
         let comment = sqlx::query_as!(
             CommentResponse,
-            r#"SELECT * FROM comments WHERE commented = $1 AND commentator = $2"#,
-            commentator as String,
-            commented as String
+            r#"SELECT id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version
+            FROM comments WHERE id = $1 AND ($2 OR hidden_at IS NULL)"#,
+            id,
+            include_hidden
         )
         .fetch_optional(conn)
         .await?;
@@ -464,15 +1663,23 @@ impl CommentRepository {
         }
     }
 
+    /// Returns the visible top level comments about the user, the oldest first,
+    /// each one with its visible replies
+    #[tracing::instrument(name = "comment.get_comments_by_commented", skip_all)]
     pub async fn get_comments_by_commented(
         &self,
         commented: String,
         pagination: Pagination,
         conn: &PgPool,
-    ) -> Result<Vec<CommentResponse>, sqlx::Error> {
+    ) -> Result<Vec<CommentThread>, sqlx::Error> {
+        let _timer = metrics::query_timer("comment", "get_comments_by_commented");
+
         let comments = sqlx::query_as!(
             CommentResponse,
-            r#"SELECT * FROM comments WHERE commented = $1 LIMIT $2 OFFSET $3"#,
+            r#"SELECT id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version
+            FROM comments WHERE commented = $1 AND parent_id IS NULL AND hidden_at IS NULL
+            ORDER BY created_at, id
+            LIMIT $2 OFFSET $3"#,
             commented as String,
             pagination.per_page as i64,
             (pagination.page - 1) * pagination.per_page as i64
@@ -480,19 +1687,105 @@ impl CommentRepository {
         .fetch_all(conn)
         .await?;
 
+        let ids: Vec<Uuid> = comments.iter().map(|comment| comment.id).collect();
+
+        let replies = self.get_replies(&ids, conn).await?;
+
+        let mut threads: Vec<CommentThread> = comments
+            .into_iter()
+            .map(|comment| CommentThread {
+                comment,
+                replies: Vec::new(),
+            })
+            .collect();
+
+        for reply in replies {
+            if let Some(thread) = threads
+                .iter_mut()
+                .find(|thread| Some(thread.comment.id) == reply.parent_id)
+            {
+                thread.replies.push(reply);
+            }
+        }
+
+        Ok(threads)
+    }
+
+    /// Returns the visible replies of the given comments, the oldest first
+    #[tracing::instrument(name = "comment.get_replies", skip_all)]
+    pub async fn get_replies(
+        &self,
+        parent_ids: &[Uuid],
+        conn: &PgPool,
+    ) -> Result<Vec<CommentResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("comment", "get_replies");
+
+        let replies = sqlx::query_as!(
+            CommentResponse,
+            r#"SELECT id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version
+            FROM comments WHERE parent_id = ANY($1) AND hidden_at IS NULL
+            ORDER BY created_at, id"#,
+            parent_ids
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(replies)
+    }
+
+    /// Returns up to limit visible top level comments about the user, the newest first,
+    /// starting before the given date and id
+    #[tracing::instrument(name = "comment.get_latest_by_commented", skip_all)]
+    pub async fn get_latest_by_commented(
+        &self,
+        commented: String,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+        conn: &PgPool,
+    ) -> Result<Vec<CommentResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("comment", "get_latest_by_commented");
+
+        let (before_created_at, before_id) = before.unzip();
+
+        let comments = sqlx::query_as!(
+            CommentResponse,
+            r#"SELECT id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version
+            FROM comments
+            WHERE commented = $1 AND parent_id IS NULL AND hidden_at IS NULL
+            AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4"#,
+            commented,
+            before_created_at,
+            before_id,
+            limit
+        )
+        .fetch_all(conn)
+        .await?;
+
         Ok(comments)
     }
 
+    /// Returns the comments and replies written by the user, the oldest first. Comments hidden
+    /// by the moderation are only returned with include_hidden, meant for the admins
+    #[tracing::instrument(name = "comment.get_comments_by_commentator", skip_all)]
     pub async fn get_comments_by_commentator(
         &self,
         commentator: String,
+        include_hidden: bool,
         pagination: Pagination,
         conn: &PgPool,
     ) -> Result<Vec<CommentResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("comment", "get_comments_by_commentator");
+
         let comments = sqlx::query_as!(
             CommentResponse,
-            r#"SELECT * FROM comments WHERE commentator = $1 LIMIT $2 OFFSET $3"#,
+            r#"SELECT id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version
+            FROM comments WHERE commentator = $1 AND ($2 OR hidden_at IS NULL)
+            ORDER BY created_at, id
+            LIMIT $3 OFFSET $4"#,
             commentator as String,
+            include_hidden,
             pagination.per_page as i64,
             (pagination.page - 1) * pagination.per_page as i64
         )
@@ -502,28 +1795,106 @@ impl CommentRepository {
         Ok(comments)
     }
 
+    /// Updates the text of the comment as long as it's still in the given version.
+    /// Top level comments are verified again
+    #[tracing::instrument(name = "comment.update_comment", skip_all)]
     pub async fn update_comment(
         &self,
-        comment: Comment,
+        id: Uuid,
+        comment: CommentUpdate,
+        version: i32,
         conn: &PgPool,
-    ) -> Result<CommentResponse, sqlx::Error> {
+    ) -> Result<CommentResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("comment", "update_comment");
+
+        let current = self.get_comment(id, true, conn).await?;
+
+        if current.version != version {
+            return Err(outdated_or_missing(true));
+        }
+
+        let verified = match current.parent_id {
+            Some(_) => false,
+            None => verify_review(&current.commentator, &current.commented, conn).await?,
+        };
+
         let comment = sqlx::query_as!(
             CommentResponse,
             r#"UPDATE comments
             SET
             comment = $1,
-            updated_at = $2
-            WHERE commentator = $3
-            AND commented = $4
-            RETURNING commentator, commented, comment, created_at, updated_at"#,
+            updated_at = $2,
+            verified = $4
+            WHERE id = $3
+            AND version = $5
+            RETURNING id, parent_id, commentator, commented, comment, created_at, updated_at, hidden_at IS NOT NULL as "hidden!", verified, version"#,
             comment.comment,
             chrono::Utc::now(),
-            comment.commentator,
-            comment.commented
+            id,
+            verified,
+            version
         )
-        .fetch_one(conn)
+        .fetch_optional(conn)
         .await?;
 
-        Ok(comment)
+        // Another update could have been applied after reading the comment
+        match comment {
+            Some(comment) => Ok(comment),
+            None => Err(outdated_or_missing(true)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TableStatistics {
+    pub table: String,
+    pub rows: i64,
+    pub total_bytes: i64,
+}
+
+#[derive(Default)]
+pub struct StatisticsRepository {}
+
+impl StatisticsRepository {
+    pub fn new() -> Self {
+        StatisticsRepository {}
+    }
+
+    /// Returns the exact row count and the size on disk of every table of the public schema
+    #[tracing::instrument(name = "statistics.get_table_statistics", skip_all)]
+    pub async fn get_table_statistics(
+        &self,
+        conn: &PgPool,
+    ) -> Result<Vec<TableStatistics>, sqlx::Error> {
+        let _timer = metrics::query_timer("statistics", "get_table_statistics");
+
+        let tables = sqlx::query!(
+            r#"SELECT relname as "table!", pg_total_relation_size(relid) as "total_bytes!"
+            FROM pg_stat_user_tables
+            WHERE schemaname = 'public'
+            ORDER BY relname"#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut statistics = Vec::with_capacity(tables.len());
+
+        for table in tables {
+            // Table names come from the catalog, quoting them keeps the statement safe
+            let rows: i64 = sqlx::query_scalar(&format!(
+                r#"SELECT count(*) FROM "{}""#,
+                table.table.replace('"', "\"\"")
+            ))
+            .fetch_one(conn)
+            .await?;
+
+            statistics.push(TableStatistics {
+                table: table.table,
+                rows,
+                total_bytes: table.total_bytes,
+            });
+        }
+
+        Ok(statistics)
     }
 }
@@ -0,0 +1,663 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    error::Error,
+};
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use online_market_model::{LocationPoint, LocationVisibility, Roles, UserLocation, UserResponse};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgPool};
+use utoipa::IntoParams;
+
+use crate::{errors::InvalidInput, metrics};
+
+/// Longest window of time a trajectory can be asked for
+const MAX_TRAJECTORY_DAYS: i64 = 31;
+const DEFAULT_TRAJECTORY_POINTS: usize = 500;
+const MIN_TRAJECTORY_POINTS: usize = 2;
+const MAX_TRAJECTORY_POINTS: usize = 5000;
+/// Days after today whose partition of the history is created in advance
+const PARTITION_DAYS_AHEAD: i32 = 7;
+const PARTITION_PREFIX: &str = "location_history_";
+/// Meters in a degree of latitude
+pub(crate) const METERS_PER_DEGREE: f64 = 111_320.0;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const DEFAULT_NEARBY_RADIUS_KM: f64 = 5.0;
+const MAX_NEARBY_RADIUS_KM: f64 = 50.0;
+const MAX_NEARBY_USERS: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct TrajectoryRequest {
+    /// Start of the window of time, RFC 3339
+    pub from: DateTime<Utc>,
+    /// End of the window of time, RFC 3339
+    pub to: DateTime<Utc>,
+    /// Most points returned, 500 by default. Longer tracks are simplified keeping their shape
+    pub max_points: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct NearbyRequest {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Distance from the point in kilometers, 5 by default and up to 50
+    pub radius_km: Option<f64>,
+    /// Only sellers when true
+    pub sellers_only: Option<bool>,
+}
+
+/// Result of a purge of the location history
+#[derive(Debug)]
+pub struct PurgeReport {
+    /// Partitions of whole days dropped
+    pub dropped_partitions: usize,
+    /// Points deleted from the partitions that were kept
+    pub deleted_points: u64,
+}
+
+/// Appends the location to the history of its user
+pub(crate) async fn record(
+    user_location: &UserLocation,
+    conn: &mut PgConnection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO location_history (user_id, latitude, longitude) VALUES ($1, $2, $3)",
+        user_location.dni,
+        user_location.latitude,
+        user_location.longitude
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Quotes an identifier to use it in a statement, whatever characters it has
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Returns the day covered by a partition of the history, None for the default partition
+fn partition_day(name: &str) -> Option<NaiveDate> {
+    let day = name.strip_prefix(PARTITION_PREFIX)?;
+
+    // Only the digits of the date, so the name is safe to use in a statement
+    if day.len() != 8 || !day.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    NaiveDate::parse_from_str(day, "%Y%m%d").ok()
+}
+
+/// Point of a track projected on a plane, in degrees of latitude
+#[derive(Clone, Copy)]
+struct PlanePoint {
+    x: f64,
+    y: f64,
+}
+
+/// Distance from the point to the segment between start and end
+fn segment_distance(point: PlanePoint, start: PlanePoint, end: PlanePoint) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = dx * dx + dy * dy;
+
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((point.x - start.x) * dx + (point.y - start.y) * dy) / length).clamp(0.0, 1.0)
+    };
+
+    let (x, y) = (start.x + t * dx, start.y + t * dy);
+
+    ((point.x - x).powi(2) + (point.y - y).powi(2)).sqrt()
+}
+
+/// Point of a segment of the simplified track that is farthest from it
+struct Farthest {
+    distance: f64,
+    index: usize,
+    start: usize,
+    end: usize,
+}
+
+impl PartialEq for Farthest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Farthest {}
+
+impl PartialOrd for Farthest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Farthest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance)
+    }
+}
+
+fn farthest(points: &[PlanePoint], start: usize, end: usize) -> Option<Farthest> {
+    (start + 1..end)
+        .map(|index| Farthest {
+            distance: segment_distance(points[index], points[start], points[end]),
+            index,
+            start,
+            end,
+        })
+        .max()
+}
+
+/// Returns at most max_points of the track keeping its shape.
+///
+/// It's Douglas-Peucker by number of points: the track starts as its first and last points and
+/// the point farthest from it is added until there are max_points
+fn simplify(points: Vec<LocationPoint>, max_points: usize) -> Vec<LocationPoint> {
+    if points.len() <= max_points {
+        return points;
+    }
+
+    // Degrees of longitude get shorter away from the equator
+    let scale = (points
+        .iter()
+        .map(|point| point.latitude as f64)
+        .sum::<f64>()
+        / points.len() as f64)
+        .to_radians()
+        .cos();
+
+    let plane: Vec<PlanePoint> = points
+        .iter()
+        .map(|point| PlanePoint {
+            x: point.longitude as f64 * scale,
+            y: point.latitude as f64,
+        })
+        .collect();
+
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[last] = true;
+
+    let mut candidates = BinaryHeap::new();
+    candidates.extend(farthest(&plane, 0, last));
+
+    for _ in MIN_TRAJECTORY_POINTS..max_points {
+        let Some(candidate) = candidates.pop() else {
+            break;
+        };
+
+        keep[candidate.index] = true;
+        candidates.extend(farthest(&plane, candidate.start, candidate.index));
+        candidates.extend(farthest(&plane, candidate.index, candidate.end));
+    }
+
+    points
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(point))
+        .collect()
+}
+
+/// Returns the center of the cell of a grid of grid_meters that contains the coordinates.
+///
+/// Every location of a cell gives the same center, so reading the location again and again
+/// doesn't reveal more than the cell
+pub fn snap_to_grid(latitude: f32, longitude: f32, grid_meters: i32) -> (f32, f32) {
+    let step = grid_meters as f64 / METERS_PER_DEGREE;
+
+    let latitude = ((latitude as f64 / step).floor() + 0.5) * step;
+
+    // Cells keep their width in meters away from the equator. The width depends on the center
+    // of the row, so every location of the row agrees on it
+    let longitude_step = step / latitude.to_radians().cos().max(0.01);
+    let longitude = ((longitude as f64 / longitude_step).floor() + 0.5) * longitude_step;
+
+    (
+        latitude.clamp(-90.0, 90.0) as f32,
+        longitude.clamp(-180.0, 180.0) as f32,
+    )
+}
+
+/// Returns if the coordinates are on the Earth
+pub(crate) fn valid_coordinates(latitude: f64, longitude: f64) -> bool {
+    (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
+}
+
+/// Distance in kilometers between two coordinates, with the haversine formula
+pub(crate) fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (latitude, longitude) = (from.0.to_radians(), from.1.to_radians());
+    let (other_latitude, other_longitude) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((other_latitude - latitude) / 2.0).sin().powi(2)
+        + latitude.cos()
+            * other_latitude.cos()
+            * ((other_longitude - longitude) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// How much of the location of a user a viewer can see
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocationAccess {
+    None,
+    Approximate,
+    Exact,
+}
+
+/// Rules to show the locations of the users to whoever asks for them, loaded once per request
+pub struct LocationPolicy {
+    grid_meters: i32,
+    /// Dni of the viewer, None when they aren't authenticated
+    viewer: Option<String>,
+    admin: bool,
+    /// Users the viewer has a pending or confirmed booking with, as customer
+    booked_sellers: HashSet<String>,
+}
+
+impl LocationPolicy {
+    pub fn access(&self, dni: &str, visibility: LocationVisibility) -> LocationAccess {
+        if self.admin || self.viewer.as_deref() == Some(dni) {
+            return LocationAccess::Exact;
+        }
+
+        match visibility {
+            LocationVisibility::Hidden => LocationAccess::None,
+            LocationVisibility::Approximate => LocationAccess::Approximate,
+            LocationVisibility::ExactToBookedCustomers if self.booked_sellers.contains(dni) => {
+                LocationAccess::Exact
+            }
+            LocationVisibility::ExactToBookedCustomers => LocationAccess::Approximate,
+        }
+    }
+
+    /// Returns the coordinates of the user the viewer can see, None when they can't see them
+    pub fn coordinates(
+        &self,
+        dni: &str,
+        visibility: LocationVisibility,
+        latitude: f32,
+        longitude: f32,
+    ) -> Option<(f32, f32)> {
+        match self.access(dni, visibility) {
+            LocationAccess::None => None,
+            LocationAccess::Approximate => {
+                Some(snap_to_grid(latitude, longitude, self.grid_meters))
+            }
+            LocationAccess::Exact => Some((latitude, longitude)),
+        }
+    }
+
+    /// Leaves in the user the location the viewer can see
+    pub fn apply(&self, user: &mut UserResponse) {
+        let coordinates = match (user.latitude, user.longitude) {
+            (Some(latitude), Some(longitude)) => {
+                self.coordinates(&user.dni, user.location_visibility, latitude, longitude)
+            }
+            _ => None,
+        };
+
+        user.latitude = coordinates.map(|coordinates| coordinates.0);
+        user.longitude = coordinates.map(|coordinates| coordinates.1);
+    }
+}
+
+#[derive(Default)]
+pub struct LocationPrivacyRepository {}
+
+impl LocationPrivacyRepository {
+    pub fn new() -> Self {
+        LocationPrivacyRepository {}
+    }
+
+    /// Returns the rules to show the locations to the viewer, given by their dni and role.
+    /// Requests without a bearer token have no viewer
+    #[tracing::instrument(name = "location_privacy.policy", skip_all)]
+    pub async fn policy(
+        &self,
+        viewer: Option<(&str, &Roles)>,
+        conn: &PgPool,
+    ) -> Result<LocationPolicy, sqlx::Error> {
+        let _timer = metrics::query_timer("location_privacy", "policy");
+
+        let grid_meters = sqlx::query_scalar!("SELECT location_grid_meters FROM settings")
+            .fetch_one(conn)
+            .await?;
+
+        let admin = matches!(viewer, Some((_, Roles::Admin)));
+
+        let booked_sellers = match viewer {
+            Some((dni, _)) if !admin => sqlx::query_scalar!(
+                r#"SELECT DISTINCT seller_id FROM bookings
+                WHERE customer_id = $1 AND status IN ('pending', 'confirmed')"#,
+                dni
+            )
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .collect(),
+            _ => HashSet::new(),
+        };
+
+        Ok(LocationPolicy {
+            grid_meters,
+            viewer: viewer.map(|(dni, _)| dni.to_owned()),
+            admin,
+            booked_sellers,
+        })
+    }
+
+    /// Returns the users whose location, as the viewer can see it, is within the radius of the
+    /// point, nearest first
+    #[tracing::instrument(name = "location_privacy.nearby", skip_all)]
+    pub async fn nearby(
+        &self,
+        request: NearbyRequest,
+        policy: &LocationPolicy,
+        conn: &PgPool,
+    ) -> Result<Vec<UserResponse>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("location_privacy", "nearby");
+
+        if !valid_coordinates(request.latitude, request.longitude) {
+            return Err(Box::new(InvalidInput::new(
+                "THE COORDINATES ARE OUT OF RANGE",
+            )));
+        }
+
+        let radius_km = request.radius_km.unwrap_or(DEFAULT_NEARBY_RADIUS_KM);
+        if !(radius_km > 0.0 && radius_km <= MAX_NEARBY_RADIUS_KM) {
+            return Err(Box::new(InvalidInput::new(
+                "radius_km MUST BE GREATER THAN 0 AND UP TO 50",
+            )));
+        }
+
+        // The approximate locations can be up to a cell away from the exact ones, so the box
+        // of candidates grows by a cell on every side
+        let margin_km = radius_km + policy.grid_meters as f64 / 1000.0;
+        let latitude_delta = margin_km * 1000.0 / METERS_PER_DEGREE;
+        let longitude_delta = latitude_delta / request.latitude.to_radians().cos().max(0.01);
+
+        let candidates = sqlx::query_as!(
+            UserResponse,
+            r#"SELECT id, dni, email, password, name, date_of_birth, registered_at, is_seller, updated_at, latitude, longitude, contact_number, category_id, rol as "rol: Roles", seller_paused_at, location_visibility as "location_visibility: LocationVisibility", version FROM users
+            WHERE latitude BETWEEN $1 AND $2 AND longitude BETWEEN $3 AND $4
+            AND (NOT $5 OR (is_seller AND seller_paused_at IS NULL))"#,
+            (request.latitude - latitude_delta) as f32,
+            (request.latitude + latitude_delta) as f32,
+            (request.longitude - longitude_delta) as f32,
+            (request.longitude + longitude_delta) as f32,
+            request.sellers_only.unwrap_or(false)
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let point = (request.latitude, request.longitude);
+
+        let mut users: Vec<(f64, UserResponse)> = candidates
+            .into_iter()
+            .filter_map(|mut user| {
+                policy.apply(&mut user);
+
+                let distance = distance_km(point, (user.latitude? as f64, user.longitude? as f64));
+
+                (distance <= radius_km).then_some((distance, user))
+            })
+            .collect();
+
+        users.sort_by(|a, b| a.0.total_cmp(&b.0));
+        users.truncate(MAX_NEARBY_USERS);
+
+        Ok(users.into_iter().map(|(_, user)| user).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct LocationHistoryRepository {}
+
+impl LocationHistoryRepository {
+    pub fn new() -> Self {
+        LocationHistoryRepository {}
+    }
+
+    /// Returns the locations of the user in the window of time, oldest first, simplified when
+    /// there are more than max_points
+    #[tracing::instrument(name = "location.trajectory", skip_all)]
+    pub async fn trajectory(
+        &self,
+        dni: String,
+        request: TrajectoryRequest,
+        conn: &PgPool,
+    ) -> Result<Vec<LocationPoint>, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("location", "trajectory");
+
+        if request.to < request.from {
+            return Err(Box::new(InvalidInput::new("from MUST BE BEFORE to")));
+        }
+        if request.to - request.from > Duration::days(MAX_TRAJECTORY_DAYS) {
+            return Err(Box::new(InvalidInput::new(
+                "THE RANGE OF DATES CAN'T BE LONGER THAN 31 DAYS",
+            )));
+        }
+
+        let max_points = request.max_points.unwrap_or(DEFAULT_TRAJECTORY_POINTS);
+        if !(MIN_TRAJECTORY_POINTS..=MAX_TRAJECTORY_POINTS).contains(&max_points) {
+            return Err(Box::new(InvalidInput::new(
+                "max_points MUST BE BETWEEN 2 AND 5000",
+            )));
+        }
+
+        let points = sqlx::query_as!(
+            LocationPoint,
+            r#"SELECT latitude, longitude, recorded_at FROM location_history
+            WHERE user_id = $1 AND recorded_at BETWEEN $2 AND $3
+            ORDER BY recorded_at"#,
+            dni,
+            request.from,
+            request.to
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(simplify(points, max_points))
+    }
+
+    /// Removes the locations older than location_retention_days and creates the partitions of
+    /// the next days.
+    ///
+    /// Partitions whose whole day is older than the retention are dropped, and the older points
+    /// left in the others, like the default partition, are deleted
+    #[tracing::instrument(name = "location.purge", skip_all)]
+    pub async fn purge(&self, conn: &PgPool) -> Result<PurgeReport, sqlx::Error> {
+        let _timer = metrics::query_timer("location", "purge");
+
+        let retention_days = sqlx::query_scalar!("SELECT location_retention_days FROM settings")
+            .fetch_one(conn)
+            .await?;
+
+        let cutoff = Utc::now() - Duration::days(retention_days as i64);
+
+        let partitions = sqlx::query_scalar!(
+            r#"SELECT c.relname::TEXT as "name!" FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            JOIN pg_class p ON p.oid = i.inhparent
+            WHERE p.relname = 'location_history'"#
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut dropped_partitions = 0;
+
+        for name in partitions {
+            let Some(day) = partition_day(&name) else {
+                continue;
+            };
+
+            let end = (day + Duration::days(1)).and_time(NaiveTime::MIN).and_utc();
+
+            if end <= cutoff {
+                sqlx::query(&format!("DROP TABLE {}", quote_ident(&name)))
+                    .execute(conn)
+                    .await?;
+
+                dropped_partitions += 1;
+            }
+        }
+
+        let deleted = sqlx::query!(
+            "DELETE FROM location_history WHERE recorded_at < $1",
+            cutoff
+        )
+        .execute(conn)
+        .await?;
+
+        sqlx::query(
+            r#"SELECT create_location_history_partition((now() AT TIME ZONE 'UTC')::date + days)
+            FROM generate_series(0, $1) days"#,
+        )
+        .bind(PARTITION_DAYS_AHEAD)
+        .execute(conn)
+        .await?;
+
+        Ok(PurgeReport {
+            dropped_partitions,
+            deleted_points: deleted.rows_affected(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Track with a point every minute at the given coordinates
+    fn track(coordinates: &[(f32, f32)]) -> Vec<LocationPoint> {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+
+        coordinates
+            .iter()
+            .enumerate()
+            .map(|(minute, &(latitude, longitude))| LocationPoint {
+                latitude,
+                longitude,
+                recorded_at: start + Duration::minutes(minute as i64),
+            })
+            .collect()
+    }
+
+    fn coordinates(points: &[LocationPoint]) -> Vec<(f32, f32)> {
+        points
+            .iter()
+            .map(|point| (point.latitude, point.longitude))
+            .collect()
+    }
+
+    #[test]
+    fn short_tracks_are_returned_as_they_are() {
+        let points = [(4.60, -74.08), (4.61, -74.07), (4.62, -74.09)];
+
+        assert_eq!(coordinates(&simplify(track(&points), 3)), points);
+        assert!(simplify(Vec::new(), 2).is_empty());
+    }
+
+    #[test]
+    fn endpoints_are_always_kept() {
+        let points: Vec<(f32, f32)> = (0..10).map(|i| (4.6 + i as f32 * 0.01, -74.0)).collect();
+
+        let simplified = simplify(track(&points), 2);
+
+        assert_eq!(coordinates(&simplified), vec![points[0], points[9]]);
+    }
+
+    #[test]
+    fn collinear_tracks_keep_max_points_in_order() {
+        let points: Vec<(f32, f32)> = (0..10).map(|i| (4.6 + i as f32 * 0.01, -74.0)).collect();
+
+        let simplified = simplify(track(&points), 4);
+
+        assert_eq!(simplified.len(), 4);
+        assert_eq!(simplified[0].latitude, points[0].0);
+        assert_eq!(simplified[3].latitude, points[9].0);
+        assert!(simplified
+            .windows(2)
+            .all(|pair| pair[0].recorded_at < pair[1].recorded_at));
+    }
+
+    #[test]
+    fn corners_are_kept_before_the_points_of_a_straight_line() {
+        // An L: north along a meridian and then east along a parallel
+        let mut points: Vec<(f32, f32)> = (0..5).map(|i| (4.6 + i as f32 * 0.01, -74.0)).collect();
+        points.extend((1..5).map(|i| (4.64, -74.0 + i as f32 * 0.01)));
+
+        let simplified = simplify(track(&points), 3);
+
+        assert_eq!(
+            coordinates(&simplified),
+            vec![points[0], (4.64, -74.0), points[8]]
+        );
+    }
+
+    #[test]
+    fn locations_of_the_same_cell_snap_to_the_same_center() {
+        let center = snap_to_grid(4.6097, -74.0817, 1000);
+        // About 100 meters in every direction, well inside the cell of 1 km
+        let offset = 0.0009;
+
+        for (latitude, longitude) in [
+            (center.0 + offset, center.1 + offset),
+            (center.0 - offset, center.1 - offset),
+            (center.0 + offset, center.1 - offset),
+        ] {
+            assert_eq!(snap_to_grid(latitude, longitude, 1000), center);
+        }
+
+        assert_ne!(snap_to_grid(center.0 + 0.01, center.1, 1000), center);
+        assert!(distance_km((4.6097, -74.0817), to_f64(center)) < 1.0);
+    }
+
+    #[test]
+    fn snapping_is_idempotent() {
+        for (latitude, longitude) in [(4.6097, -74.0817), (-33.45, -70.66), (64.14, -21.94)] {
+            let center = snap_to_grid(latitude, longitude, 500);
+
+            assert_eq!(snap_to_grid(center.0, center.1, 500), center);
+        }
+    }
+
+    #[test]
+    fn snapped_locations_stay_on_the_earth() {
+        let (latitude, longitude) = snap_to_grid(90.0, 180.0, 5000);
+
+        assert!(valid_coordinates(latitude as f64, longitude as f64));
+    }
+
+    #[test]
+    fn partition_names_are_quoted() {
+        assert_eq!(
+            quote_ident("location_history_20231120"),
+            "\"location_history_20231120\""
+        );
+        assert_eq!(
+            quote_ident("a\"; DROP TABLE users; --"),
+            "\"a\"\"; DROP TABLE users; --\""
+        );
+    }
+
+    #[test]
+    fn only_dated_partitions_have_a_day() {
+        assert_eq!(
+            partition_day("location_history_20231120"),
+            NaiveDate::from_ymd_opt(2023, 11, 20)
+        );
+        assert_eq!(partition_day("location_history_default"), None);
+        assert_eq!(partition_day("location_history_2023112"), None);
+        assert_eq!(partition_day("location_history_20231340"), None);
+    }
+
+    fn to_f64((latitude, longitude): (f32, f32)) -> (f64, f64) {
+        (latitude as f64, longitude as f64)
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Histogram,
+    HistogramVec, IntCounterVec, IntGaugeVec,
+};
+use sqlx::PgPool;
+
+/// Duration of every repository method, labeled by repository and method name
+pub static DB_QUERY_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "db_query_duration_seconds",
+        "Duration of repository queries in seconds",
+        &["repository", "method"]
+    )
+    .unwrap()
+});
+
+/// Connections of the PgPool, labeled by state (active, idle, max)
+pub static DB_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "db_pool_connections",
+        "Connections of the database pool by state",
+        &["state"]
+    )
+    .unwrap()
+});
+
+/// Lookups of the in-process caches, labeled by cache and result (hit or miss)
+pub static CACHE_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "cache_requests_total",
+        "Lookups of the in-process caches",
+        &["cache", "result"]
+    )
+    .unwrap()
+});
+
+/// Measures a repository method, see [`query_timer`]
+pub struct QueryTimer {
+    histogram: Histogram,
+    start: Instant,
+    repository: &'static str,
+    method: &'static str,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let seconds = self.start.elapsed().as_secs_f64();
+
+        self.histogram.observe(seconds);
+
+        // Logged inside the span of the method, so it carries the id of the request
+        tracing::debug!(
+            repository = self.repository,
+            method = self.method,
+            duration_ms = seconds * 1000.0,
+            "repository call finished"
+        );
+    }
+}
+
+/// Returns a timer that records the duration of a repository method in the histogram and the
+/// logs when dropped
+///
+/// # Argument
+///
+/// * repository - Name of the repository, for example "user"
+/// * method - Name of the method being measured, for example "get_by_dni"
+///
+pub fn query_timer(repository: &'static str, method: &'static str) -> QueryTimer {
+    QueryTimer {
+        histogram: DB_QUERY_DURATION_SECONDS.with_label_values(&[repository, method]),
+        start: Instant::now(),
+        repository,
+        method,
+    }
+}
+
+/// Updates the pool gauges with the current state of the PgPool
+///
+/// # Argument
+///
+/// * conn - Pool whose utilization will be recorded
+///
+pub fn observe_pool(conn: &PgPool) {
+    let size = conn.size() as i64;
+    let idle = conn.num_idle() as i64;
+
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["active"])
+        .set(size - idle);
+    DB_POOL_CONNECTIONS.with_label_values(&["idle"]).set(idle);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["max"])
+        .set(conn.options().get_max_connections() as i64);
+}
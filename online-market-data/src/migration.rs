@@ -0,0 +1,80 @@
+use sqlx::{migrate::Migrator, PgPool};
+
+/// Migrations of the online-market-data/migrations folder embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// The migration was applied but its file changed afterwards
+    ChecksumMismatch,
+    /// The migration started but did not finish successfully
+    Failed,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+/// Applies every pending migration
+///
+/// # Argument
+///
+/// * conn - Pool of the database that will be migrated
+///
+pub async fn run(conn: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    MIGRATOR.run(conn).await
+}
+
+/// Returns the state of every embedded migration in the database
+///
+/// # Argument
+///
+/// * conn - Pool of the database that will be inspected
+///
+pub async fn status(conn: &PgPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    // The migrations table doesn't exist until the first migration is applied
+    let table_exists: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(conn)
+            .await?;
+
+    let applied: Vec<(i64, Vec<u8>, bool)> = if table_exists {
+        sqlx::query_as("SELECT version, checksum, success FROM _sqlx_migrations")
+            .fetch_all(conn)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let status = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let applied_migration = applied
+                .iter()
+                .find(|(version, _, _)| *version == migration.version);
+
+            let state = match applied_migration {
+                None => MigrationState::Pending,
+                Some((_, _, false)) => MigrationState::Failed,
+                Some((_, checksum, true)) if *checksum != *migration.checksum => {
+                    MigrationState::ChecksumMismatch
+                }
+                Some(_) => MigrationState::Applied,
+            };
+
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+            }
+        })
+        .collect();
+
+    Ok(status)
+}
@@ -0,0 +1,222 @@
+use std::error::Error;
+
+use online_market_model::{CommentReportResponse, ModerationAction, ModerationDecision};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{Conflict, InvalidInput},
+    metrics, Pagination,
+};
+
+/// Returns an error if the reason is empty or doesn't fit in the table
+fn validate_reason(reason: &str) -> Result<(), InvalidInput> {
+    let length = reason.trim().chars().count();
+
+    if length == 0 || length > 200 {
+        return Err(InvalidInput::new(
+            "THE REASON MUST HAVE BETWEEN 1 AND 200 CHARACTERS",
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct ModerationRepository {}
+
+impl ModerationRepository {
+    pub fn new() -> Self {
+        ModerationRepository {}
+    }
+
+    /// Reports a comment. A user can have only one open report of the same comment
+    #[tracing::instrument(name = "moderation.report", skip_all)]
+    pub async fn report(
+        &self,
+        reporter: String,
+        comment_id: Uuid,
+        reason: String,
+        conn: &PgPool,
+    ) -> Result<CommentReportResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("moderation", "report");
+
+        validate_reason(&reason)?;
+
+        let commentator = sqlx::query_scalar!(
+            r#"SELECT commentator FROM comments WHERE id = $1"#,
+            comment_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        if reporter == commentator {
+            return Err(Box::new(Conflict::new("YOU CAN'T REPORT YOUR OWN COMMENT")));
+        }
+
+        let result = sqlx::query_scalar!(
+            r#"INSERT INTO comment_reports (comment_id, reporter, reason)
+            VALUES ($1, $2, $3)
+            RETURNING id"#,
+            comment_id,
+            reporter,
+            reason.trim()
+        )
+        .fetch_one(conn)
+        .await;
+
+        let id = match result {
+            Ok(id) => id,
+            Err(sqlx::Error::Database(error)) if error.is_foreign_key_violation() => {
+                return Err(Box::new(sqlx::Error::RowNotFound));
+            }
+            Err(sqlx::Error::Database(error)) if error.is_unique_violation() => {
+                return Err(Box::new(Conflict::new("YOU ALREADY REPORTED THIS COMMENT")));
+            }
+            Err(error) => return Err(Box::new(error)),
+        };
+
+        Ok(self.get_by_id(id, conn).await?)
+    }
+
+    #[tracing::instrument(name = "moderation.get_by_id", skip_all)]
+    pub async fn get_by_id(
+        &self,
+        id: Uuid,
+        conn: &PgPool,
+    ) -> Result<CommentReportResponse, sqlx::Error> {
+        let _timer = metrics::query_timer("moderation", "get_by_id");
+
+        let report = sqlx::query_as!(
+            CommentReportResponse,
+            r#"SELECT r.id, r.comment_id, c.commentator, c.commented, c.comment, c.hidden_at IS NOT NULL as "comment_hidden!",
+            r.reporter, r.reason, r.created_at, r.action as "action: ModerationAction", r.resolved_by, r.resolved_at
+            FROM comment_reports r
+            JOIN comments c ON c.id = r.comment_id
+            WHERE r.id = $1"#,
+            id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(report)
+    }
+
+    /// Returns the open reports, the oldest first
+    #[tracing::instrument(name = "moderation.get_queue", skip_all)]
+    pub async fn get_queue(
+        &self,
+        pagination: Pagination,
+        conn: &PgPool,
+    ) -> Result<Vec<CommentReportResponse>, sqlx::Error> {
+        let _timer = metrics::query_timer("moderation", "get_queue");
+
+        let reports = sqlx::query_as!(
+            CommentReportResponse,
+            r#"SELECT r.id, r.comment_id, c.commentator, c.commented, c.comment, c.hidden_at IS NOT NULL as "comment_hidden!",
+            r.reporter, r.reason, r.created_at, r.action as "action: ModerationAction", r.resolved_by, r.resolved_at
+            FROM comment_reports r
+            JOIN comments c ON c.id = r.comment_id
+            WHERE r.resolved_at IS NULL
+            ORDER BY r.created_at, r.id
+            LIMIT $1 OFFSET $2"#,
+            pagination.per_page as i64,
+            (pagination.page - 1) * pagination.per_page as i64
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(reports)
+    }
+
+    /// Applies the decision of an admin to a report. Dismissing only closes the report,
+    /// the other actions change the comment and resolve every open report of it.
+    /// Resolved reports keep their decision: restoring through one of them only shows the
+    /// comment again, and the audit log records who did it
+    #[tracing::instrument(name = "moderation.resolve", skip_all)]
+    pub async fn resolve(
+        &self,
+        id: Uuid,
+        admin: String,
+        decision: ModerationDecision,
+        conn: &PgPool,
+    ) -> Result<CommentReportResponse, Box<dyn Error + Send + Sync>> {
+        let _timer = metrics::query_timer("moderation", "resolve");
+
+        if let Some(reason) = &decision.reason {
+            validate_reason(reason)?;
+        }
+
+        let mut tx = conn.begin().await?;
+
+        let report = sqlx::query!(
+            r#"SELECT r.comment_id, c.commentator, r.reason, r.resolved_at, c.hidden_at
+            FROM comment_reports r
+            JOIN comments c ON c.id = r.comment_id
+            WHERE r.id = $1
+            FOR UPDATE"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Hidden comments can be restored from any of their reports, even the resolved ones
+        if decision.action == ModerationAction::Restore {
+            if report.hidden_at.is_none() {
+                return Err(Box::new(Conflict::new("THE COMMENT IS NOT HIDDEN")));
+            }
+        } else if report.resolved_at.is_some() {
+            return Err(Box::new(Conflict::new("THE REPORT IS ALREADY RESOLVED")));
+        }
+
+        match decision.action {
+            ModerationAction::Hide => {
+                sqlx::query!(
+                    r#"UPDATE comments SET hidden_at = now() WHERE id = $1"#,
+                    report.comment_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            ModerationAction::Restore => {
+                sqlx::query!(
+                    r#"UPDATE comments SET hidden_at = NULL WHERE id = $1"#,
+                    report.comment_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            ModerationAction::Warn => {
+                sqlx::query!(
+                    r#"INSERT INTO user_warnings (user_id, report_id, reason, issued_by)
+                    VALUES ($1, $2, $3, $4)"#,
+                    report.commentator,
+                    id,
+                    decision.reason.as_deref().unwrap_or(&report.reason).trim(),
+                    admin
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            ModerationAction::Dismiss => {}
+        }
+
+        sqlx::query!(
+            r#"UPDATE comment_reports
+            SET action = $1, resolved_by = $2, resolved_at = now()
+            WHERE resolved_at IS NULL
+            AND (id = $3 OR ($4 AND comment_id = $5))"#,
+            decision.action as ModerationAction,
+            admin,
+            id,
+            decision.action != ModerationAction::Dismiss,
+            report.comment_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(self.get_by_id(id, conn).await?)
+    }
+}
@@ -0,0 +1,57 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+/// Returns the Argon2 hash of the password in the PHC string format, which is what the users
+/// table stores
+pub fn hash(password: &str) -> Result<String, sqlx::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|error| sqlx::Error::Io(std::io::Error::other(error)))
+}
+
+/// Checks the password against the stored one. Users saved before the passwords were hashed
+/// still have it in plain text, those are compared as they are and should be rehashed
+pub fn verify(password: &str, stored: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => constant_time_eq(password.as_bytes(), stored.as_bytes()),
+    }
+}
+
+/// Whether the stored password is still in plain text
+pub fn needs_rehash(stored: &str) -> bool {
+    PasswordHash::new(stored).is_err()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashed_password_verifies_only_the_original() {
+        let stored = hash("hunter2").unwrap();
+
+        assert_ne!(stored, "hunter2");
+        assert!(!needs_rehash(&stored));
+        assert!(verify("hunter2", &stored));
+        assert!(!verify("hunter3", &stored));
+    }
+
+    #[test]
+    fn plain_text_passwords_are_compared_and_flagged() {
+        assert!(needs_rehash("hunter2"));
+        assert!(verify("hunter2", "hunter2"));
+        assert!(!verify("hunter", "hunter2"));
+    }
+}
@@ -0,0 +1,68 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::metrics;
+
+/// Computes again the reputation of the rated user, or of every rated user when none is given.
+///
+/// Each rate weighs half every reputation_half_life_days since it was last changed, and the
+/// weighted average is pulled towards reputation_prior_mean as if the user had
+/// reputation_prior_weight more rates of that value
+pub(crate) async fn refresh_reputations(
+    user: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"INSERT INTO reputations (user_id, score, average_rate, rates_count, refreshed_at)
+        SELECT
+            r.rated,
+            (s.reputation_prior_weight * s.reputation_prior_mean + SUM(r.weight * r.rate))
+                / (s.reputation_prior_weight + SUM(r.weight)),
+            AVG(r.rate),
+            COUNT(*),
+            now()
+        FROM settings s
+        CROSS JOIN LATERAL (
+            SELECT
+                rated,
+                rate::DOUBLE PRECISION as rate,
+                power(
+                    0.5::DOUBLE PRECISION,
+                    EXTRACT(EPOCH FROM now() - COALESCE(updated_at, created_at))::DOUBLE PRECISION
+                        / 86400 / s.reputation_half_life_days
+                ) as weight
+            FROM rates
+            WHERE $1::VARCHAR IS NULL OR rated = $1
+        ) r
+        GROUP BY r.rated, s.reputation_prior_weight, s.reputation_prior_mean
+        ON CONFLICT (user_id) DO UPDATE SET
+            score = EXCLUDED.score,
+            average_rate = EXCLUDED.average_rate,
+            rates_count = EXCLUDED.rates_count,
+            refreshed_at = EXCLUDED.refreshed_at"#,
+        user
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[derive(Default)]
+pub struct ReputationRepository {}
+
+impl ReputationRepository {
+    pub fn new() -> Self {
+        ReputationRepository {}
+    }
+
+    /// Computes again the reputation of every rated user, so the older rates lose weight.
+    /// Returns the number of reputations refreshed
+    #[tracing::instrument(name = "reputation.refresh_all", skip_all)]
+    pub async fn refresh_all(&self, conn: &PgPool) -> Result<u64, sqlx::Error> {
+        let _timer = metrics::query_timer("reputation", "refresh_all");
+
+        let mut conn = conn.acquire().await?;
+
+        refresh_reputations(None, &mut conn).await
+    }
+}
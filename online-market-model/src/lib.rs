@@ -1,8 +1,11 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug, ToSchema)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[sqlx(type_name = "modality", rename_all = "lowercase")]
 pub enum Modality {
     Domicilio,
@@ -10,20 +13,118 @@ pub enum Modality {
     Hibrido,
 }
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug, ToSchema)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
 #[sqlx(type_name = "roles", rename_all = "lowercase")]
 pub enum Roles {
     Admin,
     User,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Languages of the messages and labels of the API
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Es,
+}
+
+impl Language {
+    /// Every supported language
+    pub const ALL: [Language; 2] = [Language::En, Language::Es];
+
+    /// ISO 639-1 code of the language
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Es => "es",
+        }
+    }
+
+    /// Returns the language of a language tag like "es" or "es-CO", None when it isn't supported
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split('-').next()?.trim();
+
+        Language::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(primary))
+    }
+}
+
+impl Modality {
+    /// Every modality, in the order they are listed to the users
+    pub const ALL: [Modality; 3] = [Modality::Domicilio, Modality::Presencial, Modality::Hibrido];
+
+    /// Human readable name of the modality
+    pub fn label(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (Modality::Domicilio, Language::En) => "At home",
+            (Modality::Domicilio, Language::Es) => "A domicilio",
+            (Modality::Presencial, Language::En) => "In person",
+            (Modality::Presencial, Language::Es) => "Presencial",
+            (Modality::Hibrido, Language::En) => "Hybrid",
+            (Modality::Hibrido, Language::Es) => "Híbrido",
+        }
+    }
+}
+
+impl Roles {
+    /// Every role
+    pub const ALL: [Roles; 2] = [Roles::Admin, Roles::User];
+
+    /// Human readable name of the role
+    pub fn label(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (Roles::Admin, Language::En) => "Administrator",
+            (Roles::Admin, Language::Es) => "Administrador",
+            (Roles::User, Language::En) => "User",
+            (Roles::User, Language::Es) => "Usuario",
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "weekday", rename_all = "lowercase")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Weekday::Monday,
+            chrono::Weekday::Tue => Weekday::Tuesday,
+            chrono::Weekday::Wed => Weekday::Wednesday,
+            chrono::Weekday::Thu => Weekday::Thursday,
+            chrono::Weekday::Fri => Weekday::Friday,
+            chrono::Weekday::Sat => Weekday::Saturday,
+            chrono::Weekday::Sun => Weekday::Sunday,
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "booking_status", rename_all = "lowercase")]
+pub enum BookingStatus {
+    Pending,
+    Confirmed,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct Location {
     pub lat: f64,
     pub lon: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct LocationResponse {
     pub lat: f64,
     pub lon: f64,
@@ -34,12 +135,47 @@ pub struct Category {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct CategoryResponse {
     pub id: i64,
     pub name: String,
 }
 
+/// Name of a category in a language
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CategoryLabelUpdate {
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CategoryLabel {
+    pub id: i64,
+    pub name: String,
+    /// Name of the category in the language, its name when it has no label in it
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ModalityLabel {
+    pub modality: Modality,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RoleLabel {
+    pub rol: Roles,
+    pub label: String,
+}
+
+/// Human readable names of the values of the market, to show them to the users
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct LabelsResponse {
+    pub language: Language,
+    pub modalities: Vec<ModalityLabel>,
+    pub roles: Vec<RoleLabel>,
+    pub categories: Vec<CategoryLabel>,
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct Rate {
     pub rater: String,
@@ -47,13 +183,28 @@ pub struct Rate {
     pub rate: f32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct RateResponse {
     pub rater: String,
     pub rated: String,
     pub rate: f32,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// The rater has a completed booking with the rated user
+    pub verified: bool,
+    /// Version of the rate, sent in the If-Match header to update it
+    pub version: i32,
+}
+
+/// Rates received by a user
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct RatingSummary {
+    pub dni: String,
+    pub rates_count: i64,
+    /// None when the user has no rates
+    pub average_rate: Option<f64>,
+    /// Rates whose rater has a completed booking with the user
+    pub verified_rates_count: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
@@ -63,20 +214,92 @@ pub struct Comment {
     pub comment: String,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Reply to a top level comment
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CommentReply {
+    pub commentator: String,
+    pub comment: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CommentUpdate {
+    pub comment: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct CommentResponse {
+    pub id: Uuid,
+    /// Id of the replied comment, None for top level comments
+    pub parent_id: Option<Uuid>,
     pub commentator: String,
     pub commented: String,
     pub comment: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Hidden comments are left out of the comments of the commented user
+    pub hidden: bool,
+    /// The commentator has a completed booking with the commented user. Replies are never verified
+    pub verified: bool,
+    /// Version of the comment, sent in the If-Match header to update it
+    pub version: i32,
+}
+
+/// Top level comment with its replies, the oldest first
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: CommentResponse,
+    pub replies: Vec<CommentResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CommentReport {
+    pub reason: String,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "moderation_action", rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Hides the comment and resolves its open reports
+    Hide,
+    /// Shows the comment again and resolves its open reports
+    Restore,
+    /// Closes the report without changing the comment
+    Dismiss,
+    /// Warns the author of the comment and resolves its open reports
+    Warn,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ModerationDecision {
+    pub action: ModerationAction,
+    /// Reason given to the author when warning, the one of the report by default
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CommentReportResponse {
+    pub id: Uuid,
+    pub comment_id: Uuid,
+    pub commentator: String,
+    pub commented: String,
+    pub comment: String,
+    pub comment_hidden: bool,
+    pub reporter: String,
+    pub reason: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// None while the report is waiting in the moderation queue
+    pub action: Option<ModerationAction>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct User {
     pub dni: String,
     pub email: String,
-    pub password: String,
+    #[schema(value_type = String, format = Password)]
+    pub password: Secret,
     pub name: String,
     pub date_of_birth: chrono::NaiveDate,
     pub is_seller: bool,
@@ -88,19 +311,81 @@ pub struct User {
     pub rol: Roles,
 }
 
-#[derive(Deserialize, Debug, Serialize)]
+/// Text that must never reach the logs, like a password or its hash. Debug and Display print
+/// [REDACTED] in its place, so deriving Debug on a struct that holds one is safe
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, PartialEq, Eq, Default)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Returns the text, to hash or verify it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Serialize, ToSchema)]
 pub struct UserLocation {
     pub dni: String,
     pub latitude: f32,
     pub longitude: f32,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Who can see the location of a user. The user and the admins always see it
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ToSchema)]
+#[sqlx(type_name = "location_visibility", rename_all = "snake_case")]
+pub enum LocationVisibility {
+    /// Nobody else sees the location
+    Hidden,
+    /// Everyone sees the location snapped to a grid of location_grid_meters
+    #[default]
+    Approximate,
+    /// Customers with a pending or confirmed booking of the user see the exact location,
+    /// everyone else the approximate one
+    ExactToBookedCustomers,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct LocationVisibilityUpdate {
+    pub location_visibility: LocationVisibility,
+}
+
+/// Location of a user at an instant of time, part of their trajectory
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct LocationPoint {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct UserResponse {
     pub id: Uuid,
     pub dni: String,
     pub email: String,
-    pub password: String,
+    /// Argon2 hash of the password. Never sent in the responses
+    #[serde(skip_serializing)]
+    #[schema(value_type = String)]
+    pub password: Secret,
     pub name: String,
     pub date_of_birth: chrono::NaiveDate,
     pub registered_at: chrono::DateTime<chrono::Utc>,
@@ -111,6 +396,74 @@ pub struct UserResponse {
     pub contact_number: String,
     pub category_id: Option<i64>,
     pub rol: Roles,
+    /// When the seller paused their listings. None when the seller is active
+    pub seller_paused_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub location_visibility: LocationVisibility,
+    /// Version of the user, sent in the If-Match header to update it
+    pub version: i32,
+}
+
+/// Currency of the prices saved before they had one
+pub const DEFAULT_CURRENCY: &str = "COP";
+
+/// Exact amount of money in a currency
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(try_from = "UncheckedMoney")]
+pub struct Money {
+    /// Decimal amount with up to two decimals. It's a string so no cents are lost
+    #[schema(value_type = String, example = "25000.50")]
+    #[serde(with = "rust_decimal::serde::str")]
+    pub amount: Decimal,
+    /// ISO 4217 code of the currency
+    #[schema(example = "COP")]
+    pub currency: String,
+}
+
+impl Money {
+    /// Decimals of the amounts, the minor units of the currencies. The price column is
+    /// NUMERIC(14, 2), so every amount has exactly these wherever it comes from
+    const MINOR_UNITS: u32 = 2;
+
+    /// Returns the money if the amount fits in a price and the currency is an ISO 4217 code.
+    /// The amount is rescaled to the minor units, so 10.5 and 10.500 are both 10.50
+    pub fn new(amount: Decimal, currency: String) -> Result<Self, &'static str> {
+        // Without the trailing zeros, so they don't count as decimals
+        let mut amount = amount.normalize();
+
+        if amount.is_sign_negative() {
+            return Err("AMOUNT CAN'T BE NEGATIVE");
+        }
+        if amount.scale() > Self::MINOR_UNITS {
+            return Err("AMOUNT CAN'T HAVE MORE THAN 2 DECIMALS");
+        }
+        // The price column is NUMERIC(14, 2)
+        if amount >= Decimal::from(1_000_000_000_000i64) {
+            return Err("AMOUNT IS TOO LARGE");
+        }
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("CURRENCY MUST BE AN ISO 4217 CODE");
+        }
+
+        amount.rescale(Self::MINOR_UNITS);
+
+        Ok(Money { amount, currency })
+    }
+}
+
+/// Money as it comes in a request, before being validated
+#[derive(Deserialize)]
+struct UncheckedMoney {
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: Decimal,
+    currency: String,
+}
+
+impl TryFrom<UncheckedMoney> for Money {
+    type Error = &'static str;
+
+    fn try_from(money: UncheckedMoney) -> Result<Self, Self::Error> {
+        Money::new(money.amount, money.currency)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
@@ -118,17 +471,341 @@ pub struct Service {
     pub id: Option<Uuid>,
     pub user_id: String,
     pub category_id: i64,
-    pub price: f64,
+    pub price: Money,
     pub description: String,
     pub modality: Modality,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct ServiceResponse {
     pub id: Uuid,
     pub user_id: String,
     pub category_id: i64,
-    pub price: f64,
+    pub price: Money,
+    pub description: String,
+    pub modality: Modality,
+    /// Number of users that have the service in their favorites
+    pub favorites_count: i64,
+    /// Version of the service, sent in the If-Match header to update it
+    pub version: i32,
+}
+
+/// Zone a seller travels to for a home-visit service. Positions are [longitude, latitude], like
+/// in GeoJSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type")]
+pub enum ServiceArea {
+    /// GeoJSON polygon. The first ring is the boundary and the rest are holes, each one closed
+    /// by repeating its first position at the end
+    Polygon { coordinates: Vec<Vec<Vec<f64>>> },
+    /// Every point within radius_meters of the center
+    Circle {
+        center: Vec<f64>,
+        radius_meters: f64,
+    },
+}
+
+/// First service published by a user that becomes a seller
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct OnboardingService {
+    pub price: Money,
     pub description: String,
     pub modality: Modality,
 }
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SellerOnboarding {
+    pub category_id: i64,
+    pub contact_number: String,
+    pub service: OnboardingService,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SellerOnboardingResponse {
+    pub user: UserResponse,
+    pub service: ServiceResponse,
+}
+
+/// How rates and comments are checked against the completed bookings of the reviewer
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "review_verification", rename_all = "lowercase")]
+pub enum ReviewVerification {
+    /// Reviews are never verified
+    Off,
+    /// Every review is accepted and marked as verified when the reviewer has a completed booking
+    Flag,
+    /// Only reviews of customers with a completed booking are accepted
+    Require,
+}
+
+/// Settings of the market that admins can change, the missing ones keep their value
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Settings {
+    /// Maximum number of services a seller can publish
+    #[schema(example = 5)]
+    pub max_services_per_seller: Option<i32>,
+    pub review_verification: Option<ReviewVerification>,
+    /// Rate a seller without rates gets in the reputation
+    #[schema(example = 3)]
+    pub reputation_prior_mean: Option<f32>,
+    /// How many rates the prior mean is worth in the reputation
+    #[schema(example = 10)]
+    pub reputation_prior_weight: Option<f32>,
+    /// Days after which a rate weighs half in the reputation
+    #[schema(example = 180)]
+    pub reputation_half_life_days: Option<i32>,
+    /// Days the history of locations of the users is kept
+    #[schema(example = 30)]
+    pub location_retention_days: Option<i32>,
+    /// Size in meters of the cells of the grid the approximate locations are snapped to
+    #[schema(example = 1000)]
+    pub location_grid_meters: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SettingsResponse {
+    pub max_services_per_seller: i32,
+    pub review_verification: ReviewVerification,
+    pub reputation_prior_mean: f32,
+    pub reputation_prior_weight: f32,
+    pub reputation_half_life_days: i32,
+    pub location_retention_days: i32,
+    pub location_grid_meters: i32,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Order of the sellers in the listings
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ToSchema)]
+pub enum SellerSort {
+    /// Highest reputation first
+    #[default]
+    Reputation,
+    /// Highest plain average of the rates first
+    Rating,
+    /// Latest registered first
+    Newest,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SellerSummary {
+    pub dni: String,
+    pub name: String,
+    pub category_id: Option<i64>,
+    /// Average of the rates weighted by their age and adjusted by the number of rates
+    pub reputation: f64,
+    /// Plain average of the rates, None when the seller has no rates
+    pub average_rate: Option<f64>,
+    pub rates_count: i64,
+}
+
+/// Hours of a day of the week the seller works, in the timezone of the seller
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct AvailabilityWindow {
+    pub weekday: Weekday,
+    #[schema(value_type = String, example = "08:00:00")]
+    pub start_time: chrono::NaiveTime,
+    #[schema(value_type = String, example = "12:00:00")]
+    pub end_time: chrono::NaiveTime,
+}
+
+/// Weekly schedule of a seller, it replaces the previous one
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WeeklySchedule {
+    /// IANA name of the timezone of the windows
+    #[schema(example = "America/Bogota")]
+    pub timezone: String,
+    pub windows: Vec<AvailabilityWindow>,
+}
+
+/// Date the seller doesn't work, like a holiday
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct AvailabilityException {
+    pub date: chrono::NaiveDate,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AvailabilityResponse {
+    pub seller_id: String,
+    pub timezone: String,
+    pub windows: Vec<AvailabilityWindow>,
+    pub exceptions: Vec<AvailabilityException>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TimeSlot {
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Booking of a service made by the authenticated customer
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Booking {
+    pub service_id: Uuid,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BookingResponse {
+    pub id: Uuid,
+    /// None when the service was deleted
+    pub service_id: Option<Uuid>,
+    pub customer_id: String,
+    pub seller_id: String,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    pub status: BookingStatus,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BookingStatusUpdate {
+    pub status: BookingStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+pub enum FavoriteKind {
+    Service,
+    Seller,
+}
+
+/// Service or seller in the favorites of a user
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct FavoriteResponse {
+    pub kind: FavoriteKind,
+    /// The service when the favorite is a service
+    pub service: Option<ServiceResponse>,
+    /// Dni of the seller when the favorite is a seller
+    pub seller_id: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[sqlx(type_name = "audit_action", rename_all = "lowercase")]
+pub enum AuditAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Change of a row recorded in the audit log
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub occurred_at: chrono::DateTime<chrono::Utc>,
+    /// Dni of the user who made the change, None for anonymous requests and maintenance tasks
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    /// Name of the table
+    pub entity: String,
+    /// Identifier of the row, with the values of composite keys joined by ':'
+    pub entity_id: String,
+    /// Row before the change, None for inserts. Passwords are never recorded
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<serde_json::Value>,
+    /// Row after the change, None for deletes
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn money(amount: &str, currency: &str) -> Result<Money, &'static str> {
+        Money::new(Decimal::from_str(amount).unwrap(), currency.to_string())
+    }
+
+    #[test]
+    fn trailing_zeros_dont_count_as_decimals() {
+        let price = money("25000.500", "COP").unwrap();
+
+        assert_eq!(price.amount, Decimal::new(2500050, 2));
+    }
+
+    #[test]
+    fn amounts_always_have_the_minor_units() {
+        for amount in ["10.5", "10.50", "10.500"] {
+            assert_eq!(money(amount, "COP").unwrap().amount.to_string(), "10.50");
+        }
+        assert_eq!(money("7", "COP").unwrap().amount.to_string(), "7.00");
+    }
+
+    #[test]
+    fn amount_is_rejected_when_negative() {
+        assert_eq!(money("-0.01", "COP"), Err("AMOUNT CAN'T BE NEGATIVE"));
+        assert_eq!(money("-10", "COP"), Err("AMOUNT CAN'T BE NEGATIVE"));
+    }
+
+    #[test]
+    fn negative_zero_is_zero() {
+        let price = money("-0.00", "COP").unwrap();
+
+        assert_eq!(price.amount.to_string(), "0.00");
+    }
+
+    #[test]
+    fn amount_is_rejected_with_more_than_two_decimals() {
+        assert_eq!(
+            money("0.001", "COP"),
+            Err("AMOUNT CAN'T HAVE MORE THAN 2 DECIMALS")
+        );
+        assert!(money("0.01", "COP").is_ok());
+    }
+
+    #[test]
+    fn amount_must_fit_in_the_price_column() {
+        assert!(money("999999999999.99", "COP").is_ok());
+        assert_eq!(money("1000000000000", "COP"), Err("AMOUNT IS TOO LARGE"));
+    }
+
+    #[test]
+    fn currency_must_be_three_uppercase_letters() {
+        for currency in ["cop", "CO", "COPS", "C0P", ""] {
+            assert_eq!(
+                money("1", currency),
+                Err("CURRENCY MUST BE AN ISO 4217 CODE")
+            );
+        }
+        assert!(money("1", "USD").is_ok());
+    }
+
+    #[test]
+    fn secrets_are_redacted_when_printed() {
+        let user: User = serde_json::from_value(serde_json::json!({
+            "dni": "1", "email": "a@b.co", "password": "hunter2", "name": "A",
+            "date_of_birth": "2000-01-01", "is_seller": false, "updated_at": null,
+            "latitude": null, "longitude": null, "contact_number": "3000000000",
+            "category_id": null, "rol": "User"
+        }))
+        .unwrap();
+
+        assert_eq!(user.password.expose(), "hunter2");
+        assert!(!format!("{:?}", user).contains("hunter2"));
+        assert!(format!("{:?}", user).contains("[REDACTED]"));
+        assert_eq!(user.password.to_string(), "[REDACTED]");
+    }
+
+    #[test]
+    fn secrets_keep_their_value_in_json() {
+        let secret = Secret::from("hunter2".to_string());
+
+        assert_eq!(serde_json::to_value(&secret).unwrap(), serde_json::json!("hunter2"));
+    }
+
+    #[test]
+    fn requests_are_validated_when_deserialized() {
+        let price: Money =
+            serde_json::from_str(r#"{"amount": "10.50", "currency": "COP"}"#).unwrap();
+        assert_eq!(
+            serde_json::to_value(&price).unwrap()["amount"],
+            serde_json::json!("10.50")
+        );
+
+        assert!(serde_json::from_str::<Money>(r#"{"amount": "-1", "currency": "COP"}"#).is_err());
+    }
+}